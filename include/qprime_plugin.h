@@ -0,0 +1,29 @@
+/* Plugin interface for adding builtin functions to the Q' interpreter.
+ * Build a shared library that exports qprime_plugin_init and load it with
+ * `qprime --plugin ./libradar.so script.qpr`. */
+#ifndef QPRIME_PLUGIN_H
+#define QPRIME_PLUGIN_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A builtin function. Receives `count` numeric arguments and stores its
+ * value in `*result`. Returns 0 on success; any other status stops the
+ * script, with `*error` (if set) pointing to a static message. */
+typedef int (*qprime_function)(const double *args, size_t count, double *result, const char **error);
+
+/* Registers `function` as the builtin `name`. */
+typedef void (*qprime_register)(void *host, const char *name, qprime_function function);
+
+/* Called once when the plugin is loaded; call `reg(host, ...)` for each
+ * builtin the plugin provides. */
+void qprime_plugin_init(void *host, qprime_register reg);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* QPRIME_PLUGIN_H */
@@ -0,0 +1,28 @@
+/* C interface to the Q' interpreter. Build the library with
+ * `cargo build --release --features cdylib` and link against
+ * target/release/libqprime.so (or .dylib / .dll). */
+#ifndef WEATHER_H
+#define WEATHER_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Runs a NUL-terminated, UTF-8 script, printing to stdout. Returns 0 on
+ * success, 1 for runtime errors, 2 for syntax errors and 64 when `src` is
+ * NULL or not UTF-8. */
+int weather_run(const char *src);
+
+/* Message of the error from the last failed weather_run on this thread, or
+ * NULL if it succeeded. Owned by the library; valid until the next
+ * weather_run or weather_clear_error. */
+const char *weather_last_error(void);
+
+/* Forgets the last error message. */
+void weather_clear_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* WEATHER_H */
@@ -1,41 +1,124 @@
+use crate::span::Spanned;
 use crate::token::Token;
 use num_rational::BigRational;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
-#[allow(dead_code)]
+// An AST node together with the source span it was parsed from
+pub type Node = Spanned<ASTNode>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[allow(dead_code, clippy::upper_case_acronyms)]
 pub enum ASTNode {
-    Block(Vec<ASTNode>),
-    Float(BigRational),
+    Block(Vec<Node>),
+    Float(#[serde(with = "crate::serialize::rational")] BigRational),
+    Quantity(#[serde(with = "crate::serialize::rational")] BigRational, String), // `25C`, unit as written
+    NaN,
+    Inf,
+    Nil,
     Identifier(String),
     StringLiteral(String),
-    BinaryOp(Box<ASTNode>, Token, Box<ASTNode>),
-    Assignment(String, Box<ASTNode>),
-    Call(String, Vec<ASTNode>),
-    Function(String, Vec<String>, Box<ASTNode>),
-    Import(String),
-    Print(Box<ASTNode>),
-    If(Box<ASTNode>, Box<ASTNode>, Option<Box<ASTNode>>), // condition, then, else
-    DewPoint(Box<ASTNode>, Box<ASTNode>), // temperature, humidity
-    FToC(Box<ASTNode>), // fahrenheit -> celsius
-    CToF(Box<ASTNode>), // celsius -> fahrenheit
-    CToK(Box<ASTNode>), // celsius -> kelvin
-    KToC(Box<ASTNode>), // kelvin -> celsius
-    FToK(Box<ASTNode>), // fahrenheit -> kelvin
-    KToF(Box<ASTNode>), // kelvin -> fahrenheit
-    PauliX(Box<ASTNode>),
-    PauliY(Box<ASTNode>),
-    PauliZ(Box<ASTNode>),
-    Hadamard(Box<ASTNode>),
-    CNot(Box<ASTNode>, Box<ASTNode>),
-    Qubit(Box<ASTNode>, Box<ASTNode>), // Create a qubit with a given state
-    MeasureQubit(Box<ASTNode>), // Measure a qubit
-    ResetQubit(Box<ASTNode>), // Reset a qubit
-    Toffoli(Box<ASTNode>, Box<ASTNode>, Box<ASTNode>), // Toffoli gate
-    SWAP(Box<ASTNode>, Box<ASTNode>), // SWAP gate
-    Phase(Box<ASTNode>), // Phase gate
-    TGate(Box<ASTNode>), // T gate
-    SGate(Box<ASTNode>), // S gate
-    Fredkin(Box<ASTNode>, Box<ASTNode>, Box<ASTNode>), // Fredkin gate
+    BinaryOp(Box<Node>, Token, Box<Node>),
+    UnaryOp(Token, Box<Node>), // `-x`, `not x`
+    Index(Box<Node>, Box<Node>), // `list[index]`
+    Assignment(String, Box<Node>),
+    Call(String, Vec<Node>),
+    Function(String, Vec<String>, Box<Node>),
+    Export(Box<Node>), // function definition or assignment visible to importers
+    Import(String, Option<Vec<String>>), // module path, names to import (everything if None)
+    Print(Box<Node>),
+    If(Box<Node>, Box<Node>, Option<Box<Node>>), // condition, then, else
+    DewPoint(Vec<Node>), // temperature, humidity, then optionally the formula
+    ApparentTemp(Box<Node>, Box<Node>, Box<Node>), // temperature, humidity, wind speed
+    Svp(Vec<Node>), // temperature, then optionally the formula and the surface
+    MixingRatio(Box<Node>, Box<Node>, Box<Node>), // temperature, humidity, pressure
+    SpecificHumidity(Box<Node>, Box<Node>, Box<Node>), // temperature, humidity, pressure
+    PressureAtAltitude(Box<Node>, Box<Node>, Box<Node>), // pressure at sea level, altitude, temperature
+    Isa(Box<Node>), // standard atmosphere at an altitude
+    Dalr, // dry adiabatic lapse rate
+    Malr(Box<Node>, Box<Node>), // temperature, pressure
+    DryAdiabat(Box<Node>, Box<Node>, Box<Node>), // starting temperature and pressure, final pressure
+    Et0(Vec<Node>), // temperature, humidity, wind speed, net radiation, pressure
+    Hdd(Box<Node>, Box<Node>), // daily mean temperatures, base temperature
+    Cdd(Box<Node>, Box<Node>), // daily mean temperatures, base temperature
+    WindComponents(Box<Node>, Box<Node>), // speed, direction -> [u, v]
+    WindFromComponents(Box<Node>, Box<Node>), // u, v -> [speed, direction]
+    MmToIn(Box<Node>), // millimetres -> inches
+    InToMm(Box<Node>), // inches -> millimetres
+    MmhToInh(Box<Node>), // mm/h -> in/h
+    InhToMmh(Box<Node>), // in/h -> mm/h
+    Accumulation(Box<Node>, Box<Node>), // rates, hours per interval
+    RunningTotal(Box<Node>), // amounts per interval -> totals so far
+    Events(Vec<Node>), // series, threshold, minimum length, then optionally "above" or "below"
+    Percentile(Box<Node>, Box<Node>), // values, percent
+    PercentileRank(Box<Node>, Box<Node>), // values, value
+    Anomaly(Box<Node>, Box<Node>), // values, normals (a list or one number)
+    RollingMean(Box<Node>, Box<Node>), // values, window
+    Spi(Box<Node>, Box<Node>), // monthly precipitation, timescale in months
+    Windrose(Box<Node>, Box<Node>, Box<Node>), // directions, speeds, number of sectors
+    Slr(Box<Node>), // snow-to-liquid ratio at a temperature
+    Snowfall(Box<Node>, Box<Node>), // liquid equivalent, temperature
+    VisConvert(Box<Node>, Box<Node>, Box<Node>), // visibility, from unit, to unit
+    FogCategory(Vec<Node>), // visibility in m, then optionally the humidity
+    SolarZenith(Box<Node>, Box<Node>, Box<Node>), // latitude, longitude, Unix timestamp
+    SolarDeclination(Box<Node>), // day of the year
+    UvIndex(Vec<Node>), // solar zenith angle, then optionally the ozone column
+    Coriolis(Box<Node>), // latitude
+    PrecipitableWater(Box<Node>, Box<Node>), // pressures, mixing ratios of a sounding
+    SpeedOfSound(Box<Node>, Box<Node>), // temperature, humidity
+    Altimeter(Box<Node>, Box<Node>), // station pressure, elevation
+    RhFromWetbulb(Box<Node>, Box<Node>, Box<Node>), // dry-bulb and wet-bulb temperatures, pressure
+    Haversine(Vec<Node>), // latitude and longitude of two points
+    Bearing(Vec<Node>), // latitude and longitude of two points
+    ParseCoord(Box<Node>), // coordinate text
+    FormatCoord(Vec<Node>), // latitude, longitude, then optionally the style
+    Station(Box<Node>), // ICAO or WMO id
+    Cloudbase(Vec<Node>), // temperature, dew point, then optionally the unit
+    Convert(Vec<Node>), // value, from unit, to unit; or a quantity and the unit to convert it to
+    FToC(Box<Node>), // fahrenheit -> celsius
+    CToF(Box<Node>), // celsius -> fahrenheit
+    CToK(Box<Node>), // celsius -> kelvin
+    KToC(Box<Node>), // kelvin -> celsius
+    FToK(Box<Node>), // fahrenheit -> kelvin
+    KToF(Box<Node>), // kelvin -> fahrenheit
+    IsNaN(Box<Node>),
+    Assert(Box<Node>, Box<Node>), // condition, message
+    AssertApprox(Box<Node>, Box<Node>, Box<Node>), // actual, expected, tolerance
+    Args, // Extra command-line arguments
+    Len(Box<Node>),
+    Env(Box<Node>), // Environment variable lookup
+    Secret(Box<Node>), // API key or other secret, by name
+    ParseJson(Box<Node>), // JSON text
+    ToJson(Box<Node>), // value to write as JSON text
+    ReadJson(Box<Node>), // path of a JSON file
+    ReadGrib(Box<Node>, Box<Node>, Box<Node>), // path of a GRIB2 file, parameter, level
+    ReadNc(Box<Node>, Box<Node>), // path of a NetCDF file, variable
+    ToTimezone(Box<Node>, Box<Node>), // Unix timestamp, IANA time zone
+    UtcOffset(Box<Node>, Box<Node>), // Unix timestamp, IANA time zone
+    Rand, // Uniform random number in [0, 1)
+    RandInt(Box<Node>, Box<Node>), // lowest, highest
+    RandN(Box<Node>, Box<Node>), // mean, standard deviation
+    Seed(Box<Node>), // Seed of the random number generator
+    Sleep(Box<Node>), // seconds
+    WriteJson(Box<Node>, Box<Node>), // path, value
+    Fetch(Box<Node>), // URL
+    Forecast(Box<Node>, Box<Node>), // latitude, longitude
+    SetConstant(Box<Node>, Box<Node>), // constant name, new value
+    Format(Box<Node>, Box<Node>), // value, significant digits
+    Pmap(String, Box<Node>), // function applied to each item of a list, in parallel
+    PauliX(Box<Node>),
+    PauliY(Box<Node>),
+    PauliZ(Box<Node>),
+    Hadamard(Box<Node>),
+    CNot(Box<Node>, Box<Node>),
+    Qubit(Box<Node>, Box<Node>), // Create a qubit with a given state
+    MeasureQubit(Box<Node>), // Measure a qubit
+    ResetQubit(Box<Node>), // Reset a qubit
+    Toffoli(Box<Node>, Box<Node>, Box<Node>), // Toffoli gate
+    SWAP(Box<Node>, Box<Node>), // SWAP gate
+    Phase(Box<Node>), // Phase gate
+    TGate(Box<Node>), // T gate
+    SGate(Box<Node>), // S gate
+    Fredkin(Box<Node>, Box<Node>, Box<Node>), // Fredkin gate
     Pi,
     Kelvin,
     RD,
@@ -46,6 +129,482 @@ pub enum ASTNode {
     RhoAir,
     RhoWater,
     G,
-    GreaterThan(Box<ASTNode>, Box<ASTNode>),
-    LessThan(Box<ASTNode>, Box<ASTNode>),
+    Omega,
+    Rv,
+    Epsilon,
+    Sigma,
+    Solar,
+    Lf,
+    GreaterThan(Box<Node>, Box<Node>),
+    LessThan(Box<Node>, Box<Node>),
+}
+
+// Children are dropped on a growable stack, so freeing a deeply nested
+// tree can't overflow the native stack either
+impl Drop for ASTNode {
+    fn drop(&mut self) {
+        for child in self.children_mut() {
+            let child = std::mem::replace(&mut child.node, ASTNode::Nil);
+            crate::stack::grow(|| drop(child));
+        }
+    }
+}
+
+impl ASTNode {
+    // Variant name, used when dumping the tree
+    pub fn name(&self) -> &'static str {
+        match self {
+            ASTNode::Block(_) => "Block",
+            ASTNode::Float(_) => "Float",
+            ASTNode::Quantity(..) => "Quantity",
+            ASTNode::NaN => "NaN",
+            ASTNode::Inf => "Inf",
+            ASTNode::Nil => "Nil",
+            ASTNode::Identifier(_) => "Identifier",
+            ASTNode::StringLiteral(_) => "StringLiteral",
+            ASTNode::BinaryOp(..) => "BinaryOp",
+            ASTNode::UnaryOp(..) => "UnaryOp",
+            ASTNode::Index(..) => "Index",
+            ASTNode::Assignment(..) => "Assignment",
+            ASTNode::Call(..) => "Call",
+            ASTNode::Function(..) => "Function",
+            ASTNode::Import(..) => "Import",
+            ASTNode::Export(_) => "Export",
+            ASTNode::Print(_) => "Print",
+            ASTNode::If(..) => "If",
+            ASTNode::DewPoint(..) => "DewPoint",
+            ASTNode::ApparentTemp(..) => "ApparentTemp",
+            ASTNode::Svp(_) => "Svp",
+            ASTNode::MixingRatio(..) => "MixingRatio",
+            ASTNode::SpecificHumidity(..) => "SpecificHumidity",
+            ASTNode::PressureAtAltitude(..) => "PressureAtAltitude",
+            ASTNode::Isa(_) => "Isa",
+            ASTNode::Dalr => "Dalr",
+            ASTNode::Malr(..) => "Malr",
+            ASTNode::DryAdiabat(..) => "DryAdiabat",
+            ASTNode::Et0(_) => "Et0",
+            ASTNode::Hdd(..) => "Hdd",
+            ASTNode::Cdd(..) => "Cdd",
+            ASTNode::WindComponents(..) => "WindComponents",
+            ASTNode::WindFromComponents(..) => "WindFromComponents",
+            ASTNode::MmToIn(_) => "MmToIn",
+            ASTNode::InToMm(_) => "InToMm",
+            ASTNode::MmhToInh(_) => "MmhToInh",
+            ASTNode::InhToMmh(_) => "InhToMmh",
+            ASTNode::Accumulation(..) => "Accumulation",
+            ASTNode::RunningTotal(_) => "RunningTotal",
+            ASTNode::Events(_) => "Events",
+            ASTNode::Percentile(..) => "Percentile",
+            ASTNode::PercentileRank(..) => "PercentileRank",
+            ASTNode::Anomaly(..) => "Anomaly",
+            ASTNode::RollingMean(..) => "RollingMean",
+            ASTNode::Spi(..) => "Spi",
+            ASTNode::Windrose(..) => "Windrose",
+            ASTNode::Slr(_) => "Slr",
+            ASTNode::Snowfall(..) => "Snowfall",
+            ASTNode::VisConvert(..) => "VisConvert",
+            ASTNode::FogCategory(_) => "FogCategory",
+            ASTNode::SolarZenith(..) => "SolarZenith",
+            ASTNode::SolarDeclination(_) => "SolarDeclination",
+            ASTNode::UvIndex(_) => "UvIndex",
+            ASTNode::Coriolis(_) => "Coriolis",
+            ASTNode::PrecipitableWater(..) => "PrecipitableWater",
+            ASTNode::SpeedOfSound(..) => "SpeedOfSound",
+            ASTNode::Altimeter(..) => "Altimeter",
+            ASTNode::RhFromWetbulb(..) => "RhFromWetbulb",
+            ASTNode::Haversine(_) => "Haversine",
+            ASTNode::Bearing(_) => "Bearing",
+            ASTNode::ParseCoord(_) => "ParseCoord",
+            ASTNode::FormatCoord(_) => "FormatCoord",
+            ASTNode::Station(_) => "Station",
+            ASTNode::Cloudbase(_) => "Cloudbase",
+            ASTNode::Convert(..) => "Convert",
+            ASTNode::FToC(_) => "FToC",
+            ASTNode::CToF(_) => "CToF",
+            ASTNode::CToK(_) => "CToK",
+            ASTNode::KToC(_) => "KToC",
+            ASTNode::FToK(_) => "FToK",
+            ASTNode::KToF(_) => "KToF",
+            ASTNode::IsNaN(_) => "IsNaN",
+            ASTNode::Assert(..) => "Assert",
+            ASTNode::SetConstant(..) => "SetConstant",
+            ASTNode::AssertApprox(..) => "AssertApprox",
+            ASTNode::Args => "Args",
+            ASTNode::Len(_) => "Len",
+            ASTNode::Env(_) => "Env",
+            ASTNode::Secret(_) => "Secret",
+            ASTNode::ParseJson(_) => "ParseJson",
+            ASTNode::ToJson(_) => "ToJson",
+            ASTNode::ReadJson(_) => "ReadJson",
+            ASTNode::ReadGrib(..) => "ReadGrib",
+            ASTNode::ReadNc(..) => "ReadNc",
+            ASTNode::ToTimezone(..) => "ToTimezone",
+            ASTNode::UtcOffset(..) => "UtcOffset",
+            ASTNode::Rand => "Rand",
+            ASTNode::RandInt(..) => "RandInt",
+            ASTNode::RandN(..) => "RandN",
+            ASTNode::Seed(_) => "Seed",
+            ASTNode::Sleep(_) => "Sleep",
+            ASTNode::WriteJson(..) => "WriteJson",
+            ASTNode::Fetch(_) => "Fetch",
+            ASTNode::Forecast(..) => "Forecast",
+            ASTNode::Format(..) => "Format",
+            ASTNode::Pmap(..) => "Pmap",
+            ASTNode::PauliX(_) => "PauliX",
+            ASTNode::PauliY(_) => "PauliY",
+            ASTNode::PauliZ(_) => "PauliZ",
+            ASTNode::Hadamard(_) => "Hadamard",
+            ASTNode::CNot(..) => "CNot",
+            ASTNode::Qubit(..) => "Qubit",
+            ASTNode::MeasureQubit(_) => "MeasureQubit",
+            ASTNode::ResetQubit(_) => "ResetQubit",
+            ASTNode::Toffoli(..) => "Toffoli",
+            ASTNode::SWAP(..) => "SWAP",
+            ASTNode::Phase(_) => "Phase",
+            ASTNode::TGate(_) => "TGate",
+            ASTNode::SGate(_) => "SGate",
+            ASTNode::Fredkin(..) => "Fredkin",
+            ASTNode::Pi => "Pi",
+            ASTNode::Kelvin => "Kelvin",
+            ASTNode::RD => "RD",
+            ASTNode::CP => "CP",
+            ASTNode::P0 => "P0",
+            ASTNode::LV => "LV",
+            ASTNode::CW => "CW",
+            ASTNode::RhoAir => "RhoAir",
+            ASTNode::RhoWater => "RhoWater",
+            ASTNode::G => "G",
+            ASTNode::Omega => "Omega",
+            ASTNode::Rv => "Rv",
+            ASTNode::Epsilon => "Epsilon",
+            ASTNode::Sigma => "Sigma",
+            ASTNode::Solar => "Solar",
+            ASTNode::Lf => "Lf",
+            ASTNode::GreaterThan(..) => "GreaterThan",
+            ASTNode::LessThan(..) => "LessThan",
+        }
+    }
+
+    // Source keyword of builtin functions and constants, e.g. `ftoc` or `_pi_`
+    pub fn keyword(&self) -> Option<&'static str> {
+        match self {
+            ASTNode::NaN => Some("nan"),
+            ASTNode::Inf => Some("inf"),
+            ASTNode::Nil => Some("nil"),
+            ASTNode::Print(..) => Some("print"),
+            ASTNode::DewPoint(..) => Some("dewpoint"),
+            ASTNode::ApparentTemp(..) => Some("apparent_temp"),
+            ASTNode::Svp(..) => Some("svp"),
+            ASTNode::MixingRatio(..) => Some("mixing_ratio"),
+            ASTNode::SpecificHumidity(..) => Some("specific_humidity"),
+            ASTNode::PressureAtAltitude(..) => Some("pressure_at_altitude"),
+            ASTNode::Isa(..) => Some("isa"),
+            ASTNode::Dalr => Some("dalr"),
+            ASTNode::Malr(..) => Some("malr"),
+            ASTNode::DryAdiabat(..) => Some("temp_along_dry_adiabat"),
+            ASTNode::Et0(..) => Some("et0"),
+            ASTNode::Hdd(..) => Some("hdd"),
+            ASTNode::Cdd(..) => Some("cdd"),
+            ASTNode::WindComponents(..) => Some("wind_components"),
+            ASTNode::WindFromComponents(..) => Some("wind_from_components"),
+            ASTNode::MmToIn(..) => Some("mmtoin"),
+            ASTNode::InToMm(..) => Some("intomm"),
+            ASTNode::MmhToInh(..) => Some("mmhtoinh"),
+            ASTNode::InhToMmh(..) => Some("inhtommh"),
+            ASTNode::Accumulation(..) => Some("accumulation"),
+            ASTNode::RunningTotal(..) => Some("running_total"),
+            ASTNode::Events(..) => Some("events"),
+            ASTNode::Percentile(..) => Some("percentile"),
+            ASTNode::PercentileRank(..) => Some("percentile_rank"),
+            ASTNode::Anomaly(..) => Some("anomaly"),
+            ASTNode::RollingMean(..) => Some("rolling_mean"),
+            ASTNode::Spi(..) => Some("spi"),
+            ASTNode::Windrose(..) => Some("windrose"),
+            ASTNode::Slr(..) => Some("slr"),
+            ASTNode::Snowfall(..) => Some("snowfall"),
+            ASTNode::VisConvert(..) => Some("visconvert"),
+            ASTNode::FogCategory(..) => Some("fog_category"),
+            ASTNode::SolarZenith(..) => Some("solar_zenith"),
+            ASTNode::SolarDeclination(..) => Some("solar_declination"),
+            ASTNode::UvIndex(..) => Some("uv_index"),
+            ASTNode::Coriolis(..) => Some("coriolis"),
+            ASTNode::PrecipitableWater(..) => Some("precipitable_water"),
+            ASTNode::SpeedOfSound(..) => Some("speed_of_sound"),
+            ASTNode::Altimeter(..) => Some("altimeter"),
+            ASTNode::RhFromWetbulb(..) => Some("rh_from_wetbulb"),
+            ASTNode::Haversine(..) => Some("haversine"),
+            ASTNode::Bearing(..) => Some("bearing"),
+            ASTNode::ParseCoord(..) => Some("parse_coord"),
+            ASTNode::FormatCoord(..) => Some("format_coord"),
+            ASTNode::Station(..) => Some("station"),
+            ASTNode::Cloudbase(..) => Some("cloudbase"),
+            ASTNode::Convert(..) => Some("convert"),
+            ASTNode::FToC(..) => Some("ftoc"),
+            ASTNode::CToF(..) => Some("ctof"),
+            ASTNode::CToK(..) => Some("ctok"),
+            ASTNode::KToC(..) => Some("ktoc"),
+            ASTNode::FToK(..) => Some("ftok"),
+            ASTNode::KToF(..) => Some("ktof"),
+            ASTNode::IsNaN(..) => Some("isnan"),
+            ASTNode::Assert(..) => Some("assert"),
+            ASTNode::SetConstant(..) => Some("set_constant"),
+            ASTNode::AssertApprox(..) => Some("assert_approx"),
+            ASTNode::Args => Some("args"),
+            ASTNode::Len(..) => Some("len"),
+            ASTNode::Env(..) => Some("env"),
+            ASTNode::Secret(..) => Some("secret"),
+            ASTNode::ParseJson(..) => Some("parse_json"),
+            ASTNode::ToJson(..) => Some("to_json"),
+            ASTNode::ReadJson(..) => Some("readjson"),
+            ASTNode::ReadGrib(..) => Some("readgrib"),
+            ASTNode::ReadNc(..) => Some("readnc"),
+            ASTNode::ToTimezone(..) => Some("to_timezone"),
+            ASTNode::UtcOffset(..) => Some("utc_offset"),
+            ASTNode::Rand => Some("rand"),
+            ASTNode::RandInt(..) => Some("randint"),
+            ASTNode::RandN(..) => Some("randn"),
+            ASTNode::Seed(..) => Some("seed"),
+            ASTNode::Sleep(..) => Some("sleep"),
+            ASTNode::WriteJson(..) => Some("writejson"),
+            ASTNode::Fetch(..) => Some("fetch"),
+            ASTNode::Forecast(..) => Some("forecast"),
+            ASTNode::Format(..) => Some("format"),
+            ASTNode::Pmap(..) => Some("pmap"),
+            ASTNode::PauliX(..) => Some("pauli_x"),
+            ASTNode::PauliY(..) => Some("pauli_y"),
+            ASTNode::PauliZ(..) => Some("pauli_z"),
+            ASTNode::Hadamard(..) => Some("hadamard"),
+            ASTNode::CNot(..) => Some("cnot"),
+            ASTNode::Qubit(..) => Some("qubit"),
+            ASTNode::MeasureQubit(..) => Some("measure"),
+            ASTNode::ResetQubit(..) => Some("reset_qubit"),
+            ASTNode::Toffoli(..) => Some("toffoli"),
+            ASTNode::SWAP(..) => Some("swap_qubits"),
+            ASTNode::Phase(..) => Some("phase"),
+            ASTNode::TGate(..) => Some("t_gate"),
+            ASTNode::SGate(..) => Some("s_gate"),
+            ASTNode::Fredkin(..) => Some("fredkin"),
+            ASTNode::Pi => Some("_pi_"),
+            ASTNode::Kelvin => Some("_kelvin_"),
+            ASTNode::RD => Some("_rd_"),
+            ASTNode::CP => Some("_cp_"),
+            ASTNode::P0 => Some("_p0_"),
+            ASTNode::LV => Some("_lv_"),
+            ASTNode::CW => Some("_cw_"),
+            ASTNode::RhoAir => Some("_rho_air_"),
+            ASTNode::RhoWater => Some("_rho_water_"),
+            ASTNode::G => Some("_g_"),
+            ASTNode::Omega => Some("_omega_"),
+            ASTNode::Rv => Some("_rv_"),
+            ASTNode::Epsilon => Some("_epsilon_"),
+            ASTNode::Sigma => Some("_sigma_"),
+            ASTNode::Solar => Some("_solar_"),
+            ASTNode::Lf => Some("_lf_"),
+            _ => None,
+        }
+    }
+
+    // Direct child nodes, in source order
+    pub fn children(&self) -> Vec<&Node> {
+        match self {
+            ASTNode::Block(nodes) | ASTNode::Call(_, nodes) | ASTNode::DewPoint(nodes) | ASTNode::Svp(nodes) | ASTNode::Et0(nodes) | ASTNode::Events(nodes) | ASTNode::FogCategory(nodes) | ASTNode::UvIndex(nodes) | ASTNode::Haversine(nodes) | ASTNode::Bearing(nodes) | ASTNode::FormatCoord(nodes) | ASTNode::Cloudbase(nodes) | ASTNode::Convert(nodes) => nodes.iter().collect(),
+            ASTNode::Assignment(_, node)
+            | ASTNode::Function(_, _, node)
+            | ASTNode::Export(node)
+            | ASTNode::UnaryOp(_, node)
+            | ASTNode::Print(node)
+            | ASTNode::FToC(node)
+            | ASTNode::CToF(node)
+            | ASTNode::CToK(node)
+            | ASTNode::KToC(node)
+            | ASTNode::FToK(node)
+            | ASTNode::KToF(node)
+            | ASTNode::IsNaN(node)
+            | ASTNode::Isa(node)
+            | ASTNode::MmToIn(node)
+            | ASTNode::InToMm(node)
+            | ASTNode::MmhToInh(node)
+            | ASTNode::InhToMmh(node)
+            | ASTNode::RunningTotal(node)
+            | ASTNode::Slr(node)
+            | ASTNode::SolarDeclination(node)
+            | ASTNode::Coriolis(node)
+            | ASTNode::ParseCoord(node)
+            | ASTNode::Station(node)
+            | ASTNode::Len(node)
+            | ASTNode::Pmap(_, node)
+            | ASTNode::Env(node)
+            | ASTNode::Secret(node)
+            | ASTNode::ParseJson(node)
+            | ASTNode::ToJson(node)
+            | ASTNode::ReadJson(node)
+            | ASTNode::Fetch(node)
+            | ASTNode::Seed(node)
+            | ASTNode::Sleep(node)
+            | ASTNode::PauliX(node)
+            | ASTNode::PauliY(node)
+            | ASTNode::PauliZ(node)
+            | ASTNode::Hadamard(node)
+            | ASTNode::MeasureQubit(node)
+            | ASTNode::ResetQubit(node)
+            | ASTNode::Phase(node)
+            | ASTNode::TGate(node)
+            | ASTNode::SGate(node) => vec![node],
+            ASTNode::BinaryOp(left, _, right)
+            | ASTNode::Malr(left, right)
+            | ASTNode::Hdd(left, right)
+            | ASTNode::Cdd(left, right)
+            | ASTNode::WindComponents(left, right)
+            | ASTNode::WindFromComponents(left, right)
+            | ASTNode::Accumulation(left, right)
+            | ASTNode::Percentile(left, right)
+            | ASTNode::PercentileRank(left, right)
+            | ASTNode::Anomaly(left, right)
+            | ASTNode::RollingMean(left, right)
+            | ASTNode::Spi(left, right)
+            | ASTNode::Snowfall(left, right)
+            | ASTNode::PrecipitableWater(left, right)
+            | ASTNode::SpeedOfSound(left, right)
+            | ASTNode::Altimeter(left, right)
+            | ASTNode::Format(left, right)
+            | ASTNode::Assert(left, right)
+            | ASTNode::SetConstant(left, right)
+            | ASTNode::WriteJson(left, right)
+            | ASTNode::Forecast(left, right)
+            | ASTNode::ReadNc(left, right)
+            | ASTNode::ToTimezone(left, right)
+            | ASTNode::UtcOffset(left, right)
+            | ASTNode::RandInt(left, right)
+            | ASTNode::RandN(left, right)
+            | ASTNode::Index(left, right)
+            | ASTNode::CNot(left, right)
+            | ASTNode::Qubit(left, right)
+            | ASTNode::SWAP(left, right)
+            | ASTNode::GreaterThan(left, right)
+            | ASTNode::LessThan(left, right) => vec![left, right],
+            ASTNode::Toffoli(first, second, third)
+            | ASTNode::Fredkin(first, second, third)
+            | ASTNode::ApparentTemp(first, second, third)
+            | ASTNode::MixingRatio(first, second, third)
+            | ASTNode::SpecificHumidity(first, second, third)
+            | ASTNode::PressureAtAltitude(first, second, third)
+            | ASTNode::DryAdiabat(first, second, third)
+            | ASTNode::VisConvert(first, second, third)
+            | ASTNode::SolarZenith(first, second, third)
+            | ASTNode::RhFromWetbulb(first, second, third)
+            | ASTNode::ReadGrib(first, second, third)
+            | ASTNode::Windrose(first, second, third)
+            | ASTNode::AssertApprox(first, second, third) => vec![first, second, third],
+            ASTNode::If(condition, then_branch, else_branch) => {
+                let mut children = vec![condition.as_ref(), then_branch.as_ref()];
+                if let Some(else_branch) = else_branch {
+                    children.push(else_branch);
+                }
+                children
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    // Mutable access to the direct child nodes, in source order
+    pub fn children_mut(&mut self) -> Vec<&mut Node> {
+        match self {
+            ASTNode::Block(nodes) | ASTNode::Call(_, nodes) | ASTNode::DewPoint(nodes) | ASTNode::Svp(nodes) | ASTNode::Et0(nodes) | ASTNode::Events(nodes) | ASTNode::FogCategory(nodes) | ASTNode::UvIndex(nodes) | ASTNode::Haversine(nodes) | ASTNode::Bearing(nodes) | ASTNode::FormatCoord(nodes) | ASTNode::Cloudbase(nodes) | ASTNode::Convert(nodes) => nodes.iter_mut().collect(),
+            ASTNode::Assignment(_, node)
+            | ASTNode::Function(_, _, node)
+            | ASTNode::Export(node)
+            | ASTNode::UnaryOp(_, node)
+            | ASTNode::Print(node)
+            | ASTNode::FToC(node)
+            | ASTNode::CToF(node)
+            | ASTNode::CToK(node)
+            | ASTNode::KToC(node)
+            | ASTNode::FToK(node)
+            | ASTNode::KToF(node)
+            | ASTNode::IsNaN(node)
+            | ASTNode::Isa(node)
+            | ASTNode::MmToIn(node)
+            | ASTNode::InToMm(node)
+            | ASTNode::MmhToInh(node)
+            | ASTNode::InhToMmh(node)
+            | ASTNode::RunningTotal(node)
+            | ASTNode::Slr(node)
+            | ASTNode::SolarDeclination(node)
+            | ASTNode::Coriolis(node)
+            | ASTNode::ParseCoord(node)
+            | ASTNode::Station(node)
+            | ASTNode::Len(node)
+            | ASTNode::Pmap(_, node)
+            | ASTNode::Env(node)
+            | ASTNode::Secret(node)
+            | ASTNode::ParseJson(node)
+            | ASTNode::ToJson(node)
+            | ASTNode::ReadJson(node)
+            | ASTNode::Fetch(node)
+            | ASTNode::Seed(node)
+            | ASTNode::Sleep(node)
+            | ASTNode::PauliX(node)
+            | ASTNode::PauliY(node)
+            | ASTNode::PauliZ(node)
+            | ASTNode::Hadamard(node)
+            | ASTNode::MeasureQubit(node)
+            | ASTNode::ResetQubit(node)
+            | ASTNode::Phase(node)
+            | ASTNode::TGate(node)
+            | ASTNode::SGate(node) => vec![node],
+            ASTNode::BinaryOp(left, _, right)
+            | ASTNode::Malr(left, right)
+            | ASTNode::Hdd(left, right)
+            | ASTNode::Cdd(left, right)
+            | ASTNode::WindComponents(left, right)
+            | ASTNode::WindFromComponents(left, right)
+            | ASTNode::Accumulation(left, right)
+            | ASTNode::Percentile(left, right)
+            | ASTNode::PercentileRank(left, right)
+            | ASTNode::Anomaly(left, right)
+            | ASTNode::RollingMean(left, right)
+            | ASTNode::Spi(left, right)
+            | ASTNode::Snowfall(left, right)
+            | ASTNode::PrecipitableWater(left, right)
+            | ASTNode::SpeedOfSound(left, right)
+            | ASTNode::Altimeter(left, right)
+            | ASTNode::Format(left, right)
+            | ASTNode::Assert(left, right)
+            | ASTNode::SetConstant(left, right)
+            | ASTNode::WriteJson(left, right)
+            | ASTNode::Forecast(left, right)
+            | ASTNode::ReadNc(left, right)
+            | ASTNode::ToTimezone(left, right)
+            | ASTNode::UtcOffset(left, right)
+            | ASTNode::RandInt(left, right)
+            | ASTNode::RandN(left, right)
+            | ASTNode::Index(left, right)
+            | ASTNode::CNot(left, right)
+            | ASTNode::Qubit(left, right)
+            | ASTNode::SWAP(left, right)
+            | ASTNode::GreaterThan(left, right)
+            | ASTNode::LessThan(left, right) => vec![left, right],
+            ASTNode::Toffoli(first, second, third)
+            | ASTNode::Fredkin(first, second, third)
+            | ASTNode::ApparentTemp(first, second, third)
+            | ASTNode::MixingRatio(first, second, third)
+            | ASTNode::SpecificHumidity(first, second, third)
+            | ASTNode::PressureAtAltitude(first, second, third)
+            | ASTNode::DryAdiabat(first, second, third)
+            | ASTNode::VisConvert(first, second, third)
+            | ASTNode::SolarZenith(first, second, third)
+            | ASTNode::RhFromWetbulb(first, second, third)
+            | ASTNode::ReadGrib(first, second, third)
+            | ASTNode::Windrose(first, second, third)
+            | ASTNode::AssertApprox(first, second, third) => vec![first, second, third],
+            ASTNode::If(condition, then_branch, else_branch) => {
+                let mut children = vec![condition.as_mut(), then_branch.as_mut()];
+                if let Some(else_branch) = else_branch {
+                    children.push(else_branch);
+                }
+                children
+            }
+            _ => Vec::new(),
+        }
+    }
 }
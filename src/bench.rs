@@ -0,0 +1,80 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+pub struct Summary {
+    pub mean: Duration,
+    pub median: Duration,
+    pub p99: Duration,
+}
+
+impl Summary {
+    fn from_samples(mut samples: Vec<Duration>) -> Self {
+        samples.sort();
+        let total: Duration = samples.iter().sum();
+        let percentile = |p: usize| samples[((samples.len() * p).div_ceil(100)).saturating_sub(1)];
+        Self {
+            mean: total / samples.len() as u32,
+            median: percentile(50),
+            p99: percentile(99),
+        }
+    }
+
+    fn fields(&self) -> [(&'static str, Duration); 3] {
+        [("mean", self.mean), ("median", self.median), ("p99", self.p99)]
+    }
+
+    // Baseline file format: one `name nanoseconds` pair per line
+    pub fn save(&self, path: &Path) {
+        let text: String = self.fields().iter().map(|(name, value)| format!("{} {}\n", name, value.as_nanos())).collect();
+        fs::write(path, text).expect("Failed to write baseline file");
+    }
+
+    pub fn load(path: &Path) -> Self {
+        let text = fs::read_to_string(path).expect("Failed to read baseline file");
+        let field = |name: &str| {
+            text.lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(' ')?.trim().parse().ok())
+                .map(Duration::from_nanos)
+                .unwrap_or_else(|| panic!("Baseline file '{}' has no '{}' entry", path.display(), name))
+        };
+        Self { mean: field("mean"), median: field("median"), p99: field("p99") }
+    }
+
+    pub fn report(&self, baseline: Option<&Summary>) -> String {
+        let mut out = String::new();
+        for (index, (name, value)) in self.fields().into_iter().enumerate() {
+            out.push_str(&format!("{:<8} {:>12.3} ms", name, value.as_secs_f64() * 1e3));
+            if let Some(baseline) = baseline {
+                let before = baseline.fields()[index].1.as_secs_f64();
+                let change = (value.as_secs_f64() - before) / before * 100.0;
+                out.push_str(&format!("  (baseline {:.3} ms, {:+.1}%)", before * 1e3, change));
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+// Runs the script `iterations` times in a fresh process with its output
+// discarded, after one untimed warm-up run, and summarises the wall times
+pub fn run(path: &Path, iterations: usize) -> Summary {
+    let program = env::current_exe().expect("Failed to locate the interpreter executable");
+    let run_once = || {
+        let start = Instant::now();
+        let status = Command::new(&program)
+            .arg(path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .expect("Failed to run script");
+        if !status.success() {
+            panic!("Script '{}' failed; run it directly to see the error", path.display());
+        }
+        start.elapsed()
+    };
+    run_once();
+    Summary::from_samples((0..iterations).map(|_| run_once()).collect())
+}
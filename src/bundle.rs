@@ -0,0 +1,165 @@
+use crate::ast::{ASTNode, Node};
+use crate::interpreter::{import_dir, locate_import, resolve_import};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::remote;
+use crate::stdlib;
+use crate::stack;
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+// A bundled executable is the interpreter followed by the payload and a
+// trailer of the payload length (u64, little-endian) and this marker
+const MAGIC: &[u8; 8] = b"QPRIMEXE";
+const TRAILER_LEN: u64 = 16;
+
+// A script and the modules it imports, by import path
+pub struct Bundle {
+    pub script: String,
+    pub modules: HashMap<String, String>,
+}
+
+impl Bundle {
+    // Reads the script and, recursively, every file it imports. Imports are
+    // resolved like at run time: next to the importing module, then from the
+    // current directory and the search paths. Each module is stored under
+    // the path the executable will import it by, which for modules found
+    // next to their importer is relative to the importer's own import path.
+    pub fn collect(path: &Path, search_paths: &[PathBuf]) -> Self {
+        let script = read(path);
+        let mut modules = HashMap::new();
+        // Import path, and the directory of the importer both as an import
+        // path and on disk
+        let mut pending: Vec<(String, Option<String>, Option<PathBuf>)> = imports(&script).into_iter().map(|name| (name, None, None)).collect();
+        while let Some((name, dir, file_dir)) = pending.pop() {
+            let next_to_importer = |_: &str| file_dir.as_ref().is_some_and(|file_dir| file_dir.join(&name).exists());
+            let key = locate_import(&name, dir.as_deref(), next_to_importer);
+            // Standard library modules are already in the executable
+            if modules.contains_key(&key) || stdlib::source(&key).is_some() {
+                continue;
+            }
+            let (source, file) = if remote::is_url(&key) {
+                // Building is asked for explicitly, and runs nothing it downloads
+                (remote::fetch(&key, true).unwrap_or_else(|error| panic!("{}", error)), None)
+            } else {
+                let file = match (&file_dir, key != name) {
+                    (Some(file_dir), true) => file_dir.join(&name),
+                    _ => resolve_import(&key, search_paths),
+                };
+                (read(&file), file.parent().map(Path::to_path_buf))
+            };
+            pending.extend(imports(&source).into_iter().map(|nested| (nested, import_dir(&key), file.clone())));
+            modules.insert(key, source);
+        }
+        Self { script, modules }
+    }
+
+    // Payload format: a sequence of length-prefixed strings, the script
+    // first, then each module's import path followed by its source
+    fn encode(&self) -> Vec<u8> {
+        let mut payload = Vec::new();
+        let mut push = |text: &str| {
+            payload.extend((text.len() as u64).to_le_bytes());
+            payload.extend(text.as_bytes());
+        };
+        push(&self.script);
+        let mut names: Vec<_> = self.modules.keys().collect();
+        names.sort();
+        for name in names {
+            push(name);
+            push(&self.modules[name]);
+        }
+        payload
+    }
+
+    fn decode(payload: &[u8]) -> Option<Self> {
+        let mut rest = payload;
+        let mut next = || {
+            let len = u64::from_le_bytes(rest.get(..8)?.try_into().ok()?) as usize;
+            let text = String::from_utf8(rest.get(8..8 + len)?.to_vec()).ok()?;
+            rest = &rest[8 + len..];
+            Some(text)
+        };
+        let script = next()?;
+        let mut modules = HashMap::new();
+        while let Some(name) = next() {
+            modules.insert(name, next()?);
+        }
+        Some(Self { script, modules })
+    }
+}
+
+fn read(path: &Path) -> String {
+    fs::read_to_string(path).unwrap_or_else(|error| panic!("Failed to read '{}': {}", path.display(), error))
+}
+
+// Paths named by `import` statements anywhere in the source
+fn imports(source: &str) -> Vec<String> {
+    fn walk(node: &Node, found: &mut Vec<String>) {
+        stack::grow(|| {
+            if let ASTNode::Import(name, _) = &node.node {
+                found.push(name.clone());
+            }
+            for child in node.node.children() {
+                walk(child, found);
+            }
+        })
+    }
+    let mut found = Vec::new();
+    for node in Parser::new(Lexer::new(source.to_string())).parse() {
+        walk(&node, &mut found);
+    }
+    found
+}
+
+// Length of the interpreter at the start of `file`, and the bundle after
+// it if there is one
+fn split(file: &mut File) -> std::io::Result<(u64, Option<Bundle>)> {
+    let size = file.seek(SeekFrom::End(0))?;
+    if size < TRAILER_LEN {
+        return Ok((size, None));
+    }
+    let mut trailer = [0; TRAILER_LEN as usize];
+    file.seek(SeekFrom::End(-(TRAILER_LEN as i64)))?;
+    file.read_exact(&mut trailer)?;
+    let payload_len = u64::from_le_bytes(trailer[..8].try_into().unwrap());
+    if &trailer[8..] != MAGIC || payload_len > size - TRAILER_LEN {
+        return Ok((size, None));
+    }
+    let start = size - TRAILER_LEN - payload_len;
+    let mut payload = vec![0; payload_len as usize];
+    file.seek(SeekFrom::Start(start))?;
+    file.read_exact(&mut payload)?;
+    Ok((start, Bundle::decode(&payload)))
+}
+
+// The bundle appended to the running executable, if any
+pub fn embedded() -> Option<Bundle> {
+    let mut file = File::open(env::current_exe().ok()?).ok()?;
+    split(&mut file).ok()?.1
+}
+
+// Writes a copy of the running interpreter with the bundle appended. The
+// result runs the script when started, passing it all its arguments.
+pub fn build(bundle: &Bundle, output: &Path) {
+    let program = env::current_exe().expect("Failed to locate the interpreter executable");
+    let mut file = File::open(&program).expect("Failed to read the interpreter executable");
+    let (interpreter_len, _) = split(&mut file).expect("Failed to read the interpreter executable");
+    let mut executable = Vec::new();
+    file.seek(SeekFrom::Start(0))
+        .and_then(|_| file.take(interpreter_len).read_to_end(&mut executable))
+        .expect("Failed to read the interpreter executable");
+    let payload = bundle.encode();
+    executable.extend(&payload);
+    executable.extend((payload.len() as u64).to_le_bytes());
+    executable.extend(MAGIC);
+    fs::write(output, executable).unwrap_or_else(|error| panic!("Failed to write '{}': {}", output.display(), error));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(output, fs::Permissions::from_mode(0o755)).expect("Failed to make the executable runnable");
+    }
+}
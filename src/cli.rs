@@ -0,0 +1,202 @@
+use qprime::error::USAGE_EXIT_CODE;
+use qprime::configs::MAX_PRECISION;
+use qprime::project::Config;
+use qprime::Numeric;
+use clap::builder::RangedU64ValueParser;
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+
+// Running a script is the default, so `qprime script.qpr` needs no subcommand
+#[derive(Parser)]
+#[command(name = "qprime", version, about = "Interpreter for weather and meteorology scripts", args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[command(flatten)]
+    pub run: RunArgs,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run a script (the default when no subcommand is given)
+    Run(RunArgs),
+    /// Start an interactive session (the default with no arguments)
+    Repl,
+    /// Warn about unused variables and parameters and shadowed builtins
+    Lint { script: PathBuf },
+    /// Rewrite a script in the canonical style
+    Fmt {
+        /// Fail instead of rewriting when the script is not formatted
+        #[arg(long)]
+        check: bool,
+        script: PathBuf,
+    },
+    /// Step through a script
+    Debug(RunArgs),
+    /// List a script's functions and their comments
+    Doc {
+        /// Print Markdown instead of plain text
+        #[arg(long)]
+        markdown: bool,
+        script: PathBuf,
+    },
+    /// Run every `*_test.qpr` script under a directory
+    Test {
+        #[arg(default_value = ".")]
+        directory: PathBuf,
+    },
+    /// Bundle a script and its imports into a standalone executable
+    Build {
+        script: PathBuf,
+        /// Also look for imports in DIR (repeatable)
+        #[arg(short = 'I', long, value_name = "DIR")]
+        include: Vec<PathBuf>,
+        /// Path of the executable (default: the script name without extension)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Translate a script into another language
+    Transpile {
+        #[arg(long, value_enum)]
+        target: Target,
+        script: PathBuf,
+        /// Also look for imports in DIR (repeatable)
+        #[arg(short = 'I', long, value_name = "DIR")]
+        include: Vec<PathBuf>,
+        /// Write to FILE instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Time a script over several runs
+    Bench {
+        /// Number of timed runs
+        #[arg(long, value_name = "N", default_value_t = 10, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+        iters: usize,
+        /// Compare against results saved with --save-baseline
+        #[arg(long, value_name = "FILE")]
+        baseline: Option<PathBuf>,
+        /// Save the results for a later --baseline
+        #[arg(long, value_name = "FILE")]
+        save_baseline: Option<PathBuf>,
+        script: PathBuf,
+    },
+    /// Install a module package into weather_modules/ (all of weather.lock
+    /// when no source is given)
+    Add {
+        /// Git URL, local repository or GitHub `user/repo`
+        source: Option<String>,
+        /// Directory and import name of the package (default: the repository name)
+        #[arg(long)]
+        name: Option<String>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Target {
+    Python,
+}
+
+#[derive(Args, Default, PartialEq)]
+pub struct RunArgs {
+    /// Run CODE instead of a script file
+    #[arg(short = 'e', long, value_name = "CODE")]
+    pub eval: Option<String>,
+    /// Only lex and parse the given scripts
+    #[arg(long, conflicts_with = "eval")]
+    pub check: bool,
+    /// Reject scripts that fail the strict mode checks
+    #[arg(long)]
+    pub strict: bool,
+    /// Print the syntax tree instead of running
+    #[arg(long, group = "dump")]
+    pub ast: bool,
+    /// Print the syntax tree as a Graphviz DOT graph instead of running
+    #[arg(long, group = "dump")]
+    pub ast_dot: bool,
+    /// Print the syntax tree as JSON instead of running
+    #[arg(long, group = "dump")]
+    pub ast_json: bool,
+    /// Print the token stream instead of running
+    #[arg(long, group = "dump")]
+    pub tokens: bool,
+    /// Number representation: f64 (default, fast) or exact (BigRational)
+    #[arg(long, value_name = "MODE")]
+    pub numeric: Option<Numeric>,
+    /// Significant digits of printed numbers (default 6)
+    #[arg(long, value_name = "N", value_parser = RangedU64ValueParser::<usize>::new().range(1..=MAX_PRECISION as u64))]
+    pub precision: Option<usize>,
+    /// Pre-compute constant expressions before running
+    #[arg(long)]
+    pub opt: bool,
+    /// Print each statement to stderr as it runs
+    #[arg(long)]
+    pub trace: bool,
+    /// Print the time spent in each function to stderr
+    #[arg(long)]
+    pub profile: bool,
+    /// Also look for imports in DIR (repeatable)
+    #[arg(short = 'I', long, value_name = "DIR")]
+    pub include: Vec<PathBuf>,
+    /// Load builtin functions from a shared library (repeatable)
+    #[arg(long, value_name = "LIBRARY")]
+    pub plugin: Vec<PathBuf>,
+    /// Let scripts download data with fetch() and forecast()
+    #[arg(long)]
+    pub allow_net: bool,
+    /// Maximum depth of nested function calls
+    #[arg(long, value_name = "CALLS")]
+    pub max_depth: Option<usize>,
+    /// Maximum number of statements and expressions evaluated
+    #[arg(long, value_name = "STEPS")]
+    pub max_steps: Option<u64>,
+    /// Maximum running time
+    #[arg(long, value_name = "SECONDS", value_parser = seconds)]
+    pub timeout: Option<f64>,
+    /// The script followed by its arguments (only arguments with -e, only
+    /// scripts with --check)
+    #[arg(value_name = "SCRIPT [ARGS]", trailing_var_arg = true)]
+    pub args: Vec<String>,
+}
+
+impl RunArgs {
+    // Fills in defaults from the project configuration
+    pub fn with_config(mut self, config: &Config) -> Self {
+        self.strict |= config.strict;
+        self.numeric = self.numeric.or(config.numeric);
+        self.precision = self.precision.or(config.precision);
+        self.opt |= config.opt;
+        self.trace |= config.trace;
+        self.profile |= config.profile;
+        self.max_depth = self.max_depth.or(config.max_depth);
+        self.max_steps = self.max_steps.or(config.max_steps);
+        self.timeout = self.timeout.or(config.timeout);
+        self.include.extend(config.include.iter().cloned());
+        self
+    }
+}
+
+// Parses the command line. Usage errors exit with status 64 rather than
+// clap's default of 2, which is reserved for rejected scripts.
+pub fn parse() -> Cli {
+    Cli::try_parse().unwrap_or_else(|error| {
+        let _ = error.print();
+        process::exit(if error.use_stderr() { USAGE_EXIT_CODE } else { 0 });
+    })
+}
+
+// A length of time, which must be positive and fit in a `Duration`
+fn seconds(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(seconds) if seconds > 0.0 && Duration::try_from_secs_f64(seconds).is_ok() => Ok(seconds),
+        Ok(_) => Err("must be a positive number of seconds".to_string()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+pub fn usage_error(message: &str) -> ! {
+    let _ = Cli::command().error(ErrorKind::MissingRequiredArgument, message).print();
+    process::exit(USAGE_EXIT_CODE);
+}
@@ -1 +1,30 @@
-pub const FILE_EXTENSION: &str = "qpr";
\ No newline at end of file
+pub const FILE_EXTENSION: &str = "qpr";
+
+// REPL history, kept in the home directory
+pub const HISTORY_FILE: &str = ".qprime_history";
+
+// Significant digits of printed numbers, unless set with --precision, and
+// the most that can be asked for (enough to show any f64 exactly)
+pub const DEFAULT_PRECISION: usize = 6;
+pub const MAX_PRECISION: usize = 17;
+
+// Project configuration, looked up in the current directory and its parents
+pub const CONFIG_FILE: &str = "weather.toml";
+
+// Packages installed by `qprime add`, and the lockfile recording them, both
+// next to `weather.toml`
+pub const MODULES_DIR: &str = "weather_modules";
+pub const LOCK_FILE: &str = "weather.lock";
+
+// Module loaded when a package is imported by its name alone
+pub const PACKAGE_ENTRY: &str = "lib";
+
+// Environment variable listing more directories to search for imports,
+// separated like PATH
+pub const SEARCH_PATH_VAR: &str = "WEATHER_PATH";
+
+// Downloaded modules, kept under the user's cache directory
+pub const MODULE_CACHE_DIR: &str = "qprime/modules";
+
+// Service name that `secret()` looks up in the system keyring
+pub const KEYRING_SERVICE: &str = "qprime";
@@ -1,5 +1,7 @@
 mod weatherconstants;
 pub use weatherconstants::*;
+mod table;
+pub use table::*;
 
 /*
 mod quantumconstants;
@@ -0,0 +1,61 @@
+use super::*;
+use num_rational::BigRational;
+use num_traits::Zero;
+use std::collections::HashMap;
+
+// Physical constants that scripts can change with `set_constant` (or the
+// `[constants]` table of `weather.toml`), named as in their `_name_` form.
+// `_pi_` and `_kelvin_` are definitions rather than measurements, so they
+// stay fixed, and `_epsilon_` is worked out from `_rd_` and `_rv_`.
+pub const OVERRIDABLE: [&str; 13] = ["rd", "cp", "p0", "lv", "cw", "rho_air", "rho_water", "g", "omega", "rv", "sigma", "solar", "lf"];
+
+// Current values of the overridable constants, e.g. Mars gravity for
+// `set_constant("g", 3.71)`
+#[derive(Clone, Debug)]
+pub struct Constants {
+    values: HashMap<&'static str, BigRational>,
+}
+
+impl Default for Constants {
+    fn default() -> Self {
+        let defaults = [
+            rd_constant(),
+            cp_constant(),
+            p0_constant(),
+            lv_constant(),
+            cw_constant(),
+            rho_air_constant(),
+            rho_water_constant(),
+            g_constant(),
+            omega_constant(),
+            rv_constant(),
+            sigma_constant(),
+            solar_constant(),
+            lf_constant(),
+        ];
+        Self { values: OVERRIDABLE.into_iter().zip(defaults.into_iter().cloned()).collect() }
+    }
+}
+
+impl Constants {
+    // Value of one of the `OVERRIDABLE` constants
+    pub fn get(&self, name: &str) -> &BigRational {
+        &self.values[name]
+    }
+
+    pub fn set(&mut self, name: &str, value: BigRational) -> Result<(), String> {
+        let Some(current) = self.values.get_mut(name) else {
+            let hint = match name {
+                "pi" | "kelvin" => format!("_{}_ is fixed", name),
+                "epsilon" => "_epsilon_ follows _rd_ and _rv_".to_string(),
+                _ => format!("expected one of {}", OVERRIDABLE.join(", ")),
+            };
+            return Err(format!("Cannot set unknown constant '{}' ({})", name, hint));
+        };
+        if value <= BigRational::zero() {
+            return Err(format!("The constant '{}' must be above 0", name));
+        }
+        *current = value;
+        Ok(())
+    }
+}
@@ -1,55 +1,106 @@
 use num_bigint::BigInt;
 use num_rational::BigRational;
+use std::sync::OnceLock;
+
+// Each constant is built on first use and shared after that
 
 // Kelvin constant
-pub fn kelvin_constant() -> BigRational {
-    BigRational::new(BigInt::from(27315), BigInt::from(100))
+pub fn kelvin_constant() -> &'static BigRational {
+    static VALUE: OnceLock<BigRational> = OnceLock::new();
+    VALUE.get_or_init(|| BigRational::new(BigInt::from(27315), BigInt::from(100)))
 }
 
 // Pi constant
-pub fn pi_constant() -> BigRational {
-    BigRational::new(
-        BigInt::parse_bytes(b"31415926535897932384626433832795028841971693993751058209749445923078164062862089986280348253421170679", 10).unwrap(),
-        BigInt::parse_bytes(b"10000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000", 10).unwrap()
-    )
+pub fn pi_constant() -> &'static BigRational {
+    static VALUE: OnceLock<BigRational> = OnceLock::new();
+    VALUE.get_or_init(|| {
+        BigRational::new(
+            BigInt::parse_bytes(b"31415926535897932384626433832795028841971693993751058209749445923078164062862089986280348253421170679", 10).unwrap(),
+            BigInt::parse_bytes(b"10000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000", 10).unwrap()
+        )
+    })
 }
 
 // Gas constant for dry air (J/(kg·K))
-pub fn rd_constant() -> BigRational {
-    BigRational::new(BigInt::from(28705), BigInt::from(100))
+pub fn rd_constant() -> &'static BigRational {
+    static VALUE: OnceLock<BigRational> = OnceLock::new();
+    VALUE.get_or_init(|| BigRational::new(BigInt::from(28705), BigInt::from(100)))
 }
 
 // Specific heat capacity of air at constant pressure (J/(kg·K))
-pub fn cp_constant() -> BigRational {
-    BigRational::new(BigInt::from(1005), BigInt::from(1))
+pub fn cp_constant() -> &'static BigRational {
+    static VALUE: OnceLock<BigRational> = OnceLock::new();
+    VALUE.get_or_init(|| BigRational::new(BigInt::from(1005), BigInt::from(1)))
 }
 
 // Standard atmospheric pressure (Pa)
-pub fn p0_constant() -> BigRational {
-    BigRational::new(BigInt::from(101325), BigInt::from(1))
+pub fn p0_constant() -> &'static BigRational {
+    static VALUE: OnceLock<BigRational> = OnceLock::new();
+    VALUE.get_or_init(|| BigRational::new(BigInt::from(101325), BigInt::from(1)))
 }
 
 // Latent heat of vaporization for water (J/kg)
-pub fn lv_constant() -> BigRational {
-    BigRational::new(BigInt::from(2260000), BigInt::from(1))
+pub fn lv_constant() -> &'static BigRational {
+    static VALUE: OnceLock<BigRational> = OnceLock::new();
+    VALUE.get_or_init(|| BigRational::new(BigInt::from(2260000), BigInt::from(1)))
 }
 
 // Specific heat capacity of water (J/(kg·K))
-pub fn cw_constant() -> BigRational {
-    BigRational::new(BigInt::from(4184), BigInt::from(1))
+pub fn cw_constant() -> &'static BigRational {
+    static VALUE: OnceLock<BigRational> = OnceLock::new();
+    VALUE.get_or_init(|| BigRational::new(BigInt::from(4184), BigInt::from(1)))
 }
 
 // Density of air (kg/m³)
-pub fn rho_air_constant() -> BigRational {
-    BigRational::new(BigInt::from(1200), BigInt::from(1))
+pub fn rho_air_constant() -> &'static BigRational {
+    static VALUE: OnceLock<BigRational> = OnceLock::new();
+    VALUE.get_or_init(|| BigRational::new(BigInt::from(1200), BigInt::from(1)))
 }
 
 // Density of water (kg/m³)
-pub fn rho_water_constant() -> BigRational {
-    BigRational::new(BigInt::from(1000), BigInt::from(1))
+pub fn rho_water_constant() -> &'static BigRational {
+    static VALUE: OnceLock<BigRational> = OnceLock::new();
+    VALUE.get_or_init(|| BigRational::new(BigInt::from(1000), BigInt::from(1)))
 }
 
 // Acceleration due to gravity (m/s²)
-pub fn g_constant() -> BigRational {
-    BigRational::new(BigInt::from(981), BigInt::from(100))
-}
\ No newline at end of file
+pub fn g_constant() -> &'static BigRational {
+    static VALUE: OnceLock<BigRational> = OnceLock::new();
+    VALUE.get_or_init(|| BigRational::new(BigInt::from(981), BigInt::from(100)))
+}
+
+// Angular velocity of the Earth's rotation (rad/s)
+pub fn omega_constant() -> &'static BigRational {
+    static VALUE: OnceLock<BigRational> = OnceLock::new();
+    VALUE.get_or_init(|| BigRational::new(BigInt::from(72921159), BigInt::from(1_000_000_000_000i64)))
+}
+
+// Gas constant for water vapour (J/(kg·K))
+pub fn rv_constant() -> &'static BigRational {
+    static VALUE: OnceLock<BigRational> = OnceLock::new();
+    VALUE.get_or_init(|| BigRational::new(BigInt::from(4615), BigInt::from(10)))
+}
+
+// Ratio of the gas constants of dry air and water vapour, Rd/Rv
+pub fn epsilon_constant() -> &'static BigRational {
+    static VALUE: OnceLock<BigRational> = OnceLock::new();
+    VALUE.get_or_init(|| rd_constant() / rv_constant())
+}
+
+// Stefan-Boltzmann constant (W/(m²·K⁴))
+pub fn sigma_constant() -> &'static BigRational {
+    static VALUE: OnceLock<BigRational> = OnceLock::new();
+    VALUE.get_or_init(|| BigRational::new(BigInt::from(5670374419i64), BigInt::from(100_000_000_000_000_000i64)))
+}
+
+// Solar constant, the mean solar irradiance at the top of the atmosphere (W/m²)
+pub fn solar_constant() -> &'static BigRational {
+    static VALUE: OnceLock<BigRational> = OnceLock::new();
+    VALUE.get_or_init(|| BigRational::new(BigInt::from(1361), BigInt::from(1)))
+}
+
+// Latent heat of fusion for water (J/kg)
+pub fn lf_constant() -> &'static BigRational {
+    static VALUE: OnceLock<BigRational> = OnceLock::new();
+    VALUE.get_or_init(|| BigRational::new(BigInt::from(334000), BigInt::from(1)))
+}
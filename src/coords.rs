@@ -0,0 +1,86 @@
+// Latitudes and longitudes as written in METARs, storm reports and the like,
+// e.g. `40°42'46"N`, `40 42.77N`, `-74.006` or `W074.006`
+
+// Styles selectable with `format_coord`: decimal degrees, degrees and
+// decimal minutes, and degrees, minutes and seconds
+pub const STYLES: &[&str] = &["dd", "dm", "dms"];
+
+// Decimal degrees for a coordinate, negative in the southern and western
+// hemispheres. Up to three numbers (degrees, minutes, seconds) separated by
+// spaces, `°`, `'`, `"` or `:`, with a sign or a hemisphere letter before or
+// after them.
+pub fn parse(text: &str) -> Result<f64, String> {
+    let invalid = |reason: &str| Err(format!("'{}' is not a coordinate: {}", text, reason));
+    let mut rest = text.trim();
+    let mut hemisphere = None;
+    if let Some(letter) = rest.chars().next().filter(|ch| "NSEWnsew".contains(*ch)) {
+        hemisphere = Some(letter.to_ascii_uppercase());
+        rest = &rest[1..];
+    } else if let Some(letter) = rest.chars().last().filter(|ch| "NSEWnsew".contains(*ch)) {
+        hemisphere = Some(letter.to_ascii_uppercase());
+        rest = &rest[..rest.len() - 1];
+    }
+    let rest = rest.trim();
+    let (negative, rest) = match rest.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, rest.strip_prefix('+').unwrap_or(rest)),
+    };
+    if negative && hemisphere.is_some() {
+        return invalid("it has both a sign and a hemisphere");
+    }
+    let separator = |ch: char| ch.is_whitespace() || "°º'\"′″:".contains(ch);
+    let mut parts = Vec::new();
+    for part in rest.split(separator).filter(|part| !part.is_empty()) {
+        match part.parse::<f64>() {
+            Ok(number) if number.is_finite() && number >= 0.0 => parts.push(number),
+            _ => return invalid(&format!("'{}' is not a number of degrees, minutes or seconds", part)),
+        }
+    }
+    let degrees = match parts[..] {
+        [degrees] => degrees,
+        [degrees, minutes] if minutes < 60.0 => degrees + minutes / 60.0,
+        [degrees, minutes, seconds] if minutes < 60.0 && seconds < 60.0 => degrees + minutes / 60.0 + seconds / 3600.0,
+        [] => return invalid("it has no degrees"),
+        [_, _] | [_, _, _] => return invalid("minutes and seconds must be below 60"),
+        _ => return invalid("it has more than degrees, minutes and seconds"),
+    };
+    let limit = if matches!(hemisphere, Some('N' | 'S')) { 90.0 } else { 180.0 };
+    if degrees > limit {
+        return invalid(&format!("it is more than {} degrees", limit));
+    }
+    Ok(if negative || matches!(hemisphere, Some('S' | 'W')) { -degrees } else { degrees })
+}
+
+// One coordinate in a style of STYLES, with `positive` and `negative` as
+// the hemisphere letters
+fn format_one(value: f64, style: &str, positive: char, negative: char) -> String {
+    let hemisphere = if value < 0.0 { negative } else { positive };
+    let value = value.abs();
+    match style {
+        "dd" => format!("{:.4}°{}", value, hemisphere),
+        // Rounded as a whole so that 59.999' becomes the next degree
+        "dm" => {
+            let hundredths = (value * 6000.0).round() as u64;
+            format!("{}°{:02}.{:02}'{}", hundredths / 6000, hundredths % 6000 / 100, hundredths % 100, hemisphere)
+        }
+        _ => {
+            let seconds = (value * 3600.0).round() as u64;
+            format!("{}°{:02}'{:02}\"{}", seconds / 3600, seconds % 3600 / 60, seconds % 60, hemisphere)
+        }
+    }
+}
+
+// A latitude and longitude in decimal degrees written in a style of STYLES,
+// e.g. `40°42'46"N 74°00'22"W`
+pub fn format(latitude: f64, longitude: f64, style: &str) -> Result<String, String> {
+    if !STYLES.contains(&style) {
+        return Err(format!("Unknown coordinate style '{}', expected one of {}", style, STYLES.join(", ")));
+    }
+    if !(-90.0..=90.0).contains(&latitude) {
+        return Err(format!("The latitude must be between -90 and 90 degrees, found {}", latitude));
+    }
+    if !(-180.0..=180.0).contains(&longitude) {
+        return Err(format!("The longitude must be between -180 and 180 degrees, found {}", longitude));
+    }
+    Ok(format!("{} {}", format_one(latitude, style, 'N', 'S'), format_one(longitude, style, 'E', 'W')))
+}
@@ -0,0 +1,126 @@
+use crate::ast::Node;
+use crate::interpreter::{ExecutionHook, Interpreter};
+use std::collections::HashSet;
+use std::io::{self, BufRead, Write};
+use std::process;
+
+const HELP: &str = "Commands:
+  s, step          run until the next statement, entering function calls
+  n, next          run until the next statement in the current function
+  c, continue      run until the next breakpoint
+  b, break <line>  set a breakpoint
+  d, delete <line> remove a breakpoint
+  v, vars          show all variables
+  p, print <name>  show one variable
+  l, list          show the source around the current line
+  h, help          show this help
+  q, quit          stop the script";
+
+enum Mode {
+    Step,
+    // Pause at the next statement at or above this call depth
+    Next(usize),
+    Continue,
+}
+
+// Interactive debugger driven from stdin; starts paused at the first statement
+pub struct Debugger {
+    source: Vec<String>,
+    breakpoints: HashSet<usize>,
+    mode: Mode,
+}
+
+impl Debugger {
+    pub fn new(source: &str) -> Self {
+        Self {
+            source: source.lines().map(String::from).collect(),
+            breakpoints: HashSet::new(),
+            mode: Mode::Step,
+        }
+    }
+
+    fn should_pause(&self, line: usize, depth: usize) -> bool {
+        match self.mode {
+            Mode::Step => true,
+            Mode::Next(target) => depth <= target || self.breakpoints.contains(&line),
+            Mode::Continue => self.breakpoints.contains(&line),
+        }
+    }
+
+    fn source_line(&self, line: usize) -> &str {
+        self.source.get(line - 1).map_or("", |text| text.as_str())
+    }
+
+    fn list(&self, line: usize) {
+        let first = line.saturating_sub(3).max(1);
+        let last = (line + 3).min(self.source.len());
+        for number in first..=last {
+            let marker = if number == line { "->" } else if self.breakpoints.contains(&number) { " *" } else { "  " };
+            println!("{} {:>4} | {}", marker, number, self.source_line(number));
+        }
+    }
+
+    fn prompt(&mut self, line: usize, interpreter: &Interpreter) {
+        println!("[line {}] {}", line, self.source_line(line).trim());
+        let stdin = io::stdin();
+        loop {
+            print!("(debug) ");
+            io::stdout().flush().unwrap();
+            let mut input = String::new();
+            if stdin.lock().read_line(&mut input).unwrap() == 0 {
+                process::exit(0);
+            }
+            let mut words = input.split_whitespace();
+            match (words.next(), words.next()) {
+                (Some("s" | "step"), _) => {
+                    self.mode = Mode::Step;
+                    return;
+                }
+                (Some("n" | "next"), _) => {
+                    self.mode = Mode::Next(interpreter.call_depth());
+                    return;
+                }
+                (Some("c" | "continue"), _) => {
+                    self.mode = Mode::Continue;
+                    return;
+                }
+                (Some("b" | "break"), Some(number)) => match number.parse() {
+                    Ok(number) => {
+                        self.breakpoints.insert(number);
+                        println!("Breakpoint set on line {}", number);
+                    }
+                    Err(_) => println!("Invalid line number '{}'", number),
+                },
+                (Some("d" | "delete"), Some(number)) => match number.parse() {
+                    Ok(number) if self.breakpoints.remove(&number) => println!("Breakpoint removed from line {}", number),
+                    _ => println!("No breakpoint on line {}", number),
+                },
+                (Some("v" | "vars"), _) => {
+                    let mut variables: Vec<_> = interpreter.variables().into_iter().collect();
+                    variables.sort_by(|(left, _), (right, _)| left.cmp(right));
+                    for (name, value) in variables {
+                        println!("{} = {}", name, value);
+                    }
+                }
+                (Some("p" | "print"), Some(name)) => match interpreter.variable(name) {
+                    Some(value) => println!("{} = {}", name, value),
+                    None => println!("Undefined variable '{}'", name),
+                },
+                (Some("l" | "list"), _) => self.list(line),
+                (Some("h" | "help"), _) => println!("{}", HELP),
+                (Some("q" | "quit"), _) => process::exit(0),
+                (None, _) => {}
+                (Some(command), _) => println!("Unknown command '{}', type 'help' for a list of commands", command),
+            }
+        }
+    }
+}
+
+impl ExecutionHook for Debugger {
+    fn before_statement(&mut self, node: &Node, interpreter: &Interpreter) {
+        let line = node.span.start.line;
+        if self.should_pause(line, interpreter.call_depth()) {
+            self.prompt(line, interpreter);
+        }
+    }
+}
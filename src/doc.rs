@@ -0,0 +1,81 @@
+use crate::ast::{ASTNode, Node};
+use crate::span::Spanned;
+use std::collections::HashMap;
+
+// A top-level function and the comment block directly above it
+pub struct FunctionDoc {
+    pub name: String,
+    pub params: Vec<String>,
+    pub line: usize,
+    pub comment: Vec<String>,
+}
+
+impl FunctionDoc {
+    pub fn signature(&self) -> String {
+        format!("{}({})", self.name, self.params.join(", "))
+    }
+}
+
+// Collects the script's top-level functions in source order. A comment block
+// documents a function when its lines run without a gap up to the `fn` line.
+pub fn collect(nodes: &[Node], comments: &[Spanned<String>]) -> Vec<FunctionDoc> {
+    let by_line: HashMap<usize, &str> = comments
+        .iter()
+        .map(|comment| (comment.span.start.line, comment.node.as_str()))
+        .collect();
+    let mut docs = Vec::new();
+    let mut previous_end = 0;
+    for node in nodes {
+        let definition = match &node.node {
+            ASTNode::Export(inner) => &inner.node,
+            other => other,
+        };
+        if let ASTNode::Function(name, params, _) = definition {
+            let mut comment = Vec::new();
+            let mut line = node.span.start.line - 1;
+            // Lines that end an earlier statement hold trailing comments, not docs
+            while line > previous_end {
+                match by_line.get(&line) {
+                    Some(text) => comment.push(text.strip_prefix(' ').unwrap_or(text).trim_end().to_string()),
+                    None => break,
+                }
+                line -= 1;
+            }
+            comment.reverse();
+            docs.push(FunctionDoc {
+                name: name.clone(),
+                params: params.clone(),
+                line: node.span.start.line,
+                comment,
+            });
+        }
+        previous_end = node.span.end.line;
+    }
+    docs
+}
+
+pub fn markdown(title: &str, docs: &[FunctionDoc]) -> String {
+    let mut out = format!("# {}\n", title);
+    for doc in docs {
+        out.push_str(&format!("\n## `{}`\n", doc.signature()));
+        if !doc.comment.is_empty() {
+            out.push('\n');
+            for line in &doc.comment {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+pub fn terminal(title: &str, docs: &[FunctionDoc]) -> String {
+    let mut out = format!("{}\n", title);
+    for doc in docs {
+        out.push_str(&format!("\n{}  (line {})\n", doc.signature(), doc.line));
+        for line in &doc.comment {
+            out.push_str(&format!("    {}\n", line));
+        }
+    }
+    out
+}
@@ -0,0 +1,90 @@
+use crate::ast::{ASTNode, Node};
+use crate::lexer::Lexer;
+use crate::stack;
+use crate::token::Token;
+use num_traits::ToPrimitive;
+
+// Lexes the whole input, one token per line with its span
+pub fn dump_tokens(lexer: &mut Lexer) -> String {
+    let mut out = String::new();
+    loop {
+        let token = lexer.next_token();
+        let text = match &token.node {
+            Token::Float(value) => format!("Float({})", value),
+            Token::Quantity(value, unit) => format!("Quantity({} {})", value, unit),
+            other => format!("{:?}", other),
+        };
+        out.push_str(&format!("{:<12} {}\n", token.span.to_string(), text));
+        if token.node == Token::EOF {
+            break;
+        }
+    }
+    out
+}
+
+// Renders the AST as an indented tree, one node per line with its span
+pub fn dump_ast(nodes: &[Node]) -> String {
+    let mut out = String::new();
+    for node in nodes {
+        dump_node(node, 0, &mut out);
+    }
+    out
+}
+
+fn dump_node(node: &Node, depth: usize, out: &mut String) {
+    stack::grow(|| {
+        out.push_str(&"  ".repeat(depth));
+        out.push_str(node.node.name());
+        if let Some(detail) = node_detail(&node.node) {
+            out.push(' ');
+            out.push_str(&detail);
+        }
+        out.push_str(&format!(" @ {}\n", node.span));
+        for child in node.node.children() {
+            dump_node(child, depth + 1, out);
+        }
+    })
+}
+
+// Renders the AST as a Graphviz DOT digraph rooted at a `Program` node
+pub fn dump_ast_dot(nodes: &[Node]) -> String {
+    let mut out = String::from("digraph AST {\n    node [shape=box, fontname=\"monospace\"];\n    n0 [label=\"Program\"];\n");
+    let mut next_id = 1;
+    for node in nodes {
+        dot_node(node, 0, &mut next_id, &mut out);
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn dot_node(node: &Node, parent: usize, next_id: &mut usize, out: &mut String) {
+    stack::grow(|| {
+        let id = *next_id;
+        *next_id += 1;
+        let mut label = node.node.name().to_string();
+        if let Some(detail) = node_detail(&node.node) {
+            label.push(' ');
+            label.push_str(&detail);
+        }
+        label.push_str(&format!("\n{}", node.span));
+        out.push_str(&format!("    n{} [label={:?}];\n", id, label));
+        out.push_str(&format!("    n{} -> n{};\n", parent, id));
+        for child in node.node.children() {
+            dot_node(child, id, next_id, out);
+        }
+    })
+}
+
+// Payload that isn't a child node (names, literal values, operators)
+pub fn node_detail(node: &ASTNode) -> Option<String> {
+    match node {
+        ASTNode::Float(value) => Some(value.to_f64().unwrap().to_string()),
+        ASTNode::Quantity(value, unit) => Some(format!("{} {}", value.to_f64().unwrap(), unit)),
+        ASTNode::Identifier(name) | ASTNode::Assignment(name, _) | ASTNode::Call(name, _) | ASTNode::Pmap(name, _) => Some(name.clone()),
+        ASTNode::StringLiteral(value) | ASTNode::Import(value, None) => Some(format!("{:?}", value)),
+        ASTNode::Import(module, Some(names)) => Some(format!("{:?} ({})", module, names.join(", "))),
+        ASTNode::BinaryOp(_, op, _) | ASTNode::UnaryOp(op, _) => Some(format!("{:?}", op)),
+        ASTNode::Function(name, params, _) => Some(format!("{}({})", name, params.join(", "))),
+        _ => None,
+    }
+}
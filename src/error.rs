@@ -0,0 +1,65 @@
+use std::any::Any;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::process;
+
+// Exit status for bad command-line usage (EX_USAGE from sysexits.h)
+pub const USAGE_EXIT_CODE: i32 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorKind {
+    // The script was rejected before running: syntax or strict mode errors
+    Parse,
+    // The script failed while running, or a file could not be read or written
+    Runtime,
+}
+
+#[derive(Debug, Clone)]
+pub struct WeatherError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl WeatherError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self { kind, message: message.into() }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self.kind {
+            ErrorKind::Parse => 2,
+            ErrorKind::Runtime => 1,
+        }
+    }
+
+    // Reports the error on stderr and exits with its status
+    pub fn exit(&self) -> ! {
+        eprintln!("error: {}", self);
+        process::exit(self.exit_code());
+    }
+}
+
+impl fmt::Display for WeatherError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for WeatherError {}
+
+// Text of a panic payload; interpreter errors are raised as panics with a
+// formatted message
+pub fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<String>()
+        .map(String::as_str)
+        .or_else(|| payload.downcast_ref::<&str>().copied())
+        .unwrap_or("unknown error")
+}
+
+// Runs `f`, turning an error raised inside it into a `WeatherError` of the
+// given kind. Callers install a quiet panic hook so the error is only
+// reported once, by whoever handles the result.
+pub fn catch<T>(kind: ErrorKind, f: impl FnOnce() -> T) -> Result<T, WeatherError> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| WeatherError::new(kind, panic_message(payload.as_ref())))
+}
@@ -0,0 +1,70 @@
+use crate::error::{self, ErrorKind, USAGE_EXIT_CODE};
+use crate::{Interpreter, Lexer, Parser};
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic;
+use std::ptr;
+use std::sync::Once;
+
+thread_local! {
+    // Message of the last failed `weather_run` on this thread
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+static QUIET_PANICS: Once = Once::new();
+
+fn set_last_error(message: Option<String>) {
+    // Interior NUL bytes would cut the message short, so they are dropped
+    let message = message.map(|message| CString::new(message.replace('\0', "")).unwrap());
+    LAST_ERROR.with(|error| *error.borrow_mut() = message);
+}
+
+// Runs a NUL-terminated script, printing to stdout. Returns 0 on success,
+// 1 for runtime errors, 2 for syntax errors and 64 when `src` is null or
+// not UTF-8, like the `qprime` exit status. On failure the message is
+// available from `weather_last_error`.
+/// # Safety
+/// `src` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn weather_run(src: *const c_char) -> c_int {
+    // Errors are reported through `weather_last_error`, not on stderr
+    QUIET_PANICS.call_once(|| panic::set_hook(Box::new(|_| {})));
+    if src.is_null() {
+        set_last_error(Some("weather_run was given a null script".to_string()));
+        return USAGE_EXIT_CODE;
+    }
+    let source = match CStr::from_ptr(src).to_str() {
+        Ok(source) => source,
+        Err(_) => {
+            set_last_error(Some("weather_run was given a script that is not UTF-8".to_string()));
+            return USAGE_EXIT_CODE;
+        }
+    };
+    let result = error::catch(ErrorKind::Parse, || Parser::new(Lexer::new(source.to_string())).parse())
+        .and_then(|nodes| error::catch(ErrorKind::Runtime, || Interpreter::new().interpret(nodes)));
+    match result {
+        Ok(()) => {
+            set_last_error(None);
+            0
+        }
+        Err(error) => {
+            let code = error.exit_code();
+            set_last_error(Some(error.message));
+            code
+        }
+    }
+}
+
+// Message of the error from the last failed `weather_run` on this thread,
+// or null if it succeeded. The string is owned by the library and stays
+// valid until the next `weather_run` or `weather_clear_error`.
+#[no_mangle]
+pub extern "C" fn weather_last_error() -> *const c_char {
+    LAST_ERROR.with(|error| error.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+// Forgets the last error message
+#[no_mangle]
+pub extern "C" fn weather_clear_error() {
+    set_last_error(None);
+}
@@ -0,0 +1,238 @@
+use crate::ast::{ASTNode, Node};
+use crate::configs::FILE_EXTENSION;
+use crate::remote::is_url;
+use crate::parser::{infix_binding_power, NEGATE_BINDING_POWER, NOT_BINDING_POWER};
+use crate::span::{Position, Spanned};
+use crate::stack;
+use crate::token::Token;
+use num_rational::BigRational;
+use num_traits::ToPrimitive;
+
+const INDENT: &str = "    ";
+
+// Re-emits a parsed script in canonical form: four-space indentation, single
+// spaces around binary operators, opening braces on the statement's line, at
+// most one blank line between statements, and comments kept where they were.
+pub fn format_program(nodes: &[Node], comments: &[Spanned<String>]) -> String {
+    let mut formatter = Formatter {
+        comments,
+        next_comment: 0,
+        last_line: 0,
+        out: String::new(),
+    };
+    formatter.statements(nodes, 0, Position::new(usize::MAX, usize::MAX));
+    formatter.flush_comments(usize::MAX, 0);
+    formatter.out
+}
+
+struct Formatter<'a> {
+    comments: &'a [Spanned<String>],
+    next_comment: usize,
+    // Last source line emitted, used to preserve blank lines
+    last_line: usize,
+    out: String,
+}
+
+impl Formatter<'_> {
+    fn line(&mut self, depth: usize, text: &str) {
+        self.out.push_str(&INDENT.repeat(depth));
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    // Keeps a single blank line where the source had one or more
+    fn blank_line_before(&mut self, source_line: usize) {
+        if self.last_line > 0 && source_line > self.last_line + 1 && !self.out.ends_with("{\n") {
+            self.out.push('\n');
+        }
+    }
+
+    // Emits own-line comments that start before `line`
+    fn flush_comments(&mut self, line: usize, depth: usize) {
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            if comment.span.start.line >= line {
+                break;
+            }
+            self.blank_line_before(comment.span.start.line);
+            self.line(depth, &format!("#{}", comment.node.trim_end()));
+            self.last_line = comment.span.start.line;
+            self.next_comment += 1;
+        }
+    }
+
+    // Appends a comment sitting on the same source line as the text just
+    // emitted, unless it comes after `limit` (the end of the enclosing block)
+    fn trailing_comment(&mut self, line: usize, limit: Position) {
+        if let Some(comment) = self.comments.get(self.next_comment) {
+            if comment.span.start.line == line && comment.span.start < limit {
+                self.out.pop();
+                self.out.push_str(&format!("  #{}\n", comment.node.trim_end()));
+                self.next_comment += 1;
+            }
+        }
+    }
+
+    fn statements(&mut self, nodes: &[Node], depth: usize, limit: Position) {
+        for node in nodes {
+            self.flush_comments(node.span.start.line, depth);
+            self.blank_line_before(node.span.start.line);
+            self.statement(node, depth);
+            self.trailing_comment(node.span.end.line, limit);
+            self.last_line = node.span.end.line;
+        }
+    }
+
+    // Emits `header {` and the block body; the caller closes the brace
+    fn open_block(&mut self, header: &str, block: &Node, depth: usize) {
+        self.line(depth, &format!("{}{{", header));
+        self.trailing_comment(block.span.start.line, block.span.end);
+        self.last_line = block.span.start.line;
+        if let ASTNode::Block(nodes) = &block.node {
+            self.statements(nodes, depth + 1, block.span.end);
+        }
+        self.flush_comments(block.span.end.line, depth + 1);
+    }
+
+    fn statement(&mut self, node: &Node, depth: usize) {
+        stack::grow(|| {
+            match &node.node {
+                ASTNode::If(condition, then_branch, else_branch) => {
+                    self.open_block(&format!("if ({}) ", expression(condition)), then_branch, depth);
+                    if let Some(else_branch) = else_branch {
+                        self.open_block("} else ", else_branch, depth);
+                    }
+                    self.line(depth, "}");
+                }
+                ASTNode::Function(name, params, body) => {
+                    self.open_block(&format!("fn {}({}) ", name, params.join(", ")), body, depth);
+                    self.line(depth, "}");
+                }
+                ASTNode::Block(_) => {
+                    self.open_block("", node, depth);
+                    self.line(depth, "}");
+                }
+                ASTNode::Import(module, names) => self.line(depth, &import(module, names.as_deref())),
+                ASTNode::Export(inner) => {
+                    let start = self.out.len() + INDENT.len() * depth;
+                    self.statement(inner, depth);
+                    self.out.insert_str(start, "export ");
+                }
+                ASTNode::Call(..) => self.line(depth, &format!("call({})", expression(node))),
+                _ => self.line(depth, &expression(node)),
+            }
+        })
+    }
+}
+
+// Precedence level and right-associativity of a binary operator
+fn precedence(op: &Token) -> (u8, bool) {
+    let (left, right) = infix_binding_power(op).unwrap_or((0, 0));
+    (left.min(right), right < left)
+}
+
+// Precedence of an operand position: binary ops use their level, prefix ops
+// use the binding power of their operand, and everything else is atomic
+fn operand_precedence(node: &ASTNode) -> Option<u8> {
+    match node {
+        ASTNode::BinaryOp(_, op, _) => Some(precedence(op).0),
+        ASTNode::UnaryOp(Token::Not, _) => Some(NOT_BINDING_POWER),
+        ASTNode::UnaryOp(_, _) => Some(NEGATE_BINDING_POWER),
+        _ => None,
+    }
+}
+
+fn parenthesize(node: &Node, needs_parens: bool) -> String {
+    if needs_parens {
+        format!("({})", expression(node))
+    } else {
+        expression(node)
+    }
+}
+
+pub fn operator(op: &Token) -> &'static str {
+    match op {
+        Token::Plus => "+",
+        Token::Minus => "-",
+        Token::Star => "*",
+        Token::StarStar => "**",
+        Token::Slash => "/",
+        Token::Modulo => "%",
+        Token::GreaterThan => ">",
+        Token::LessThan => "<",
+        Token::GreaterEqual => ">=",
+        Token::LessEqual => "<=",
+        Token::EqualEqual => "==",
+        Token::NotEqual => "!=",
+        Token::And => "and",
+        Token::Or => "or",
+        Token::Not => "not ",
+        _ => panic!("Unexpected operator: {:?}", op),
+    }
+}
+
+pub fn number(value: &BigRational) -> String {
+    if value.is_integer() {
+        value.to_integer().to_string()
+    } else {
+        value.to_f64().unwrap().to_string()
+    }
+}
+
+fn arguments(args: &[&Node]) -> String {
+    args.iter().map(|arg| expression(arg)).collect::<Vec<_>>().join(", ")
+}
+
+pub fn import(module: &str, names: Option<&[String]>) -> String {
+    let module = match is_url(module) {
+        true => module,
+        false => module.strip_suffix(&format!(".{}", FILE_EXTENSION)).unwrap_or(module),
+    };
+    match names {
+        Some(names) => format!("import {} ({})", string(module), names.join(", ")),
+        None => format!("import {}", string(module)),
+    }
+}
+
+// A string literal that reads back as `value`
+pub fn string(value: &str) -> String {
+    let escaped = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\t', "\\t");
+    format!("\"{}\"", escaped)
+}
+
+pub fn expression(node: &Node) -> String {
+    stack::grow(|| {
+        match &node.node {
+            ASTNode::Float(value) => number(value),
+            ASTNode::Quantity(value, unit) => format!("{}{}", number(value), unit),
+            ASTNode::Identifier(name) => name.clone(),
+            ASTNode::StringLiteral(value) => string(value),
+            ASTNode::BinaryOp(left, op, right) => {
+                let (level, right_assoc) = precedence(op);
+                let left_parens = operand_precedence(&left.node).is_some_and(|child| child < level || (child == level && right_assoc));
+                let right_parens = operand_precedence(&right.node).is_some_and(|child| child < level || (child == level && !right_assoc));
+                format!("{} {} {}", parenthesize(left, left_parens), operator(op), parenthesize(right, right_parens))
+            }
+            ASTNode::UnaryOp(op, operand) => {
+                let level = operand_precedence(&node.node).unwrap();
+                let parens = operand_precedence(&operand.node).is_some_and(|child| child < level);
+                format!("{}{}", operator(op), parenthesize(operand, parens))
+            }
+            ASTNode::Index(list, index) => {
+                let parens = operand_precedence(&list.node).is_some();
+                format!("{}[{}]", parenthesize(list, parens), expression(index))
+            }
+            ASTNode::Args => "args()".to_string(),
+            ASTNode::Dalr => "dalr()".to_string(),
+            ASTNode::GreaterThan(left, right) => format!("{} > {}", expression(left), expression(right)),
+            ASTNode::LessThan(left, right) => format!("{} < {}", expression(left), expression(right)),
+            ASTNode::Assignment(name, value) => format!("{} = {}", name, expression(value)),
+            ASTNode::Call(name, args) => format!("{}({})", name, arguments(&args.iter().collect::<Vec<_>>())),
+            ASTNode::Pmap(function, list) => format!("pmap({}, {})", function, expression(list)),
+            other => match other.keyword() {
+                Some(keyword) if other.children().is_empty() => keyword.to_string(),
+                Some(keyword) => format!("{}({})", keyword, arguments(&other.children())),
+                None => panic!("Cannot format {} as an expression", other.name()),
+            },
+        }
+    })
+}
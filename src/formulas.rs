@@ -0,0 +1,420 @@
+// Meteorological formulas behind the builtins that need floating point
+// functions (exp, log), in f64. Temperatures are in °C and pressures in hPa
+// unless noted otherwise.
+
+use crate::constants::{kelvin_constant, Constants};
+use num_rational::BigRational;
+use num_traits::ToPrimitive;
+
+fn constant(value: &BigRational) -> f64 {
+    value.to_f64().unwrap_or(f64::NAN)
+}
+
+// Saturation vapour pressure formulas selectable with `svp`
+pub const SVP_FORMULAS: &[&str] = &["magnus", "buck", "goff_gratch"];
+
+// Saturation vapour pressure in hPa over water, or over ice when `ice` is
+// set and the temperature is below 0 °C. `formula` is one of SVP_FORMULAS.
+pub fn saturation_vapour_pressure(temp: f64, formula: &str, ice: bool) -> f64 {
+    let ice = ice && temp < 0.0;
+    match (formula, ice) {
+        // WMO (2008) Magnus coefficients
+        ("magnus", false) => 6.112 * (17.62 * temp / (243.12 + temp)).exp(),
+        ("magnus", true) => 6.112 * (22.46 * temp / (272.62 + temp)).exp(),
+        // Buck (1996)
+        ("buck", false) => 6.1121 * ((18.678 - temp / 234.5) * (temp / (257.14 + temp))).exp(),
+        ("buck", true) => 6.1115 * ((23.036 - temp / 333.7) * (temp / (279.82 + temp))).exp(),
+        // Goff and Gratch (1946), as given by the WMO (1966)
+        ("goff_gratch", false) => {
+            let ratio = 373.16 / (temp + 273.15);
+            let log = -7.90298 * (ratio - 1.0) + 5.02808 * ratio.log10()
+                - 1.3816e-7 * (10f64.powf(11.344 * (1.0 - 1.0 / ratio)) - 1.0)
+                + 8.1328e-3 * (10f64.powf(-3.49149 * (ratio - 1.0)) - 1.0)
+                + 1013.246f64.log10();
+            10f64.powf(log)
+        }
+        ("goff_gratch", true) => {
+            let ratio = 273.16 / (temp + 273.15);
+            let log = -9.09718 * (ratio - 1.0) - 3.56654 * ratio.log10() + 0.876793 * (1.0 - 1.0 / ratio) + 6.1071f64.log10();
+            10f64.powf(log)
+        }
+        _ => f64::NAN,
+    }
+}
+
+// Ratio of the gas constants of dry air and water vapour
+const EPSILON: f64 = 0.622;
+
+// Vapour pressure in hPa from the temperature and relative humidity (%)
+pub fn vapour_pressure(temp: f64, humidity: f64) -> f64 {
+    humidity / 100.0 * saturation_vapour_pressure(temp, "magnus", false)
+}
+
+// Mass of water vapour per mass of dry air, in g/kg
+pub fn mixing_ratio(temp: f64, humidity: f64, pressure: f64) -> f64 {
+    let vapour = vapour_pressure(temp, humidity);
+    1000.0 * EPSILON * vapour / (pressure - vapour)
+}
+
+// Mass of water vapour per mass of moist air, in g/kg
+pub fn specific_humidity(temp: f64, humidity: f64, pressure: f64) -> f64 {
+    let vapour = vapour_pressure(temp, humidity);
+    1000.0 * EPSILON * vapour / (pressure - (1.0 - EPSILON) * vapour)
+}
+
+// International Standard Atmosphere, which defines its own constants
+const ISA_G: f64 = 9.80665;
+const ISA_R: f64 = 287.05287;
+const ISA_T0: f64 = 288.15;
+const ISA_P0: f64 = 101325.0;
+const ISA_LAPSE_RATE: f64 = 0.0065;
+const ISA_TROPOPAUSE: f64 = 11000.0;
+// Top of the lower stratosphere, the highest altitude `isa` covers
+pub const ISA_CEILING: f64 = 20000.0;
+
+// Temperature (°C), pressure (hPa) and density (kg/m³) of the ISA at an
+// altitude in m: the temperature falls by 6.5 K/km up to the tropopause
+// and stays constant above it
+pub fn isa(altitude: f64) -> (f64, f64, f64) {
+    let (temp, pressure) = if altitude <= ISA_TROPOPAUSE {
+        troposphere(altitude)
+    } else {
+        let (temp, base) = troposphere(ISA_TROPOPAUSE);
+        (temp, base * (-ISA_G * (altitude - ISA_TROPOPAUSE) / (ISA_R * temp)).exp())
+    };
+    (temp - 273.15, pressure / 100.0, pressure / (ISA_R * temp))
+}
+
+// Temperature (K) and pressure (Pa) at an altitude in the troposphere
+fn troposphere(altitude: f64) -> (f64, f64) {
+    let temp = ISA_T0 - ISA_LAPSE_RATE * altitude;
+    (temp, ISA_P0 * (temp / ISA_T0).powf(ISA_G / (ISA_LAPSE_RATE * ISA_R)))
+}
+
+// Latent heat of vaporization at 0 °C (J/kg); `_lv_` is the value at 100 °C
+const LV_0C: f64 = 2.501e6;
+
+// Saturated (moist) adiabatic lapse rate in K/km
+pub fn moist_lapse_rate(temp: f64, pressure: f64, constants: &Constants) -> f64 {
+    let (g, cp, rd) = (constant(constants.get("g")), constant(constants.get("cp")), constant(constants.get("rd")));
+    let kelvin = temp + constant(kelvin_constant());
+    let mixing_ratio = mixing_ratio(temp, 100.0, pressure) / 1000.0;
+    let numerator = 1.0 + LV_0C * mixing_ratio / (rd * kelvin);
+    let denominator = cp + LV_0C * LV_0C * mixing_ratio * EPSILON / (rd * kelvin * kelvin);
+    1000.0 * g * numerator / denominator
+}
+
+// FAO-56 Penman-Monteith reference evapotranspiration in mm/day, from the
+// daily mean temperature, relative humidity (%), wind speed at 2 m (m/s),
+// net radiation at the surface (MJ/m²/day, not the incoming solar
+// radiation) and pressure. The soil heat flux of a day is taken as zero.
+pub fn reference_evapotranspiration(temp: f64, humidity: f64, wind: f64, net_radiation: f64, pressure: f64) -> f64 {
+    // Vapour pressures in kPa, with FAO-56's own coefficients
+    let saturation = 0.6108 * (17.27 * temp / (temp + 237.3)).exp();
+    let actual = saturation * humidity / 100.0;
+    let slope = 4098.0 * saturation / (temp + 237.3).powi(2);
+    let psychrometric = 0.665e-3 * pressure / 10.0;
+    let numerator = 0.408 * slope * net_radiation + psychrometric * 900.0 / (temp + 273.0) * wind * (saturation - actual);
+    numerator / (slope + psychrometric * (1.0 + 0.34 * wind))
+}
+
+// Eastward (u) and northward (v) components of a wind blowing from
+// `direction` degrees (meteorological convention)
+pub fn wind_components(speed: f64, direction: f64) -> (f64, f64) {
+    let radians = direction.to_radians();
+    (-speed * radians.sin(), -speed * radians.cos())
+}
+
+// Speed and direction (degrees the wind blows from, 0 for calm) of a wind
+// with the given u and v components
+pub fn wind_from_components(u: f64, v: f64) -> (f64, f64) {
+    let speed = u.hypot(v);
+    if speed == 0.0 {
+        return (0.0, 0.0);
+    }
+    (speed, (-u).atan2(-v).to_degrees().rem_euclid(360.0))
+}
+
+// Snow-to-liquid ratio estimated from the temperature with the Kuchera
+// method (12:1 at -2 °C, higher when colder), 0 when it is too warm for snow
+pub fn snow_liquid_ratio(temp: f64) -> f64 {
+    let below = 271.16 - (temp + 273.15);
+    let ratio = if below < 0.0 { 12.0 + 2.0 * below } else { 12.0 + below };
+    ratio.max(0.0)
+}
+
+// WMO obscuration for a visibility in m: fog below 1 km, and mist (or haze
+// when the air is drier than 80%) up to 5 km
+pub fn fog_category(visibility: f64, humidity: Option<f64>) -> &'static str {
+    if visibility < 1000.0 {
+        "fog"
+    } else if visibility <= 5000.0 {
+        match humidity {
+            Some(humidity) if humidity < 80.0 => "haze",
+            _ => "mist",
+        }
+    } else {
+        "clear"
+    }
+}
+
+// Days since 1970-01-01 of January 1st of `year` (proleptic Gregorian)
+fn days_to_year(year: i64) -> i64 {
+    let year = year - 1;
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    era * 146097 + year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + 306 - 719468
+}
+
+// Day of the year (1 for January 1st), hour of the day and length of the
+// year in days for a Unix timestamp in seconds (UTC)
+pub fn day_of_year(timestamp: f64) -> (f64, f64, f64) {
+    let days = (timestamp / 86400.0).floor();
+    let hours = (timestamp - days * 86400.0) / 3600.0;
+    let days = days as i64;
+    // Estimate the year, then correct it
+    let mut year = 1970 + days.div_euclid(365);
+    while days_to_year(year) > days {
+        year -= 1;
+    }
+    while days_to_year(year + 1) <= days {
+        year += 1;
+    }
+    let length = days_to_year(year + 1) - days_to_year(year);
+    ((days - days_to_year(year) + 1) as f64, hours, length as f64)
+}
+
+// Fractional year in radians used by the NOAA solar position equations
+fn fractional_year(day: f64, hours: f64, length: f64) -> f64 {
+    2.0 * std::f64::consts::PI / length * (day - 1.0 + (hours - 12.0) / 24.0)
+}
+
+fn declination(year: f64) -> f64 {
+    0.006918 - 0.399912 * year.cos() + 0.070257 * year.sin() - 0.006758 * (2.0 * year).cos() + 0.000907 * (2.0 * year).sin()
+        - 0.002697 * (3.0 * year).cos()
+        + 0.00148 * (3.0 * year).sin()
+}
+
+// Solar declination in degrees at noon on a day of the year
+pub fn solar_declination(day: f64) -> f64 {
+    declination(fractional_year(day, 12.0, 365.0)).to_degrees()
+}
+
+// Solar zenith angle in degrees at a latitude and longitude (degrees east)
+// and a Unix timestamp, with the NOAA equations
+pub fn solar_zenith(latitude: f64, longitude: f64, timestamp: f64) -> f64 {
+    let (day, hours, length) = day_of_year(timestamp);
+    let year = fractional_year(day, hours, length);
+    // Equation of time in minutes
+    let equation = 229.18
+        * (0.000075 + 0.001868 * year.cos() - 0.032077 * year.sin() - 0.014615 * (2.0 * year).cos() - 0.040849 * (2.0 * year).sin());
+    let solar_minutes = hours * 60.0 + equation + 4.0 * longitude;
+    let hour_angle = (solar_minutes / 4.0 - 180.0).to_radians();
+    let (latitude, declination) = (latitude.to_radians(), declination(year));
+    let cosine = latitude.sin() * declination.sin() + latitude.cos() * declination.cos() * hour_angle.cos();
+    cosine.clamp(-1.0, 1.0).acos().to_degrees()
+}
+
+// Ozone column assumed by `uv_index` when none is given, in Dobson units
+pub const DEFAULT_OZONE: f64 = 300.0;
+
+// Clear-sky UV index from the solar zenith angle (degrees) and the ozone
+// column (DU), with Madronich's (2007) approximation
+pub fn uv_index(zenith: f64, ozone: f64) -> f64 {
+    let cosine = zenith.to_radians().cos();
+    if cosine <= 0.0 {
+        return 0.0;
+    }
+    12.5 * cosine.powf(2.42) * (ozone / 300.0).powf(-1.23)
+}
+
+// Ratio of the specific heats of air
+const HEAT_CAPACITY_RATIO: f64 = 1.4;
+
+// Speed of sound in m/s in moist air at standard pressure, from the virtual
+// temperature: water vapour is lighter than air, so humid air carries sound
+// slightly faster
+pub fn speed_of_sound(temp: f64, humidity: f64, constants: &Constants) -> f64 {
+    let pressure = constant(constants.get("p0")) / 100.0;
+    let kelvin = temp + constant(kelvin_constant());
+    let virtual_temp = kelvin / (1.0 - vapour_pressure(temp, humidity) / pressure * (1.0 - EPSILON));
+    (HEAT_CAPACITY_RATIO * constant(constants.get("rd")) * virtual_temp).sqrt()
+}
+
+// Altimeter setting (QNH) in hPa from the station pressure in hPa and the
+// station elevation in m, with the NWS formula based on the standard
+// atmosphere rather than the actual temperature
+pub fn altimeter_setting(pressure: f64, elevation: f64) -> f64 {
+    const EXPONENT: f64 = 0.190284;
+    let pressure = pressure - 0.3;
+    let standard = 1013.25f64.powf(EXPONENT) * ISA_LAPSE_RATE / ISA_T0;
+    pressure * (1.0 + standard * elevation / pressure.powf(EXPONENT)).powf(1.0 / EXPONENT)
+}
+
+// Relative humidity (%) from dry-bulb and wet-bulb temperatures and the
+// pressure, with the psychrometric equation for a ventilated psychrometer
+pub fn rh_from_wetbulb(dry: f64, wet: f64, pressure: f64) -> f64 {
+    let coefficient = 6.6e-4 * (1.0 + 0.00115 * wet);
+    let vapour = saturation_vapour_pressure(wet, "magnus", false) - coefficient * pressure * (dry - wet);
+    100.0 * vapour / saturation_vapour_pressure(dry, "magnus", false)
+}
+
+// Mean radius of the Earth in km (IUGG)
+const EARTH_RADIUS: f64 = 6371.0088;
+
+// Great-circle distance in km between two points given by their latitudes
+// and longitudes in degrees, with the haversine formula
+pub fn haversine(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let (dlat, dlon) = (lat2 - lat1, (lon2 - lon1).to_radians());
+    let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().min(1.0).asin()
+}
+
+// Initial bearing in degrees clockwise from north for the great circle from
+// the first point to the second
+pub fn bearing(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let dlon = (lon2 - lon1).to_radians();
+    let y = dlon.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * dlon.cos();
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+// Dew point with the Arden Buck formula, more accurate than Magnus below
+// freezing; the humidity is in percent
+pub fn buck_dewpoint(temp: f64, humidity: f64) -> f64 {
+    let (b, c, d) = (18.678, 257.14, 234.5);
+    let gamma = (humidity / 100.0 * ((b - temp / d) * (temp / (c + temp))).exp()).ln();
+    c * gamma / (b - gamma)
+}
+
+// Number of observations and their mean speed (NaN when there are none) in
+// each of `sectors` equal direction sectors, the first centred on north.
+// Calm observations (speed 0) are left out.
+pub fn windrose(directions: &[f64], speeds: &[f64], sectors: usize) -> Vec<(usize, f64)> {
+    let width = 360.0 / sectors as f64;
+    let mut totals = vec![(0, 0.0); sectors];
+    for (direction, speed) in directions.iter().zip(speeds) {
+        if *speed == 0.0 {
+            continue;
+        }
+        let sector = ((direction + width / 2.0).rem_euclid(360.0) / width) as usize % sectors;
+        totals[sector].0 += 1;
+        totals[sector].1 += speed;
+    }
+    totals.into_iter().map(|(count, total)| (count, if count == 0 { f64::NAN } else { total / count as f64 })).collect()
+}
+
+// Natural log of the gamma function, with the Lanczos approximation (g = 7)
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let series = COEFFICIENTS.iter().enumerate().skip(1).fold(COEFFICIENTS[0], |sum, (index, coefficient)| sum + coefficient / (x + index as f64));
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+}
+
+// Regularized lower incomplete gamma function P(a, x): a series below
+// x = a + 1 and a continued fraction above, as in Numerical Recipes
+fn gamma_cdf(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    let prefactor = (-x + a * x.ln() - ln_gamma(a)).exp();
+    if x < a + 1.0 {
+        let (mut term, mut sum) = (1.0 / a, 1.0 / a);
+        for n in 1..1000 {
+            term *= x / (a + n as f64);
+            sum += term;
+            if term.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        return sum * prefactor;
+    }
+    const TINY: f64 = 1e-300;
+    let mut b = x + 1.0 - a;
+    let (mut c, mut d) = (1.0 / TINY, 1.0 / b);
+    let mut fraction = d;
+    for i in 1..1000 {
+        let an = -(i as f64) * (i as f64 - a);
+        b += 2.0;
+        d = an * d + b;
+        d = if d.abs() < TINY { TINY } else { d };
+        c = b + an / c;
+        c = if c.abs() < TINY { TINY } else { c };
+        d = 1.0 / d;
+        let delta = d * c;
+        fraction *= delta;
+        if (delta - 1.0).abs() < 1e-15 {
+            break;
+        }
+    }
+    1.0 - prefactor * fraction
+}
+
+// Standard normal deviate with the cumulative probability `p`, with the
+// rational approximation of Abramowitz and Stegun (26.2.23) that McKee et
+// al. (1993) used for the SPI
+fn normal_quantile(p: f64) -> f64 {
+    if p <= 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if p >= 1.0 {
+        return f64::INFINITY;
+    }
+    let deviate = |q: f64| {
+        let t = (-2.0 * q.ln()).sqrt();
+        t - (2.515517 + 0.802853 * t + 0.010328 * t * t) / (1.0 + 1.432788 * t + 0.189269 * t * t + 0.001308 * t * t * t)
+    };
+    if p < 0.5 { -deviate(p) } else { deviate(1.0 - p) }
+}
+
+// Standardized Precipitation Index of each month of a monthly precipitation
+// series, from the totals over the last `timescale` months. The totals of
+// each calendar month (every twelfth value) are fitted with a gamma
+// distribution by maximum likelihood (Thom's approximation), with dry months
+// counted separately, and the probability of each total is mapped to a
+// standard normal deviate. Months without a full timescale before them, and
+// calendar months with fewer than two wet totals, are NaN.
+pub fn spi(precipitation: &[f64], timescale: usize) -> Vec<f64> {
+    let totals: Vec<f64> = (0..precipitation.len())
+        .map(|month| if month + 1 < timescale { f64::NAN } else { precipitation[month + 1 - timescale..=month].iter().sum() })
+        .collect();
+    let mut result = vec![f64::NAN; totals.len()];
+    for calendar_month in 0..12 {
+        let months: Vec<usize> = (calendar_month..totals.len()).step_by(12).filter(|month| !totals[*month].is_nan()).collect();
+        let wet: Vec<f64> = months.iter().map(|month| totals[*month]).filter(|total| *total > 0.0).collect();
+        if wet.len() < 2 {
+            continue;
+        }
+        let mean = wet.iter().sum::<f64>() / wet.len() as f64;
+        let a = mean.ln() - wet.iter().map(|total| total.ln()).sum::<f64>() / wet.len() as f64;
+        if a <= 0.0 {
+            continue;
+        }
+        let shape = (1.0 + (1.0 + 4.0 * a / 3.0).sqrt()) / (4.0 * a);
+        let scale = mean / shape;
+        let dry = (months.len() - wet.len()) as f64 / months.len() as f64;
+        for month in months {
+            let probability = dry + (1.0 - dry) * gamma_cdf(shape, totals[month] / scale);
+            result[month] = normal_quantile(probability);
+        }
+    }
+    result
+}
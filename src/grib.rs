@@ -0,0 +1,321 @@
+// Fields from GRIB2 files, the format of NWP model output such as GFS, HRRR
+// and ECMWF open data. Only what `readgrib` needs is decoded: regular grids,
+// product templates 4.0 to 4.15 and simple, complex and IEEE packing.
+
+// A 2D field, row by row as stored in the file. Missing points are NaN.
+// Regular latitude/longitude grids also give the latitude of each row and
+// the longitude of each column, in degrees.
+pub struct Field {
+    pub rows: Vec<Vec<f64>>,
+    pub latitudes: Option<Vec<f64>>,
+    pub longitudes: Option<Vec<f64>>,
+}
+
+// NCEP abbreviations of common parameters, with their discipline, category
+// and number in the WMO code table 4.2
+const PARAMETERS: &[(&str, (u8, u8, u8))] = &[
+    ("TMP", (0, 0, 0)),
+    ("POT", (0, 0, 2)),
+    ("TMAX", (0, 0, 4)),
+    ("TMIN", (0, 0, 5)),
+    ("DPT", (0, 0, 6)),
+    ("SPFH", (0, 1, 0)),
+    ("RH", (0, 1, 1)),
+    ("PWAT", (0, 1, 3)),
+    ("PRATE", (0, 1, 7)),
+    ("APCP", (0, 1, 8)),
+    ("SNOD", (0, 1, 11)),
+    ("WEASD", (0, 1, 13)),
+    ("WDIR", (0, 2, 0)),
+    ("WIND", (0, 2, 1)),
+    ("UGRD", (0, 2, 2)),
+    ("VGRD", (0, 2, 3)),
+    ("VVEL", (0, 2, 8)),
+    ("ABSV", (0, 2, 10)),
+    ("GUST", (0, 2, 22)),
+    ("PRES", (0, 3, 0)),
+    ("PRMSL", (0, 3, 1)),
+    ("HGT", (0, 3, 5)),
+    ("TCDC", (0, 6, 1)),
+    ("CAPE", (0, 7, 6)),
+    ("CIN", (0, 7, 7)),
+    ("VIS", (0, 19, 0)),
+    ("LAND", (2, 0, 0)),
+];
+
+// A parameter as an abbreviation of PARAMETERS or as
+// `discipline.category.number`, e.g. `0.0.0` for temperature
+fn parameter(text: &str) -> Result<(u8, u8, u8), String> {
+    if let Some((_, code)) = PARAMETERS.iter().find(|(name, _)| name.eq_ignore_ascii_case(text)) {
+        return Ok(*code);
+    }
+    let numbers: Vec<u8> = text.split('.').filter_map(|part| part.parse().ok()).collect();
+    match numbers[..] {
+        [discipline, category, number] if text.split('.').count() == 3 => Ok((discipline, category, number)),
+        _ => Err(format!("Unknown GRIB parameter '{}', expected an abbreviation such as TMP or discipline.category.number", text)),
+    }
+}
+
+// Type of the fixed surface in the WMO code table 4.5 and, for surfaces at
+// a height or pressure, its value in metres or pascals
+fn level(text: &str) -> Result<(u8, Option<f64>), String> {
+    let invalid = || Err(format!("Unknown GRIB level '{}', expected e.g. surface, mean sea level, 500 mb or 2 m above ground", text));
+    let text = text.trim().to_lowercase();
+    let surface = match text.as_str() {
+        "surface" | "sfc" => Some(1),
+        "mean sea level" | "msl" => Some(101),
+        "entire atmosphere" => Some(200),
+        _ => None,
+    };
+    if let Some(surface) = surface {
+        return Ok((surface, None));
+    }
+    let (number, unit) = text.split_at(text.find(|ch: char| !ch.is_ascii_digit() && ch != '.').unwrap_or(text.len()));
+    let Ok(number) = number.parse::<f64>() else {
+        return invalid();
+    };
+    match unit.trim() {
+        "" | "mb" | "hpa" => Ok((100, Some(number * 100.0))),
+        "pa" => Ok((100, Some(number))),
+        "m" | "m above ground" => Ok((103, Some(number))),
+        "m above mean sea level" => Ok((102, Some(number))),
+        _ => invalid(),
+    }
+}
+
+// Octets `first` to `last` of a section as an unsigned number, numbered
+// from 1 as in the WMO tables
+fn unsigned(section: &[u8], first: usize, last: usize) -> Result<u64, String> {
+    let bytes = section.get(first - 1..last).ok_or("The GRIB file is truncated")?;
+    Ok(bytes.iter().fold(0, |number, byte| number << 8 | *byte as u64))
+}
+
+// Signed numbers in GRIB2 keep their sign in the top bit
+fn signed(section: &[u8], first: usize, last: usize) -> Result<i64, String> {
+    let number = unsigned(section, first, last)?;
+    let sign = 1 << (8 * (last - first + 1) - 1);
+    Ok(if number & sign != 0 { -((number & !sign) as i64) } else { number as i64 })
+}
+
+struct Bits<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Bits<'_> {
+    fn read(&mut self, count: u32) -> Result<u64, String> {
+        let mut number = 0;
+        for _ in 0..count {
+            let byte = self.data.get(self.position / 8).ok_or("The GRIB data section is truncated")?;
+            number = number << 1 | (*byte >> (7 - self.position % 8) & 1) as u64;
+            self.position += 1;
+        }
+        Ok(number)
+    }
+
+    fn align(&mut self) {
+        self.position = self.position.div_ceil(8) * 8;
+    }
+}
+
+// The field of the first message with the parameter and level, e.g. "TMP"
+// and "2 m above ground"
+pub fn read(data: &[u8], parameter_name: &str, level_name: &str) -> Result<Field, String> {
+    let wanted = parameter(parameter_name)?;
+    let (surface, height) = level(level_name)?;
+    let mut start = 0;
+    while let Some(offset) = data[start..].windows(4).position(|window| window == b"GRIB") {
+        let message = &data[start + offset..];
+        if unsigned(message, 8, 8)? != 2 {
+            return Err("Only GRIB edition 2 files can be read".to_string());
+        }
+        let discipline = unsigned(message, 7, 7)? as u8;
+        let length = unsigned(message, 9, 16)? as usize;
+        let message = message.get(..length).ok_or("The GRIB file is truncated")?;
+        let (mut grid, mut product, mut representation): (&[u8], &[u8], &[u8]) = (&[], &[], &[]);
+        let mut bitmap = None;
+        let mut position = 16;
+        while position + 4 < message.len() && &message[position..position + 4] != b"7777" {
+            let size = unsigned(message, position + 1, position + 4)? as usize;
+            let section = message.get(position..position + size).filter(|_| size > 5).ok_or("The GRIB file has a malformed section")?;
+            match section[4] {
+                3 => grid = section,
+                4 => product = section,
+                5 => representation = section,
+                // 254 keeps the bitmap of the previous field
+                6 => match section[5] {
+                    0 => bitmap = Some(&section[6..]),
+                    254 => {}
+                    _ => bitmap = None,
+                },
+                7 => {
+                    let template = unsigned(product, 8, 9)?;
+                    let code = (discipline, unsigned(product, 10, 10)? as u8, unsigned(product, 11, 11)? as u8);
+                    let fixed = template <= 15 && code == wanted && unsigned(product, 23, 23)? == surface as u64;
+                    let at_height = match height {
+                        Some(height) => fixed && matches(product, height)?,
+                        None => fixed,
+                    };
+                    if at_height {
+                        return field(grid, representation, bitmap, &section[5..]);
+                    }
+                }
+                _ => {}
+            }
+            position += size;
+        }
+        start += offset + length.max(4);
+    }
+    Err(format!("The GRIB file has no {} at {}", parameter_name, level_name))
+}
+
+// Whether the first fixed surface of a product definition is at `height`
+fn matches(product: &[u8], height: f64) -> Result<bool, String> {
+    let scale = signed(product, 24, 24)?;
+    let value = unsigned(product, 25, 28)? as f64 / 10f64.powi(scale as i32);
+    Ok((value - height).abs() <= 1e-6 * height.abs().max(1.0))
+}
+
+// Values packed in a data section as described by a data representation
+// section, NaN where missing
+fn values(representation: &[u8], data: &[u8]) -> Result<Vec<f64>, String> {
+    let count = unsigned(representation, 6, 9)? as usize;
+    let template = unsigned(representation, 10, 11)?;
+    if template == 4 {
+        let size = if unsigned(representation, 12, 12)? == 2 { 8 } else { 4 };
+        let chunks = data.get(..count * size).ok_or("The GRIB data section is truncated")?.chunks_exact(size);
+        return Ok(chunks
+            .map(|chunk| {
+                let bits = chunk.iter().fold(0, |bits, byte| bits << 8 | *byte as u64);
+                if size == 4 { f32::from_bits(bits as u32) as f64 } else { f64::from_bits(bits) }
+            })
+            .collect());
+    }
+    if !matches!(template, 0 | 2 | 3) {
+        return Err(format!("GRIB data representation template 5.{} is not supported, only simple, complex and IEEE packing", template));
+    }
+    let reference = f32::from_bits(unsigned(representation, 12, 15)? as u32) as f64;
+    let binary = 2f64.powi(signed(representation, 16, 17)? as i32);
+    let decimal = 10f64.powi(-signed(representation, 18, 19)? as i32);
+    let bits = unsigned(representation, 20, 20)? as u32;
+    let unpack = |packed: Option<i64>| packed.map_or(f64::NAN, |packed| (reference + packed as f64 * binary) * decimal);
+    let mut reader = Bits { data, position: 0 };
+    if template == 0 {
+        return (0..count).map(|_| Ok(unpack(Some(reader.read(bits)? as i64)))).collect();
+    }
+    complex(representation, data, count, bits).map(|packed| packed.into_iter().map(unpack).collect())
+}
+
+// Integers of complex packing (templates 5.2 and 5.3): values in groups,
+// each with its own reference and width, optionally as first or second
+// differences of neighbouring values
+fn complex(representation: &[u8], data: &[u8], count: usize, bits: u32) -> Result<Vec<Option<i64>>, String> {
+    let missing = unsigned(representation, 23, 23)?;
+    let groups = unsigned(representation, 32, 35)? as usize;
+    let (width_reference, width_bits) = (unsigned(representation, 36, 36)?, unsigned(representation, 37, 37)? as u32);
+    let (length_reference, length_increment) = (unsigned(representation, 38, 41)?, unsigned(representation, 42, 42)?);
+    let (last_length, length_bits) = (unsigned(representation, 43, 46)?, unsigned(representation, 47, 47)? as u32);
+    let (order, octets) = match unsigned(representation, 10, 11)? {
+        3 => (unsigned(representation, 48, 48)? as usize, unsigned(representation, 49, 49)? as usize),
+        _ => (0, 0),
+    };
+    if order > 2 {
+        return Err(format!("GRIB spatial differencing of order {} is not supported", order));
+    }
+    // The first values and the minimum difference come before the groups
+    let mut leading = Vec::new();
+    if order > 0 {
+        for index in 0..=order {
+            leading.push(signed(data, index * octets + 1, (index + 1) * octets)?);
+        }
+    }
+    let minimum = leading.pop().unwrap_or(0);
+    let skip = if order > 0 { (order + 1) * octets } else { 0 };
+    let mut reader = Bits { data: data.get(skip..).unwrap_or_default(), position: 0 };
+    let references = (0..groups).map(|_| reader.read(bits)).collect::<Result<Vec<_>, _>>()?;
+    reader.align();
+    let widths = (0..groups).map(|_| Ok(reader.read(width_bits)? + width_reference)).collect::<Result<Vec<_>, String>>()?;
+    reader.align();
+    let mut lengths = (0..groups).map(|_| Ok(length_reference + reader.read(length_bits)? * length_increment)).collect::<Result<Vec<_>, String>>()?;
+    reader.align();
+    if let Some(last) = lengths.last_mut() {
+        *last = last_length;
+    }
+    let mut packed = Vec::with_capacity(count);
+    for ((reference, width), length) in references.into_iter().zip(widths).zip(lengths) {
+        for _ in 0..length {
+            let value = reader.read(width as u32)?;
+            // With missing value management, all ones (and all ones less one
+            // for secondary missing values) mark missing values
+            let (all_ones, group) = if width == 0 { ((1 << bits) - 1, reference) } else { ((1 << width) - 1, value) };
+            let is_missing = (missing >= 1 && group == all_ones) || (missing == 2 && group == all_ones - 1);
+            packed.push(if is_missing { None } else { Some((reference + value) as i64) });
+        }
+    }
+    if packed.len() != count {
+        return Err(format!("The GRIB data section has {} values where {} were expected", packed.len(), count));
+    }
+    // Undo the differencing, skipping missing values
+    if order > 0 {
+        let mut previous: Vec<i64> = Vec::new();
+        for value in packed.iter_mut().flatten() {
+            *value = match (previous.len(), order) {
+                (index, _) if index < order => leading[index],
+                (_, 1) => *value + minimum + previous[previous.len() - 1],
+                _ => *value + minimum + 2 * previous[previous.len() - 1] - previous[previous.len() - 2],
+            };
+            previous.push(*value);
+        }
+    }
+    Ok(packed)
+}
+
+// The field of a grid definition, data representation, bitmap and data
+// section
+fn field(grid: &[u8], representation: &[u8], bitmap: Option<&[u8]>, data: &[u8]) -> Result<Field, String> {
+    let points = unsigned(grid, 7, 10)? as usize;
+    let template = unsigned(grid, 13, 14)?;
+    // Octet of the scanning mode in each supported grid template
+    let scanning = match template {
+        0 | 1 | 40 => 72,
+        10 => 60,
+        20 | 30 => 65,
+        _ => return Err(format!("GRIB grid definition template 3.{} is not supported", template)),
+    };
+    let (columns, rows) = (unsigned(grid, 31, 34)? as usize, unsigned(grid, 35, 38)? as usize);
+    if unsigned(grid, 11, 11)? != 0 || columns * rows != points {
+        return Err("Only GRIB fields on regular grids can be read".to_string());
+    }
+    let mut packed = values(representation, data)?.into_iter();
+    let mut values = Vec::with_capacity(points);
+    for point in 0..points {
+        let present = bitmap.is_none_or(|bitmap| bitmap.get(point / 8).is_some_and(|byte| byte >> (7 - point % 8) & 1 == 1));
+        values.push(if present { packed.next().ok_or("The GRIB data section has too few values")? } else { f64::NAN });
+    }
+    // Points are stored along rows unless the scanning mode says columns,
+    // and every other row is reversed in boustrophedonic order
+    let mode = unsigned(grid, scanning, scanning)?;
+    let mut field: Vec<Vec<f64>> = match mode & 0x20 {
+        0 => values.chunks(columns).map(<[f64]>::to_vec).collect(),
+        _ => (0..rows).map(|row| (0..columns).map(|column| values[column * rows + row]).collect()).collect(),
+    };
+    if mode & 0x10 != 0 {
+        field.iter_mut().skip(1).step_by(2).for_each(|row| row.reverse());
+    }
+    let (mut latitudes, mut longitudes) = (None, None);
+    if template == 0 {
+        // Angles are in millionths of a degree unless a basic angle is given
+        let (basic, subdivisions) = (unsigned(grid, 39, 42)?, unsigned(grid, 43, 46)?);
+        let unit = match (basic, subdivisions) {
+            (0 | 0xFFFFFFFF, _) | (_, 0 | 0xFFFFFFFF) => 1e-6,
+            (basic, subdivisions) => basic as f64 / subdivisions as f64,
+        };
+        let (first_latitude, first_longitude) = (signed(grid, 47, 50)? as f64 * unit, signed(grid, 51, 54)? as f64 * unit);
+        let (column_step, row_step) = (unsigned(grid, 64, 67)? as f64 * unit, unsigned(grid, 68, 71)? as f64 * unit);
+        let column_step = if mode & 0x80 != 0 { -column_step } else { column_step };
+        let row_step = if mode & 0x40 != 0 { row_step } else { -row_step };
+        latitudes = Some((0..rows).map(|row| first_latitude + row as f64 * row_step).collect());
+        longitudes = Some((0..columns).map(|column| first_longitude + column as f64 * column_step).collect());
+    }
+    Ok(Field { rows: field, latitudes, longitudes })
+}
@@ -0,0 +1,176 @@
+use crate::ast::Node;
+use crate::lexer::Lexer;
+use crate::parser::{infix_binding_power, Parser};
+use crate::stack;
+use crate::token::Token;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+// Re-parses a changing source (REPL buffer, editor contents) statement by
+// statement. The source is split into chunks of whole lines that each hold
+// one or more top-level statements; chunks whose text is unchanged since an
+// earlier parse reuse their cached nodes, moved to the chunk's current line.
+#[derive(Default)]
+pub struct IncrementalParser {
+    // Nodes of each chunk, keyed by a hash of its text, with spans relative
+    // to the chunk's first line
+    cache: HashMap<u64, Vec<Node>>,
+    // Chunks reused and parsed by the last call to `parse`
+    reused: usize,
+    parsed: usize,
+}
+
+impl IncrementalParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(&mut self, source: &str) -> Vec<Node> {
+        let lines: Vec<&str> = source.split_inclusive('\n').collect();
+        let mut live = HashMap::new();
+        let mut nodes = Vec::new();
+        self.reused = 0;
+        self.parsed = 0;
+        for (first, last) in chunks(source) {
+            let text: String = lines[first - 1..last].concat();
+            let key = hash(&text);
+            let chunk = match self.cache.remove(&key).or_else(|| live.get(&key).cloned()) {
+                Some(chunk) => {
+                    self.reused += 1;
+                    chunk
+                }
+                None => {
+                    self.parsed += 1;
+                    Parser::new(Lexer::new(text)).parse()
+                }
+            };
+            nodes.extend(chunk.iter().cloned().map(|mut node| {
+                shift_lines(&mut node, first - 1);
+                node
+            }));
+            live.insert(key, chunk);
+        }
+        // Only chunks present in the latest source stay cached
+        self.cache = live;
+        nodes
+    }
+
+    // (reused, parsed) chunk counts of the last call to `parse`
+    pub fn stats(&self) -> (usize, usize) {
+        (self.reused, self.parsed)
+    }
+}
+
+fn hash(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn shift_lines(node: &mut Node, lines: usize) {
+    stack::grow(|| {
+        node.span.start.line += lines;
+        node.span.end.line += lines;
+        for child in node.node.children_mut() {
+            shift_lines(child, lines);
+        }
+    })
+}
+
+// Tokens that may begin a top-level statement
+fn starts_statement(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Identifier(_)
+            | Token::Print
+            | Token::If
+            | Token::Function
+            | Token::Import
+            | Token::Export
+            | Token::Call
+            | Token::Assert
+            | Token::AssertApprox
+            | Token::SetConstant
+            | Token::WriteJson
+            | Token::Sleep
+    )
+}
+
+// Tokens after which the statement must continue
+fn expects_operand(token: &Token) -> bool {
+    matches!(token, Token::Assign | Token::Comma | Token::LParen | Token::LBracket | Token::Not | Token::LBrace) || infix_binding_power(token).is_some()
+}
+
+// Whether the source is a finished piece of input rather than the start of
+// a longer one: strings are closed, brackets balanced and the last token
+// does not need an operand. The REPL keeps reading lines until this holds.
+pub fn is_complete(source: &str) -> bool {
+    let mut in_string = false;
+    let mut in_comment = false;
+    let mut escaped = false;
+    for ch in source.chars() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '\n' => in_comment = false,
+            '#' if !in_string => in_comment = true,
+            '"' if !in_comment => in_string = !in_string,
+            _ => {}
+        }
+    }
+    if in_string {
+        return false;
+    }
+    let mut lexer = Lexer::new(source.to_string());
+    let mut depth = 0isize;
+    let mut last = Token::EOF;
+    loop {
+        let token = lexer.next_token().node;
+        match token {
+            Token::EOF => break,
+            Token::LParen | Token::LBrace | Token::LBracket => depth += 1,
+            Token::RParen | Token::RBrace | Token::RBracket => depth -= 1,
+            _ => {}
+        }
+        last = token;
+    }
+    depth <= 0 && !expects_operand(&last)
+}
+
+// Splits the source into inclusive (first, last) line ranges. A new chunk
+// starts at the first token of a line when it is outside any brackets, can
+// begin a statement, and the previous token does not need an operand. Bare
+// `{ }` blocks stay with the chunk before them, which parses the same.
+fn chunks(source: &str) -> Vec<(usize, usize)> {
+    let mut lexer = Lexer::new(source.to_string());
+    let mut starts = Vec::new();
+    let mut depth = 0usize;
+    let mut previous: Option<(Token, usize)> = None;
+    loop {
+        let token = lexer.next_token();
+        if token.node == Token::EOF {
+            break;
+        }
+        let line = token.span.start.line;
+        let first_on_line = previous.as_ref().is_none_or(|(_, previous_line)| *previous_line < line);
+        let continues = previous.as_ref().is_some_and(|(previous, _)| expects_operand(previous));
+        if depth == 0 && first_on_line && !continues && starts_statement(&token.node) {
+            starts.push(line);
+        }
+        match token.node {
+            Token::LParen | Token::LBrace | Token::LBracket => depth += 1,
+            Token::RParen | Token::RBrace | Token::RBracket => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        // Multi-line tokens (strings) end on a later line
+        previous = Some((token.node, token.span.end.line));
+    }
+    let line_count = source.lines().count();
+    let mut ranges = Vec::new();
+    for (index, first) in starts.iter().enumerate() {
+        let last = starts.get(index + 1).map_or(line_count, |next| next - 1);
+        ranges.push((*first, last));
+    }
+    ranges
+}
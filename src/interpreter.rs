@@ -1,217 +1,1673 @@
 use num_bigint::BigInt;
 use num_rational::BigRational;
-use crate::ast::ASTNode;
+use crate::ast::{ASTNode, Node};
+use crate::span::Span;
+use crate::stack;
+use crate::format::operator;
 use crate::token::Token;
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
-use num_traits::ToPrimitive;
+use crate::serialize::Snapshot;
+use crate::value::{Numeric, Secret, Value};
+use crate::configs::{CONFIG_FILE, DEFAULT_PRECISION, FILE_EXTENSION, KEYRING_SERVICE, MAX_PRECISION, PACKAGE_ENTRY};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::panic::{self, AssertUnwindSafe};
+use rayon::prelude::*;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
 use num_complex::Complex;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use num_traits::Zero;
 
 use crate::constants::*;
+use crate::formulas::{self, saturation_vapour_pressure, DEFAULT_OZONE, ISA_CEILING, SVP_FORMULAS};
+use crate::units::{self, Compound, Dimension, Unit};
+use crate::coords;
+use crate::timezone;
+use crate::remote;
+use crate::stations;
+
+// Observer notified as statements execute (used by the debugger and --trace)
+pub trait ExecutionHook: Send {
+    fn before_statement(&mut self, node: &Node, interpreter: &Interpreter);
+
+    fn after_statement(&mut self, _span: Span, _interpreter: &Interpreter) {}
+
+    // Called with the value of an `if` condition before a branch is chosen
+    fn condition_evaluated(&mut self, _span: Span, _result: &Value, _interpreter: &Interpreter) {}
+
+    // Bracket each call of a user function or builtin (used by --profile)
+    fn enter_function(&mut self, _name: &str, _builtin: bool) {}
+
+    fn exit_function(&mut self, _name: &str, _builtin: bool) {}
+}
+
+// Caps on a script's resources; `None` means unlimited
+#[derive(Clone, Copy, Default)]
+pub struct Limits {
+    pub max_depth: Option<usize>,
+    pub max_steps: Option<u64>,
+    pub timeout: Option<Duration>,
+}
+
+// Resources used so far, shared by function calls and imported modules
+#[derive(Default)]
+struct Budget {
+    limits: Limits,
+    steps: u64,
+    started: Option<Instant>,
+}
+
+impl Budget {
+    // Counts one statement or expression evaluated at `span`
+    fn step(&mut self, span: Span) {
+        self.steps += 1;
+        if let Some(max_steps) = self.limits.max_steps {
+            if self.steps > max_steps {
+                limit_exceeded(&format!("more than {} evaluation steps", max_steps), span);
+            }
+        }
+        if let Some(timeout) = self.limits.timeout {
+            if self.started.get_or_insert_with(Instant::now).elapsed() > timeout {
+                limit_exceeded(&format!("ran longer than {:?}", timeout), span);
+            }
+        }
+    }
+
+    // Waits for `duration`, or only until the timeout if that comes first
+    fn sleep(&mut self, duration: Duration, span: Span) {
+        if let Some(timeout) = self.limits.timeout {
+            let remaining = timeout.saturating_sub(self.started.get_or_insert_with(Instant::now).elapsed());
+            if duration > remaining {
+                std::thread::sleep(remaining);
+                limit_exceeded(&format!("ran longer than {:?}", timeout), span);
+            }
+        }
+        std::thread::sleep(duration);
+    }
+}
+
+// Source of an imported module file. There is no filesystem in the browser,
+// so the wasm build only has bundled modules.
+#[cfg(not(feature = "wasm"))]
+fn read_module_file(name: &str, search_paths: &[PathBuf], allow_net: bool, span: Span) -> String {
+    if crate::remote::is_url(name) {
+        return crate::remote::fetch(name, allow_net).unwrap_or_else(|error| panic!("{} on line {}, column {}.", error, span.start.line, span.start.column));
+    }
+    std::fs::read_to_string(resolve_import(name, search_paths))
+        .unwrap_or_else(|error| panic!("Failed to read module '{}': {} on line {}, column {}.", name, error, span.start.line, span.start.column))
+}
+
+#[cfg(feature = "wasm")]
+fn read_module_file(name: &str, _search_paths: &[PathBuf], _allow_net: bool, span: Span) -> String {
+    panic!("Cannot import '{}': imports are not available in the browser on line {}, column {}.", name, span.start.line, span.start.column)
+}
+
+// File for an import path: relative to the current directory if it exists
+// there, otherwise the first match in the search paths. A directory in a
+// search path named after the module is a package, imported through its
+// `lib.qpr`.
+pub fn resolve_import(name: &str, search_paths: &[PathBuf]) -> PathBuf {
+    let path = PathBuf::from(name);
+    if path.is_absolute() || path.exists() {
+        return path;
+    }
+    let package = path.with_extension("").join(PACKAGE_ENTRY).with_extension(FILE_EXTENSION);
+    search_paths
+        .iter()
+        .flat_map(|dir| [dir.join(name), dir.join(&package)])
+        .find(|candidate| candidate.is_file())
+        .unwrap_or(path)
+}
+
+// Import path for `name` imported by a module in `dir`: the module next to
+// it if there is one (according to `exists`), otherwise `name` itself, which
+// is looked up in the current directory and the search paths. Modules
+// downloaded from a URL import relative URLs.
+pub fn locate_import(name: &str, dir: Option<&str>, exists: impl Fn(&str) -> bool) -> String {
+    let Some(dir) = dir else {
+        return name.to_string();
+    };
+    if crate::stdlib::source(name).is_some() || crate::remote::is_url(name) || Path::new(name).is_absolute() {
+        return name.to_string();
+    }
+    if crate::remote::is_url(dir) {
+        return format!("{}/{}", dir, name);
+    }
+    let local = Path::new(dir).join(name).to_string_lossy().into_owned();
+    if exists(&local) {
+        local
+    } else {
+        name.to_string()
+    }
+}
+
+// Directory that imports in the module at `path` are relative to
+pub fn import_dir(path: &str) -> Option<String> {
+    if crate::remote::is_url(path) {
+        return path.rfind('/').map(|end| path[..end].to_string());
+    }
+    Path::new(path).parent().map(|dir| dir.to_string_lossy().into_owned())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn keyring_secret(name: &str) -> Option<String> {
+    keyring::Entry::new(KEYRING_SERVICE, name).ok()?.get_password().ok()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn keyring_secret(_name: &str) -> Option<String> {
+    None
+}
+
+// Variable holding the current item of `pmap`; the space keeps scripts
+// from naming it
+const PMAP_ITEM: &str = "pmap item";
+
+// `function(item)` for each item of `pmap`, parsed once so builtins such as
+// `ctof` work as well as user and native functions
+fn pmap_call(function: &str, span: Span) -> Node {
+    // Starting on the `pmap`'s line makes parse errors, such as a builtin
+    // that takes two arguments, point at it
+    let source = format!("{}{}(item)", "\n".repeat(span.start.line.saturating_sub(1)), function);
+    let mut call = crate::parser::Parser::new(crate::lexer::Lexer::new(source)).parse_expression();
+    call.span = span;
+    for arg in call.node.children_mut() {
+        *arg = Node::new(ASTNode::Identifier(PMAP_ITEM.to_string()), span);
+    }
+    call
+}
+
+// Dew point with the Magnus formula (17.27, 237.7 °C), exact in exact mode
+// apart from the logarithm
+fn magnus_dewpoint(temp: Value, humidity: Value, numeric: Numeric) -> Value {
+    let a = Value::ratio(1727, 100);
+    let b = Value::ratio(2377, 10);
+    let alpha = ((a.clone() * temp.clone()) / (b.clone() + temp)) + Value::float((humidity / Value::int(100)).to_f64().ln(), numeric);
+    (b * alpha.clone()) / (a - alpha)
+}
+
+// Physical quantities taken by the weather builtins. Arguments are checked
+// before use, so impossible inputs stop the script with an error naming the
+// builtin instead of giving NaN.
+#[derive(Clone, Copy)]
+enum Quantity {
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+    Humidity,
+    Speed,
+    Pressure,
+    Duration,
+    Hours,
+    // Precipitation depths and rates
+    Millimetres,
+    Inches,
+    MillimetresPerHour,
+    InchesPerHour,
+    // Any number, e.g. an altitude or a direction
+    Number,
+}
+
+impl Quantity {
+    fn name(self) -> &'static str {
+        match self {
+            Quantity::Celsius | Quantity::Fahrenheit | Quantity::Kelvin => "temperature",
+            Quantity::Humidity => "relative humidity",
+            Quantity::Speed => "wind speed",
+            Quantity::Pressure => "pressure",
+            Quantity::Duration | Quantity::Hours => "duration",
+            Quantity::Millimetres | Quantity::Inches => "precipitation depth",
+            Quantity::MillimetresPerHour | Quantity::InchesPerHour => "precipitation rate",
+            Quantity::Number => "argument",
+        }
+    }
+
+    // Lowest allowed value and whether it is allowed itself
+    fn minimum(self) -> Option<(f64, bool)> {
+        match self {
+            Quantity::Celsius => Some((-273.15, false)),
+            Quantity::Fahrenheit => Some((-459.67, false)),
+            Quantity::Kelvin => Some((0.0, false)),
+            Quantity::Humidity | Quantity::Speed | Quantity::Pressure | Quantity::Duration | Quantity::Hours => Some((0.0, true)),
+            Quantity::Millimetres | Quantity::Inches | Quantity::MillimetresPerHour | Quantity::InchesPerHour | Quantity::Number => None,
+        }
+    }
+
+    fn requirement(self) -> &'static str {
+        match self {
+            Quantity::Celsius => "above absolute zero (-273.15 °C)",
+            Quantity::Fahrenheit => "above absolute zero (-459.67 °F)",
+            Quantity::Kelvin => "above absolute zero (0 K)",
+            Quantity::Humidity => "between 0 and 100 percent",
+            Quantity::Speed | Quantity::Pressure | Quantity::Duration | Quantity::Hours => "zero or more",
+            Quantity::Millimetres | Quantity::Inches | Quantity::MillimetresPerHour | Quantity::InchesPerHour | Quantity::Number => "a number",
+        }
+    }
+
+    // Unit the builtins take this quantity in, which arguments with a unit
+    // are converted to
+    fn unit(self) -> Option<&'static Unit> {
+        let name = match self {
+            Quantity::Celsius => "degC",
+            Quantity::Fahrenheit => "degF",
+            Quantity::Kelvin => "K",
+            Quantity::Speed => "m/s",
+            Quantity::Pressure => "hPa",
+            Quantity::Duration => "s",
+            Quantity::Hours => "h",
+            Quantity::Millimetres => "mm",
+            Quantity::Inches => "in",
+            Quantity::MillimetresPerHour => "mm/h",
+            Quantity::InchesPerHour => "in/h",
+            Quantity::Humidity | Quantity::Number => return None,
+        };
+        units::find(name)
+    }
+
+    fn allows(self, value: f64) -> bool {
+        let above = match self.minimum() {
+            None => return true,
+            Some((minimum, true)) => value >= minimum,
+            Some((minimum, false)) => value > minimum,
+        };
+        above && !(matches!(self, Quantity::Humidity) && value > 100.0)
+    }
+}
+
+fn binary_op(op: &Token, left: Value, right: Value, numeric: Numeric) -> Value {
+    match op {
+        Token::Plus => left + right,
+        Token::Minus => left - right,
+        Token::Star => left * right,
+        Token::StarStar => left.pow(&right, numeric),
+        Token::Slash => left / right,
+        Token::Modulo => left.modulo(&right),
+        Token::GreaterThan => Value::bool(left > right),
+        Token::LessThan => Value::bool(left < right),
+        Token::GreaterEqual => Value::bool(left >= right),
+        Token::LessEqual => Value::bool(left <= right),
+        Token::EqualEqual => Value::bool(left == right),
+        Token::NotEqual => Value::bool(left != right),
+        _ => panic!("Unexpected operator: {:?}", op),
+    }
+}
+
+// An operator applied to two numbers, either of which may have a unit
+fn arithmetic(op: &Token, left: Value, right: Value, numeric: Numeric, span: Span) -> Value {
+    match (&left, &right) {
+        (Value::Quantity(..), _) | (_, Value::Quantity(..)) => quantity_op(op, left, right, numeric, span),
+        _ => binary_op(op, left, right, numeric),
+    }
+}
+
+fn parse_json(text: &str, span: Span) -> Value {
+    match serde_json::from_str(text) {
+        Ok(json) => Value::from_json(json),
+        Err(error) => panic!("Invalid JSON: {} on line {}, column {}.", error, span.start.line, span.start.column),
+    }
+}
+
+fn compound(unit: &str) -> Compound {
+    Compound::parse(unit).expect("Quantity with an unknown unit")
+}
+
+// A quantity in a unit with an offset, like °C, in kelvin, which products
+// and quotients need: 10 °C is not twice 5 °C
+fn absolute(magnitude: Value, unit: Compound) -> (Value, Compound) {
+    match unit.unit() {
+        Some(single) if single.has_offset() => {
+            let kelvin = Compound::parse("K").expect("Kelvin is a unit");
+            (unit.convert(magnitude, &kelvin), kelvin)
+        }
+        _ => (magnitude, unit),
+    }
+}
+
+// Arithmetic and comparisons with a number that has a unit. Adding,
+// subtracting and comparing need quantities of one dimension, and a
+// quantity in another unit is converted to the left operand's unit first.
+// What is added is a difference, so `25C + 9F` is 30 °C while `25C - 77F`
+// is 0 °C. Multiplying and dividing combine the units, so `100m / 10s` is
+// 10 m/s, and dividing two quantities of one dimension gives their plain
+// ratio. A plain number counts as being in the unit of the other operand.
+fn quantity_op(op: &Token, left: Value, right: Value, numeric: Numeric, span: Span) -> Value {
+    let comparison = matches!(op, Token::GreaterThan | Token::LessThan | Token::GreaterEqual | Token::LessEqual | Token::EqualEqual | Token::NotEqual);
+    let describe = |value: &Value| match value {
+        Value::Quantity(_, unit) => format!("{} ({})", value, compound(unit).dimension().name()),
+        other => other.to_string(),
+    };
+    let fail = |left: &Value, right: &Value| -> ! {
+        panic!("Cannot apply '{}' to {} and {} on line {}, column {}.", operator(op).trim(), describe(left), describe(right), span.start.line, span.start.column)
+    };
+    // The magnitude with the unit, or a plain number when the units cancel
+    let quantity = |magnitude: Value, unit: Compound| if unit.is_empty() { magnitude } else { Value::Quantity(Box::new(magnitude), unit.name()) };
+    match (left, right) {
+        (Value::Quantity(magnitude, unit), Value::Quantity(other, other_unit)) => {
+            let (to, from) = (compound(&unit), compound(&other_unit));
+            let same = to.dimension() == from.dimension();
+            match op {
+                Token::Slash if same && to.unit().is_some() && from.unit().is_some() => *magnitude / from.convert(*other, &to),
+                Token::Star | Token::Slash => {
+                    let ((magnitude, to), (other, from)) = (absolute(*magnitude, to), absolute(*other, from));
+                    let from = if matches!(op, Token::Slash) { from.powi(-1) } else { from };
+                    let (factor, product) = to.times(&from);
+                    quantity(binary_op(op, magnitude, other, numeric) * factor, product)
+                }
+                Token::StarStar => fail(&Value::Quantity(magnitude, unit), &Value::Quantity(other, other_unit)),
+                _ if !same => fail(&Value::Quantity(magnitude, unit), &Value::Quantity(other, other_unit)),
+                _ => {
+                    let other = match op {
+                        Token::Plus => from.convert(*other, &to) - from.convert(Value::int(0), &to),
+                        _ => from.convert(*other, &to),
+                    };
+                    match op {
+                        _ if comparison => binary_op(op, *magnitude, other, numeric),
+                        _ => Value::Quantity(Box::new(binary_op(op, *magnitude, other, numeric)), unit),
+                    }
+                }
+            }
+        }
+        (Value::Quantity(magnitude, unit), other) if other.is_number() => match op {
+            // Whole powers raise the unit too, so `(2m) ** 2` is 4 m^2
+            Token::StarStar => {
+                let exponent = other.to_f64();
+                if exponent.fract() != 0.0 || exponent.abs() > i32::MAX as f64 {
+                    fail(&Value::Quantity(magnitude, unit), &other);
+                }
+                let (magnitude, unit) = absolute(*magnitude, compound(&unit));
+                quantity(magnitude.pow(&other, numeric), unit.powi(exponent as i32))
+            }
+            _ if comparison => binary_op(op, *magnitude, other, numeric),
+            _ => Value::Quantity(Box::new(binary_op(op, *magnitude, other, numeric)), unit),
+        },
+        (other, Value::Quantity(magnitude, unit)) if other.is_number() => match op {
+            Token::Slash => {
+                let (magnitude, unit) = absolute(*magnitude, compound(&unit));
+                quantity(other / magnitude, unit.powi(-1))
+            }
+            Token::StarStar | Token::Modulo => fail(&other, &Value::Quantity(magnitude, unit)),
+            _ if comparison => binary_op(op, other, *magnitude, numeric),
+            _ => Value::Quantity(Box::new(binary_op(op, other, *magnitude, numeric)), unit),
+        },
+        // `==` and `!=` with values that aren't numbers
+        (left, right) => binary_op(op, left, right, numeric),
+    }
+}
+
+fn limit_exceeded(reason: &str, span: Span) -> ! {
+    panic!("Execution limit exceeded: {} on line {}, column {}.", reason, span.start.line, span.start.column)
+}
+
+// Host function registered by an embedder; errors are reported as script errors
+pub type NativeFunction = Arc<dyn Fn(&[Value]) -> Result<Value, String> + Send + Sync>;
+
+// Receives each line printed by the script, without the newline
+pub type OutputSink = Arc<dyn Fn(&str) + Send + Sync>;
+
+// Variables of one function call, falling back to those of the scope it
+// was called from. A call only binds its parameters, so it costs the same
+// however many variables the caller has.
+#[derive(Clone, Default)]
+struct Scope {
+    variables: HashMap<String, Value>,
+    parent: Option<Arc<Scope>>,
+}
+
+impl Scope {
+    fn get(&self, name: &str) -> Option<&Value> {
+        let mut scope = self;
+        loop {
+            if let Some(value) = scope.variables.get(name) {
+                return Some(value);
+            }
+            scope = scope.parent.as_deref()?;
+        }
+    }
+
+    // Every visible variable, inner scopes shadowing outer ones
+    fn flatten(&self) -> HashMap<String, Value> {
+        let mut variables = self.parent.as_ref().map(|parent| parent.flatten()).unwrap_or_default();
+        variables.extend(self.variables.iter().map(|(name, value)| (name.clone(), value.clone())));
+        variables
+    }
+}
+
+// What an imported module leaves behind for the scripts importing it
+struct Module {
+    functions: Arc<HashMap<String, Arc<Node>>>,
+    // Top-level variables, such as station metadata or calibration constants
+    variables: HashMap<String, Value>,
+    homes: Arc<HashMap<String, Arc<Module>>>,
+    // Names marked with `export`, or `None` if the module exports everything
+    exports: Option<HashSet<String>>,
+}
 
 pub struct Interpreter {
-    variables: HashMap<String, Complex<BigRational>>,
-    functions: HashMap<String, ASTNode>,
+    scope: Scope,
+    // Function definitions, shared with the interpreters of calls and only
+    // copied when a call defines a function of its own
+    functions: Arc<HashMap<String, Arc<Node>>>,
+    // Module of each function imported by name, whose calls run among that
+    // module's functions and variables rather than this script's
+    homes: Arc<HashMap<String, Arc<Module>>>,
+    natives: HashMap<String, NativeFunction>,
+    output: OutputSink,
+    hooks: Vec<Box<dyn ExecutionHook>>,
+    budget: Budget,
+    // Modules imported so far, shared like the budget; `None` marks one
+    // still running its top level
+    imported: HashMap<PathBuf, Option<Arc<Module>>>,
+    // Extra command-line arguments, returned by `args()`
+    args: Arc<Vec<Value>>,
+    // Module sources bundled into the executable, by import path
+    modules: Arc<HashMap<String, String>>,
+    search_paths: Arc<Vec<PathBuf>>,
+    // Secrets from the project configuration, by name
+    secrets: Arc<HashMap<String, String>>,
+    // Whether `fetch` and `forecast` may use the network (`--allow-net`)
+    allow_net: bool,
+    // Physical constants, shared like the budget so `set_constant` in a
+    // function or module applies to the rest of the script
+    constants: Constants,
+    // The one random number generator of `rand`, `randint` and `randn`,
+    // shared with functions, modules and `pmap` threads so `seed` makes
+    // the whole script repeatable
+    rng: Arc<Mutex<StdRng>>,
+    numeric: Numeric,
+    // Significant digits of printed numbers
+    precision: usize,
+    // Number of user function calls currently active
+    call_depth: usize,
+    // Names this module has exported so far
+    exports: Option<HashSet<String>>,
+    // Directory (or URL) of the module being run, which its imports are
+    // relative to; `None` for the main script
+    dir: Option<String>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Interpreter {
     pub fn new() -> Self {
         Self {
-            variables: HashMap::new(),
-            functions: HashMap::new(),
+            scope: Scope::default(),
+            functions: Arc::new(HashMap::new()),
+            homes: Arc::new(HashMap::new()),
+            natives: HashMap::new(),
+            output: Arc::new(|line| println!("{}", line)),
+            hooks: Vec::new(),
+            budget: Budget::default(),
+            imported: HashMap::new(),
+            args: Arc::new(Vec::new()),
+            modules: Arc::new(HashMap::new()),
+            search_paths: Arc::new(Vec::new()),
+            secrets: Arc::new(HashMap::new()),
+            allow_net: false,
+            constants: Constants::default(),
+            rng: Arc::new(Mutex::new(StdRng::from_entropy())),
+            numeric: Numeric::default(),
+            precision: DEFAULT_PRECISION,
+            call_depth: 0,
+            exports: None,
+            dir: None,
         }
     }
 
-    pub fn execute(interpreter: Arc<Mutex<Self>>, node: ASTNode) {
-        match node {
-            ASTNode::Assignment(name, expr) => {
-                let value = {
-                    let mut guard = interpreter.lock().unwrap();
-                    guard.evaluate(*expr)
+    // Interpreter whose `print` output goes to `writer` instead of stdout
+    pub fn with_output(writer: impl Write + Send + 'static) -> Self {
+        let writer = Mutex::new(writer);
+        let mut interpreter = Self::new();
+        interpreter.set_output(move |line| {
+            let mut writer = writer.lock().unwrap_or_else(PoisonError::into_inner);
+            writeln!(writer, "{}", line).expect("Failed to write script output");
+        });
+        interpreter
+    }
+
+    // Sends each printed line to `callback` instead of stdout
+    pub fn set_output(&mut self, callback: impl Fn(&str) + Send + Sync + 'static) {
+        self.output = Arc::new(callback);
+    }
+
+    pub fn set_args(&mut self, args: Vec<Value>) {
+        self.args = Arc::new(args);
+    }
+
+    // Serves these sources to `import` instead of reading files
+    pub fn set_modules(&mut self, modules: HashMap<String, String>) {
+        self.modules = Arc::new(modules);
+    }
+
+    // Bundled modules come first, then the standard library, then files
+    fn read_module(&self, name: &str, span: Span) -> String {
+        match (self.modules.get(name), crate::stdlib::source(name)) {
+            (Some(source), _) => source.clone(),
+            (None, Some(source)) => source.to_string(),
+            (None, None) => read_module_file(name, &self.search_paths, self.allow_net, span),
+        }
+    }
+
+    // Registry key of an import: the import path of a bundled or standard
+    // library module, the URL, or the canonical path of the file, so
+    // different spellings of one file share an entry
+    fn module_key(&self, name: &str) -> PathBuf {
+        if self.modules.contains_key(name) || crate::stdlib::source(name).is_some() || crate::remote::is_url(name) {
+            return PathBuf::from(name);
+        }
+        let path = resolve_import(name, &self.search_paths);
+        std::fs::canonicalize(&path).unwrap_or(path)
+    }
+
+    // Runs a module the first time it is imported; later imports, direct or
+    // through other modules, only merge the functions and variables it
+    // defined: all of them, or just those in `names`
+    fn import(&mut self, name: &str, names: Option<&[String]>, span: Span) {
+        let located = locate_import(name, self.dir.as_deref(), |path| self.modules.contains_key(path) || Path::new(path).exists());
+        let name = located.as_str();
+        let key = self.module_key(name);
+        let module = match self.imported.get(&key) {
+            Some(Some(module)) => module.clone(),
+            Some(None) => panic!("Circular import of '{}' on line {}, column {}.", name, span.start.line, span.start.column),
+            None => {
+                let source = self.read_module(name, span);
+                let nodes = crate::parser::Parser::new(crate::lexer::Lexer::new(source)).parse();
+                self.imported.insert(key.clone(), None);
+                let mut imported = self.module();
+                imported.dir = match self.modules.contains_key(name) || crate::remote::is_url(name) {
+                    true => import_dir(name),
+                    false if crate::stdlib::source(name).is_some() => None,
+                    false => import_dir(&resolve_import(name, &self.search_paths).to_string_lossy()),
                 };
-                let mut guard = interpreter.lock().unwrap();
-                guard.variables.insert(name, value.into());
-            }
-            ASTNode::Print(expr) => {
-                match *expr {
-                    ASTNode::StringLiteral(value) => {
-                        println!("{}", value);
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    for node in &nodes {
+                        imported.execute(node);
                     }
-                    _ => {
-                        let value = {
-                            let mut guard = interpreter.lock().unwrap();
-                            guard.evaluate(*expr)
-                        };
-                        println!("{}", value.to_f64().unwrap());
+                }));
+                self.finish_call(&mut imported);
+                if let Err(payload) = result {
+                    // A failed module can be imported again, e.g. after fixing it in the REPL
+                    self.imported.remove(&key);
+                    panic::resume_unwind(payload);
+                }
+                let module = Arc::new(Module {
+                    functions: std::mem::take(&mut imported.functions),
+                    variables: std::mem::take(&mut imported.scope.variables),
+                    homes: std::mem::take(&mut imported.homes),
+                    exports: imported.exports.take(),
+                });
+                self.imported.insert(key, Some(module.clone()));
+                module
+            }
+        };
+        let exported = |name: &String| module.exports.as_ref().is_none_or(|exports| exports.contains(name));
+        let merged: Vec<&String> = match names {
+            Some(names) => {
+                for imported in names {
+                    if !exported(imported) || !(module.functions.contains_key(imported) || module.variables.contains_key(imported)) {
+                        panic!("Module '{}' has no exported function or variable '{}' on line {}, column {}.", name, imported, span.start.line, span.start.column);
                     }
                 }
+                names.iter().collect()
             }
-            ASTNode::If(condition, then_branch, else_branch) => {
-                let condition_result = {
-                    let mut guard = interpreter.lock().unwrap();
-                    guard.evaluate(*condition)
+            None => module.functions.keys().chain(module.variables.keys()).filter(|name| exported(name)).collect(),
+        };
+        // When some of the module stays behind, calls to its functions need
+        // to be pointed back at the rest of it
+        let partial = names.is_some() || module.exports.is_some();
+        let functions = Arc::make_mut(&mut self.functions);
+        let homes = Arc::make_mut(&mut self.homes);
+        for imported in merged {
+            if let Some(function) = module.functions.get(imported) {
+                functions.insert(imported.clone(), function.clone());
+                match module.homes.get(imported).cloned().or_else(|| partial.then(|| module.clone())) {
+                    Some(home) => homes.insert(imported.clone(), home),
+                    None => homes.remove(imported),
                 };
-                if condition_result != BigRational::from(BigInt::from(0)).into() {
-                    Interpreter::execute(interpreter, *then_branch);
+            }
+            if let Some(value) = module.variables.get(imported) {
+                self.scope.variables.insert(imported.clone(), value.clone());
+            }
+        }
+    }
+
+    // Directories searched for imports not found in the current directory
+    pub fn set_search_paths(&mut self, search_paths: Vec<PathBuf>) {
+        self.search_paths = Arc::new(search_paths);
+    }
+
+    pub fn set_secrets(&mut self, secrets: HashMap<String, String>) {
+        self.secrets = Arc::new(secrets);
+    }
+
+    // Lets `fetch` and `forecast` download data; scripts are offline unless
+    // this is set
+    pub fn set_allow_net(&mut self, allow: bool) {
+        self.allow_net = allow;
+    }
+
+    // Starting values of the physical constants, e.g. from `weather.toml`
+    pub fn set_constants(&mut self, constants: Constants) {
+        self.constants = constants;
+    }
+
+    // Current value of an overridable constant in the numeric mode
+    fn constant(&self, name: &str) -> Value {
+        Value::number(self.constants.get(name), self.numeric)
+    }
+
+    // Evaluates an argument of the weather builtin `builtin`, which must be a
+    // number in the range of `quantity`
+    fn input(&mut self, arg: &Node, quantity: Quantity, builtin: &Node) -> Value {
+        let value = self.evaluate(arg);
+        self.check_input(value, quantity, builtin)
+    }
+
+    fn check_input(&self, value: Value, quantity: Quantity, builtin: &Node) -> Value {
+        let (name, span) = (builtin.node.keyword().unwrap_or("builtin"), builtin.span);
+        let value = match value {
+            Value::Quantity(magnitude, unit) => {
+                let unit = compound(&unit);
+                match quantity.unit().map(Compound::single) {
+                    Some(expected) if expected.dimension() == unit.dimension() => unit.convert(*magnitude, &expected),
+                    Some(expected) => panic!(
+                        "The {} given to {} must be a {}, found {} {} on line {}, column {}.",
+                        quantity.name(), name, expected.dimension().name(), magnitude, unit.name(), span.start.line, span.start.column
+                    ),
+                    None => panic!("The {} given to {} can't have a unit, found {} {} on line {}, column {}.", quantity.name(), name, magnitude, unit.name(), span.start.line, span.start.column),
+                }
+            }
+            other => other,
+        };
+        if !value.is_number() {
+            panic!("The {} given to {} must be a number, found a {} on line {}, column {}.", quantity.name(), name, value.type_name(), span.start.line, span.start.column);
+        }
+        if !quantity.allows(value.to_f64()) {
+            panic!("The {} given to {} must be {}, found {} on line {}, column {}.", quantity.name(), name, quantity.requirement(), value, span.start.line, span.start.column);
+        }
+        value
+    }
+
+    // Response to a GET request, parsed as JSON when it is JSON
+    fn download(&self, url: &str, builtin: &str, span: Span) -> Value {
+        if !self.allow_net {
+            panic!("{} needs network access, which scripts only have when run with --allow-net on line {}, column {}.", builtin, span.start.line, span.start.column);
+        }
+        let text = remote::get(url).unwrap_or_else(|error| panic!("{} on line {}, column {}.", error, span.start.line, span.start.column));
+        serde_json::from_str(&text).map_or(Value::Str(text), Value::from_json)
+    }
+
+    // Uniform random number in [0, 1)
+    fn random(&self) -> f64 {
+        self.rng.lock().unwrap_or_else(PoisonError::into_inner).gen()
+    }
+
+    // File path argument of a builtin, which must be a string
+    fn path(&mut self, path: &Node, builtin: &str, span: Span) -> String {
+        match self.evaluate(path) {
+            Value::Str(path) => path,
+            other => panic!("{} expects a file path, found a {} on line {}, column {}.", builtin, other.type_name(), span.start.line, span.start.column),
+        }
+    }
+
+    // Evaluates the argument of `builtin` that must be a list of numbers,
+    // described as `what` in errors
+    fn numbers(&mut self, list: &Node, builtin: &str, what: &str, span: Span) -> Vec<Value> {
+        let values = match self.evaluate(list) {
+            Value::List(values) => values,
+            other => panic!("{} expects a list of {}, found a {} on line {}, column {}.", builtin, what, other.type_name(), span.start.line, span.start.column),
+        };
+        if let Some(value) = values.iter().find(|value| !value.is_number()) {
+            panic!("{} expects a list of {}, found a {} in it on line {}, column {}.", builtin, what, value.type_name(), span.start.line, span.start.column);
+        }
+        values
+    }
+
+    // Sum over daily mean temperatures of how far each is below the base
+    // (heating degree days) or above it (cooling degree days)
+    fn degree_days(&mut self, temps: &Node, base: &Node, heating: bool, builtin: &Node) -> Value {
+        let temps = self.numbers(temps, if heating { "hdd" } else { "cdd" }, "temperatures", builtin.span);
+        let base = self.input(base, Quantity::Celsius, builtin);
+        let mut total = Value::int(0);
+        for temp in temps {
+            let temp = self.check_input(temp, Quantity::Celsius, builtin);
+            let difference = if heating { base.clone() - temp } else { temp - base.clone() };
+            if difference > Value::int(0) {
+                total = total + difference;
+            }
+        }
+        total
+    }
+
+    // Secrets come from the environment (the name in upper case), then the
+    // project configuration, then the system keyring
+    fn lookup_secret(&self, name: &str) -> Option<String> {
+        std::env::var(name.to_uppercase())
+            .ok()
+            .or_else(|| self.secrets.get(name).cloned())
+            .or_else(|| keyring_secret(name))
+    }
+
+    pub fn set_numeric(&mut self, numeric: Numeric) {
+        self.numeric = numeric;
+    }
+
+    pub fn set_precision(&mut self, precision: usize) {
+        self.precision = precision;
+    }
+
+    pub fn precision(&self) -> usize {
+        self.precision
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.budget.limits = limits;
+    }
+
+    // Interpreter for a call to a user function; it borrows this
+    // interpreter's scope, hooks and budget until `call_function` hands
+    // them back
+    fn call(&mut self, variables: HashMap<String, Value>, span: Span) -> Interpreter {
+        let call_depth = self.call_depth + 1;
+        if let Some(max_depth) = self.budget.limits.max_depth {
+            if call_depth > max_depth {
+                limit_exceeded(&format!("recursion deeper than {} calls", max_depth), span);
+            }
+        }
+        Interpreter {
+            scope: Scope { variables, parent: Some(Arc::new(std::mem::take(&mut self.scope))) },
+            functions: self.functions.clone(),
+            homes: self.homes.clone(),
+            natives: self.natives.clone(),
+            output: self.output.clone(),
+            hooks: std::mem::take(&mut self.hooks),
+            budget: std::mem::take(&mut self.budget),
+            imported: std::mem::take(&mut self.imported),
+            args: self.args.clone(),
+            modules: self.modules.clone(),
+            search_paths: self.search_paths.clone(),
+            secrets: self.secrets.clone(),
+            allow_net: self.allow_net,
+            constants: std::mem::take(&mut self.constants),
+            rng: self.rng.clone(),
+            numeric: self.numeric,
+            precision: self.precision,
+            call_depth,
+            exports: None,
+            dir: self.dir.clone(),
+        }
+    }
+
+    // Interpreter for an imported module; hooks only see the main script,
+    // but the module's work counts against this interpreter's budget
+    fn module(&mut self) -> Interpreter {
+        Interpreter {
+            natives: self.natives.clone(),
+            output: self.output.clone(),
+            budget: std::mem::take(&mut self.budget),
+            imported: std::mem::take(&mut self.imported),
+            args: self.args.clone(),
+            modules: self.modules.clone(),
+            search_paths: self.search_paths.clone(),
+            secrets: self.secrets.clone(),
+            allow_net: self.allow_net,
+            constants: std::mem::take(&mut self.constants),
+            rng: self.rng.clone(),
+            numeric: self.numeric,
+            precision: self.precision,
+            ..Interpreter::new()
+        }
+    }
+
+    // Interpreter for one thread of `pmap`. Like a module it starts without
+    // variables or hooks; it gets a copy of the budget, so each thread may
+    // use what was left when `pmap` started.
+    fn worker(&self) -> Interpreter {
+        Interpreter {
+            functions: self.functions.clone(),
+            homes: self.homes.clone(),
+            natives: self.natives.clone(),
+            output: self.output.clone(),
+            budget: Budget { limits: self.budget.limits, steps: self.budget.steps, started: self.budget.started },
+            args: self.args.clone(),
+            modules: self.modules.clone(),
+            search_paths: self.search_paths.clone(),
+            secrets: self.secrets.clone(),
+            allow_net: self.allow_net,
+            constants: self.constants.clone(),
+            rng: self.rng.clone(),
+            numeric: self.numeric,
+            precision: self.precision,
+            call_depth: self.call_depth,
+            exports: None,
+            dir: self.dir.clone(),
+            ..Interpreter::new()
+        }
+    }
+
+    // Runs a user function with its parameters bound in a new scope on top
+    // of the caller's; `run` executes or evaluates the body
+    fn call_function(&mut self, name: &str, args: &[Node], span: Span, run: impl FnOnce(&mut Interpreter, &Node) -> Value) -> Value {
+        let function = self.functions.get(name)
+            .unwrap_or_else(|| panic!("Undefined function '{}' on line {}, column {}.", name, span.start.line, span.start.column))
+            .clone();
+        let ASTNode::Function(_, params, body) = &function.node else {
+            panic!("Expected function, got {:?}", function);
+        };
+        // A function imported by name sees its module's variables under its
+        // parameters
+        let home = self.homes.get(name).cloned();
+        let mut variables = home.as_ref().map(|home| home.variables.clone()).unwrap_or_default();
+        for (param, arg) in params.iter().zip(args.iter()) {
+            let value = self.evaluate(arg);
+            variables.insert(param.clone(), value);
+        }
+        // Hooks follow execution into the call and are handed back afterwards
+        let mut callee = self.call(variables, span);
+        if let Some(home) = home {
+            callee.functions = home.functions.clone();
+            callee.homes = home.homes.clone();
+        }
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            callee.notify_hooks(|hook, _| hook.enter_function(name, false));
+            let value = run(&mut callee, body);
+            callee.notify_hooks(|hook, _| hook.exit_function(name, false));
+            value
+        }));
+        // The caller's scope comes back even if the call failed, so the REPL
+        // keeps its variables after an error
+        self.finish_call(&mut callee);
+        let caller = callee.scope.parent.take().expect("Call scope has no parent");
+        drop(callee);
+        self.scope = Arc::try_unwrap(caller).unwrap_or_else(|caller| (*caller).clone());
+        result.unwrap_or_else(|payload| panic::resume_unwind(payload))
+    }
+
+    fn finish_call(&mut self, callee: &mut Interpreter) {
+        self.hooks.append(&mut callee.hooks);
+        self.budget = std::mem::take(&mut callee.budget);
+        self.imported = std::mem::take(&mut callee.imported);
+        self.constants = std::mem::take(&mut callee.constants);
+    }
+
+    // Makes a Rust function callable from scripts as `name(args)`. Script
+    // functions with the same name take precedence.
+    pub fn register_native<F, E>(&mut self, name: &str, function: F)
+    where
+        F: Fn(&[Value]) -> Result<Value, E> + Send + Sync + 'static,
+        E: std::fmt::Display,
+    {
+        let native: NativeFunction = Arc::new(move |args| function(args).map_err(|error| error.to_string()));
+        self.natives.insert(name.to_string(), native);
+    }
+
+    // Calls the native function `name`, or returns `None` if there is none
+    fn call_native(&mut self, name: &str, args: &[Node], span: Span) -> Option<Value> {
+        let native = self.natives.get(name)?.clone();
+        let args: Vec<Value> = args.iter().map(|arg| self.evaluate(arg)).collect();
+        self.hooks.iter_mut().for_each(|hook| hook.enter_function(name, true));
+        let result = native(&args);
+        self.hooks.iter_mut().for_each(|hook| hook.exit_function(name, true));
+        Some(result.unwrap_or_else(|message| panic!("Native function '{}' failed: {} on line {}, column {}.", name, message, span.start.line, span.start.column)))
+    }
+
+    pub fn add_hook(&mut self, hook: Box<dyn ExecutionHook>) {
+        self.hooks.push(hook);
+    }
+
+    // Every variable visible to the running code
+    pub fn variables(&self) -> HashMap<String, Value> {
+        self.scope.flatten()
+    }
+
+    pub fn variable(&self, name: &str) -> Option<&Value> {
+        self.scope.get(name)
+    }
+
+    pub fn functions(&self) -> &HashMap<String, Arc<Node>> {
+        &self.functions
+    }
+
+    // Variables and functions, to be saved and later given to `restore`.
+    // Variables holding secrets are left out, and functions imported by name
+    // lose the rest of their module.
+    pub fn snapshot(&self) -> Snapshot {
+        let variables = self.scope.flatten().into_iter().filter(|(_, value)| !matches!(value, Value::Secret(_))).collect();
+        let mut functions: Vec<_> = self.functions.iter().collect();
+        functions.sort_by_key(|(name, _)| name.as_str());
+        let functions = functions.into_iter().map(|(_, function)| function.as_ref().clone()).collect();
+        Snapshot { variables, functions }
+    }
+
+    // Replaces all variables and functions with those of the snapshot
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.scope = Scope { variables: snapshot.variables.into_iter().collect(), parent: None };
+        let functions = snapshot.functions.into_iter().filter_map(|function| match &function.node {
+            ASTNode::Function(name, ..) => Some((name.clone(), Arc::new(function.clone()))),
+            _ => None,
+        });
+        self.functions = Arc::new(functions.collect());
+        self.homes = Arc::new(HashMap::new());
+    }
+
+    pub fn call_depth(&self) -> usize {
+        self.call_depth
+    }
+
+    // Calls `notify` on every hook, handing them the interpreter's current state
+    fn notify_hooks(&mut self, mut notify: impl FnMut(&mut dyn ExecutionHook, &Interpreter)) {
+        if self.hooks.is_empty() {
+            return;
+        }
+        let mut hooks = std::mem::take(&mut self.hooks);
+        for hook in hooks.iter_mut() {
+            notify(hook.as_mut(), self);
+        }
+        self.hooks = hooks;
+    }
+
+    pub fn execute(&mut self, node: &Node) {
+        let span = node.span;
+        let is_statement = !matches!(node.node, ASTNode::Block(_));
+        self.budget.step(span);
+        if is_statement {
+            self.notify_hooks(|hook, interpreter| hook.before_statement(node, interpreter));
+        }
+        stack::grow(|| self.execute_node(node));
+        if is_statement {
+            self.notify_hooks(|hook, interpreter| hook.after_statement(span, interpreter));
+        }
+    }
+
+    fn execute_node(&mut self, node: &Node) {
+        let span = node.span;
+        match &node.node {
+            ASTNode::Assignment(name, expr) => {
+                let value = self.evaluate(expr);
+                self.scope.variables.insert(name.clone(), value);
+            }
+            ASTNode::Print(expr) => {
+                let text = match &expr.node {
+                    ASTNode::StringLiteral(value) => value.clone(),
+                    _ => self.evaluate(expr).rounded(self.precision),
+                };
+                (self.output)(&text);
+            }
+            ASTNode::Assert(condition, message) => {
+                if !self.evaluate(condition).is_truthy() {
+                    let message = match &message.node {
+                        ASTNode::StringLiteral(value) => value.clone(),
+                        _ => self.evaluate(message).to_string(),
+                    };
+                    panic!("Assertion failed: {} on line {}, column {}.", message, span.start.line, span.start.column);
+                }
+            }
+            ASTNode::AssertApprox(actual, expected, tolerance) => {
+                let actual = self.evaluate(actual);
+                let expected = self.evaluate(expected);
+                let tolerance = self.evaluate(tolerance);
+                // NaN is never within tolerance
+                let within = (actual.to_f64() - expected.to_f64()).abs() <= tolerance.to_f64();
+                if !within {
+                    panic!("Assertion failed: {} is not within {} of {} on line {}, column {}.", actual, tolerance, expected, span.start.line, span.start.column);
+                }
+            }
+            ASTNode::SetConstant(name, value) => {
+                let name = match self.evaluate(name) {
+                    Value::Str(name) => name,
+                    other => panic!("Constant names given to set_constant must be strings, found a {} on line {}, column {}.", other.type_name(), span.start.line, span.start.column),
+                };
+                let value = match self.evaluate(value) {
+                    Value::Number(value) if value.im.is_zero() => value.re,
+                    Value::Float(value) => BigRational::from_float(value)
+                        .unwrap_or_else(|| panic!("The value given to set_constant must be finite, found {} on line {}, column {}.", value, span.start.line, span.start.column)),
+                    other => panic!("The value given to set_constant must be a real number, found a {} on line {}, column {}.", other.type_name(), span.start.line, span.start.column),
+                };
+                if let Err(message) = self.constants.set(&name, value) {
+                    panic!("{} on line {}, column {}.", message, span.start.line, span.start.column);
+                }
+            }
+            ASTNode::Seed(seed) => {
+                let seed = self.evaluate(seed);
+                let seed = seed.to_usize()
+                    .unwrap_or_else(|| panic!("seed expects a whole number of at least 0, found {} on line {}, column {}.", seed, span.start.line, span.start.column));
+                *self.rng.lock().unwrap_or_else(PoisonError::into_inner) = StdRng::seed_from_u64(seed as u64);
+            }
+            // There is no blocking sleep in the browser
+            #[cfg(not(target_arch = "wasm32"))]
+            ASTNode::Sleep(seconds) => {
+                let seconds = self.input(seconds, Quantity::Duration, node).to_f64();
+                let duration = Duration::try_from_secs_f64(seconds)
+                    .unwrap_or_else(|_| panic!("sleep expects a number of seconds, found {} on line {}, column {}.", seconds, span.start.line, span.start.column));
+                self.budget.sleep(duration, span);
+            }
+            #[cfg(target_arch = "wasm32")]
+            ASTNode::Sleep(_) => panic!("sleep is not available in the browser on line {}, column {}.", span.start.line, span.start.column),
+            ASTNode::WriteJson(path, value) => {
+                let path = self.path(path, "writejson", span);
+                let json = self.evaluate(value).to_json().unwrap_or_else(|error| panic!("{} on line {}, column {}.", error, span.start.line, span.start.column));
+                let text = serde_json::to_string_pretty(&json).expect("JSON values can be written");
+                if let Err(error) = std::fs::write(&path, text + "\n") {
+                    panic!("Failed to write {}: {} on line {}, column {}.", path, error, span.start.line, span.start.column);
+                }
+            }
+            ASTNode::If(condition, then_branch, else_branch) => {
+                let condition_span = condition.span;
+                let condition_result = self.evaluate(condition);
+                self.notify_hooks(|hook, interpreter| hook.condition_evaluated(condition_span, &condition_result, interpreter));
+                if condition_result.is_truthy() {
+                    self.execute(then_branch);
                 } else if let Some(else_branch) = else_branch {
-                    Interpreter::execute(interpreter, *else_branch);
+                    self.execute(else_branch);
                 }
             }
             ASTNode::Block(nodes) => {
                 for node in nodes {
-                    Interpreter::execute(interpreter.clone(), node);
+                    self.execute(node);
                 }
             }
-            ASTNode::Function(name, params, body) => {
-                let mut guard = interpreter.lock().unwrap();
-                let name_clone = name.clone();
-                guard.functions.insert(name_clone, ASTNode::Function(name, params, body));
+            ASTNode::Function(name, ..) => {
+                // Defining a function is the only time its body is copied;
+                // calls share it
+                Arc::make_mut(&mut self.functions).insert(name.clone(), Arc::new(node.clone()));
+                if self.homes.contains_key(name) {
+                    Arc::make_mut(&mut self.homes).remove(name);
+                }
             }
             ASTNode::Call(name, args) => {
-                let mut guard = interpreter.lock().unwrap();
-                let function = guard.functions.get(&name).expect("Undefined function").clone();
-                if let ASTNode::Function(_, params, body) = function {
-                    let mut variables = guard.variables.clone();
-                    for (param, arg) in params.iter().zip(args.iter()) {
-                        let value = guard.evaluate(arg.clone());
-                        variables.insert(param.clone(), value.into());
-                    }
-                    let interpreter = Interpreter {
-                        variables,
-                        functions: guard.functions.clone(),
-                    };
-                    Interpreter::execute(Arc::new(Mutex::new(interpreter)), *body);
-                } else {
-                    panic!("Expected function, got {:?}", function);
+                if !self.functions.contains_key(name) && self.call_native(name, args, span).is_some() {
+                    return;
                 }
+                self.call_function(name, args, span, |callee, body| {
+                    callee.execute(body);
+                    Value::Nil
+                });
             }
-            ASTNode::Import(module_name) => {
-                // Load and parse the module file
-                let module_content = std::fs::read_to_string(module_name).expect("Failed to read module file");
-                let lexer = crate::lexer::Lexer::new(module_content);
-                let mut parser = crate::parser::Parser::new(lexer);
-                let nodes = parser.parse();
-
-                // Execute the parsed nodes
-                let imported_interpreter = Arc::new(Mutex::new(Interpreter::new()));
-                for node in nodes {
-                    Interpreter::execute(imported_interpreter.clone(), node);
-                }
-
-                // Merge imported functions into the current interpreter
-                let imported_guard = imported_interpreter.lock().unwrap();
-                let functions_to_merge: Vec<_> = imported_guard.functions.clone().into_iter().collect();
-                drop(imported_guard); // Release the lock before re-acquiring it
-                let mut guard = interpreter.lock().unwrap();
-                for (name, function) in functions_to_merge {
-                    guard.functions.insert(name, function);
+            ASTNode::Import(module_name, names) => self.import(module_name, names.as_deref(), span),
+            ASTNode::Export(inner) => {
+                self.execute_node(inner);
+                if let ASTNode::Function(name, ..) | ASTNode::Assignment(name, _) = &inner.node {
+                    self.exports.get_or_insert_default().insert(name.clone());
                 }
             }
-            _ => panic!("Unexpected AST node: {:?}", node),
+            other => panic!("Unexpected AST node {:?} on line {}, column {}.", other, span.start.line, span.start.column),
         }
     }
 
-    pub fn evaluate(&mut self, node: ASTNode) -> Complex<BigRational> {
-        match node {
-            ASTNode::Float(value) => BigRational::from_float(value.to_f64().unwrap()).unwrap().into(),
+    pub fn evaluate(&mut self, node: &Node) -> Value {
+        // Builtin function calls are reported to hooks; constants are not
+        let builtin = match node.node.keyword() {
+            Some(keyword) if !self.hooks.is_empty() && !node.node.children().is_empty() => keyword,
+            _ => return stack::grow(|| self.evaluate_node(node)),
+        };
+        self.hooks.iter_mut().for_each(|hook| hook.enter_function(builtin, true));
+        let value = stack::grow(|| self.evaluate_node(node));
+        self.hooks.iter_mut().for_each(|hook| hook.exit_function(builtin, true));
+        value
+    }
+
+    fn evaluate_node(&mut self, node: &Node) -> Value {
+        let span = node.span;
+        self.budget.step(span);
+        match &node.node {
+            ASTNode::Float(value) => Value::number(value, self.numeric),
+            ASTNode::Quantity(value, unit) => {
+                let unit = units::find(unit).expect("Number literal with an unknown unit");
+                Value::Quantity(Box::new(Value::number(value, self.numeric)), Compound::single(unit).name())
+            }
+            ASTNode::NaN => Value::Float(f64::NAN),
+            ASTNode::Inf => Value::Float(f64::INFINITY),
+            ASTNode::Nil => Value::Nil,
             ASTNode::Identifier(name) => {
-                let value = self.variables.get(&name).expect("Undefined variable").clone();
-                value
+                self.scope.get(name)
+                    .unwrap_or_else(|| panic!("Undefined variable '{}' on line {}, column {}.", name, span.start.line, span.start.column))
+                    .clone()
             },
+            // `and`/`or` short-circuit, so the right side is only evaluated when needed
+            ASTNode::BinaryOp(left, Token::And, right) => {
+                let result = self.evaluate(left).is_truthy() && self.evaluate(right).is_truthy();
+                Value::bool(result)
+            }
+            ASTNode::BinaryOp(left, Token::Or, right) => {
+                let result = self.evaluate(left).is_truthy() || self.evaluate(right).is_truthy();
+                Value::bool(result)
+            }
             ASTNode::BinaryOp(left, op, right) => {
-                let left_val = self.evaluate(*left);
-                let right_val = self.evaluate(*right);
+                let left_val = self.evaluate(left);
+                let right_val = self.evaluate(right);
+                // Any values can be tested for equality and strings can be ordered;
+                // everything else needs numbers
+                let allowed = match op {
+                    Token::EqualEqual | Token::NotEqual => true,
+                    _ if left_val.is_number() && right_val.is_number() => true,
+                    Token::LessThan | Token::LessEqual | Token::GreaterThan | Token::GreaterEqual => {
+                        matches!((&left_val, &right_val), (Value::Str(_), Value::Str(_)))
+                    }
+                    _ => false,
+                };
+                if !allowed {
+                    panic!("Cannot apply '{}' to a {} and a {} on line {}, column {}.", operator(op).trim(), left_val.type_name(), right_val.type_name(), span.start.line, span.start.column);
+                }
+                arithmetic(op, left_val, right_val, self.numeric, span)
+            }
+            ASTNode::UnaryOp(op, operand) => {
+                let value = self.evaluate(operand);
                 match op {
-                    Token::Plus => left_val + right_val,
-                    Token::Minus => left_val - right_val,
-                    Token::Star => left_val * right_val,
-                    Token::StarStar => {
-                        let exponent = right_val.re.to_f64().unwrap();
-                        let base = left_val.re.to_f64().unwrap();
-                        let result = base.powf(exponent);
-                        BigRational::from_float(result).unwrap().into()
-                    },
-                    Token::Slash => left_val / right_val,
-                    Token::Modulo => {
-                        let left_val = left_val.re.to_integer();
-                        let right_val = right_val.re.to_integer();
-                        BigRational::from_integer(left_val % right_val).into()
+                    Token::Minus => -value,
+                    Token::Not => Value::bool(!value.is_truthy()),
+                    _ => panic!("Unexpected operator: {:?}", op),
+                }
+            }
+            ASTNode::DewPoint(args) => {
+                let temp = self.input(&args[0], Quantity::Celsius, node);
+                let humidity = self.input(&args[1], Quantity::Humidity, node);
+                // ln() of 0% is -inf
+                if humidity == Value::int(0) {
+                    panic!("The relative humidity given to dewpoint must be above 0 percent on line {}, column {}.", span.start.line, span.start.column);
+                }
+                match args.get(2).map(|formula| self.evaluate(formula)) {
+                    None => magnus_dewpoint(temp, humidity, self.numeric),
+                    Some(Value::Str(formula)) if formula == "magnus" => magnus_dewpoint(temp, humidity, self.numeric),
+                    Some(Value::Str(formula)) if formula == "buck" => Value::float(formulas::buck_dewpoint(temp.to_f64(), humidity.to_f64()), self.numeric),
+                    Some(other) => panic!("Unknown dew point formula '{}', expected magnus or buck on line {}, column {}.", other, span.start.line, span.start.column),
+                }
+            }
+            ASTNode::ApparentTemp(temp, humidity, wind) => {
+                let temp = self.input(temp, Quantity::Celsius, node);
+                let humidity = self.input(humidity, Quantity::Humidity, node);
+                let wind = self.input(wind, Quantity::Speed, node);
+                // Australian Bureau of Meteorology apparent temperature (Steadman),
+                // from the water vapour pressure in hPa and the wind speed in m/s
+                let exponent = (Value::ratio(1727, 100) * temp.clone()) / (Value::ratio(2377, 10) + temp.clone());
+                let vapour = humidity / Value::int(100) * Value::ratio(6105, 1000) * Value::float(exponent.to_f64().exp(), self.numeric);
+                temp + Value::ratio(33, 100) * vapour - Value::ratio(70, 100) * wind - Value::int(4)
+            }
+            ASTNode::Svp(args) => {
+                let temp = self.input(&args[0], Quantity::Celsius, node).to_f64();
+                let formula = match args.get(1).map(|formula| self.evaluate(formula)) {
+                    None => "magnus".to_string(),
+                    Some(Value::Str(formula)) if SVP_FORMULAS.contains(&formula.as_str()) => formula,
+                    Some(other) => panic!(
+                        "Unknown saturation vapour pressure formula '{}', expected one of {} on line {}, column {}.",
+                        other, SVP_FORMULAS.join(", "), span.start.line, span.start.column
+                    ),
+                };
+                let ice = match args.get(2).map(|surface| self.evaluate(surface)) {
+                    None => false,
+                    Some(Value::Str(surface)) if surface == "water" || surface == "ice" => surface == "ice",
+                    Some(other) => panic!("Expected the surface \"water\" or \"ice\", found '{}' on line {}, column {}.", other, span.start.line, span.start.column),
+                };
+                Value::float(saturation_vapour_pressure(temp, &formula, ice), self.numeric)
+            }
+            ASTNode::MixingRatio(temp, humidity, pressure) | ASTNode::SpecificHumidity(temp, humidity, pressure) => {
+                let temp = self.input(temp, Quantity::Celsius, node).to_f64();
+                let humidity = self.input(humidity, Quantity::Humidity, node).to_f64();
+                let pressure = self.input(pressure, Quantity::Pressure, node).to_f64();
+                let value = match &node.node {
+                    ASTNode::MixingRatio(..) => formulas::mixing_ratio(temp, humidity, pressure),
+                    _ => formulas::specific_humidity(temp, humidity, pressure),
+                };
+                Value::float(value, self.numeric)
+            }
+            ASTNode::PressureAtAltitude(pressure, altitude, temp) => {
+                let pressure = self.input(pressure, Quantity::Pressure, node);
+                let altitude = self.input(altitude, Quantity::Number, node);
+                let temp = self.input(temp, Quantity::Celsius, node) + Value::number(kelvin_constant(), self.numeric);
+                // Barometric formula for an isothermal layer at the mean temperature
+                let g = self.constant("g");
+                let rd = self.constant("rd");
+                let exponent = -(g * altitude) / (rd * temp);
+                pressure * Value::float(exponent.to_f64().exp(), self.numeric)
+            }
+            ASTNode::Isa(altitude) => {
+                let altitude = self.input(altitude, Quantity::Number, node).to_f64();
+                if altitude > ISA_CEILING {
+                    panic!("The standard atmosphere only covers altitudes up to {} m, found {} on line {}, column {}.", ISA_CEILING, altitude, span.start.line, span.start.column);
+                }
+                let (temp, pressure, density) = formulas::isa(altitude);
+                Value::List(vec![Value::float(temp, self.numeric), Value::float(pressure, self.numeric), Value::float(density, self.numeric)])
+            }
+            // g / cp, in K/km
+            ASTNode::Dalr => self.constant("g") / self.constant("cp") * Value::int(1000),
+            ASTNode::Malr(temp, pressure) => {
+                let temp = self.input(temp, Quantity::Celsius, node).to_f64();
+                let pressure = self.input(pressure, Quantity::Pressure, node).to_f64();
+                Value::float(formulas::moist_lapse_rate(temp, pressure, &self.constants), self.numeric)
+            }
+            ASTNode::DryAdiabat(temp, start, end) => {
+                let kelvin = Value::number(kelvin_constant(), self.numeric);
+                let temp = self.input(temp, Quantity::Celsius, node) + kelvin.clone();
+                let ratio = self.input(end, Quantity::Pressure, node) / self.input(start, Quantity::Pressure, node);
+                // Poisson's equation: potential temperature is conserved
+                let exponent = self.constant("rd") / self.constant("cp");
+                temp * ratio.pow(&exponent, self.numeric) - kelvin
+            }
+            ASTNode::Et0(args) => {
+                let quantities = [Quantity::Celsius, Quantity::Humidity, Quantity::Speed, Quantity::Number, Quantity::Pressure];
+                let args: Vec<f64> = args.iter().zip(quantities).map(|(arg, quantity)| self.input(arg, quantity, node).to_f64()).collect();
+                Value::float(formulas::reference_evapotranspiration(args[0], args[1], args[2], args[3], args[4]), self.numeric)
+            }
+            ASTNode::Hdd(temps, base) => self.degree_days(temps, base, true, node),
+            ASTNode::Cdd(temps, base) => self.degree_days(temps, base, false, node),
+            // 1 inch is 25.4 mm, for depths and rates alike
+            ASTNode::MmToIn(mm) => self.input(mm, Quantity::Millimetres, node) * Value::ratio(5, 127),
+            ASTNode::MmhToInh(mm) => self.input(mm, Quantity::MillimetresPerHour, node) * Value::ratio(5, 127),
+            ASTNode::InToMm(inches) => self.input(inches, Quantity::Inches, node) * Value::ratio(127, 5),
+            ASTNode::InhToMmh(inches) => self.input(inches, Quantity::InchesPerHour, node) * Value::ratio(127, 5),
+            // Plain numbers may be in any unit; rates with a unit are taken in
+            // mm/h, so the total is then in mm
+            ASTNode::Accumulation(rates, hours) => {
+                let rates = self.numbers(rates, "accumulation", "rates", span);
+                let hours = self.input(hours, Quantity::Hours, node);
+                rates.into_iter().fold(Value::int(0), |total, rate| total + self.check_input(rate, Quantity::MillimetresPerHour, node) * hours.clone())
+            }
+            ASTNode::RunningTotal(amounts) => {
+                let mut total = Value::int(0);
+                let amounts = self.numbers(amounts, "running_total", "amounts", span);
+                let totals = amounts.into_iter().map(|amount| {
+                    total = total.clone() + self.check_input(amount, Quantity::Millimetres, node);
+                    total.clone()
+                });
+                Value::List(totals.collect())
+            }
+            // Linear interpolation between the closest ranks, as in NumPy and
+            // Excel's PERCENTILE.INC
+            ASTNode::Percentile(values, percent) => {
+                let mut values = self.numbers(values, "percentile", "values", span);
+                let percent = self.input(percent, Quantity::Number, node);
+                if values.is_empty() {
+                    panic!("percentile needs at least one value on line {}, column {}.", span.start.line, span.start.column);
+                }
+                if !(0.0..=100.0).contains(&percent.to_f64()) {
+                    panic!("The percentile must be between 0 and 100, found {} on line {}, column {}.", percent, span.start.line, span.start.column);
+                }
+                values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+                let rank = percent / Value::int(100) * Value::int(values.len() as i64 - 1);
+                let lower = (rank.to_f64().floor() as usize).min(values.len() - 1);
+                let upper = (lower + 1).min(values.len() - 1);
+                let fraction = rank - Value::int(lower as i64);
+                let step = arithmetic(&Token::Minus, values[upper].clone(), values[lower].clone(), self.numeric, span);
+                arithmetic(&Token::Plus, values[lower].clone(), arithmetic(&Token::Star, step, fraction, self.numeric, span), self.numeric, span)
+            }
+            // Percentage of the values at or below `value`
+            ASTNode::PercentileRank(values, value) => {
+                let values = self.numbers(values, "percentile_rank", "values", span);
+                let value = self.input(value, Quantity::Number, node);
+                if values.is_empty() {
+                    panic!("percentile_rank needs at least one value on line {}, column {}.", span.start.line, span.start.column);
+                }
+                let total = values.len() as i64;
+                let count = values.into_iter().filter(|item| arithmetic(&Token::LessEqual, item.clone(), value.clone(), self.numeric, span).is_truthy()).count();
+                Value::int(100 * count as i64) / Value::int(total)
+            }
+            // Departure of each value from its normal
+            ASTNode::Anomaly(values, normals) => {
+                let values = self.numbers(values, "anomaly", "values", span);
+                let normals = match self.evaluate(normals) {
+                    Value::List(normals) if normals.len() == values.len() && normals.iter().all(Value::is_number) => normals,
+                    Value::List(normals) => panic!(
+                        "anomaly expects one number of normals for each of the {} values, found a list of {} on line {}, column {}.",
+                        values.len(), normals.len(), span.start.line, span.start.column
+                    ),
+                    normal if normal.is_number() => vec![normal; values.len()],
+                    other => panic!("anomaly expects a list of normals or one number, found a {} on line {}, column {}.", other.type_name(), span.start.line, span.start.column),
+                };
+                Value::List(values.into_iter().zip(normals).map(|(value, normal)| arithmetic(&Token::Minus, value, normal, self.numeric, span)).collect())
+            }
+            // Centred moving mean that wraps around the ends, so daily normals
+            // for a year are smoothed across New Year
+            ASTNode::RollingMean(values, window) => {
+                let values = self.numbers(values, "rolling_mean", "values", span);
+                let window = match self.evaluate(window).to_usize() {
+                    Some(window) if window % 2 == 1 && window <= values.len() => window,
+                    _ => panic!("The window given to rolling_mean must be an odd whole number no longer than the list on line {}, column {}.", span.start.line, span.start.column),
+                };
+                let length = values.len();
+                let means = (0..length).map(|center| {
+                    let mut window_values = (0..window).map(|offset| values[(center + length + offset - window / 2) % length].clone());
+                    let first = window_values.next().expect("The window is at least 1");
+                    let total = window_values.fold(first, |total, value| arithmetic(&Token::Plus, total, value, self.numeric, span));
+                    arithmetic(&Token::Slash, total, Value::int(window as i64), self.numeric, span)
+                });
+                Value::List(means.collect())
+            }
+            ASTNode::Spi(precipitation, timescale) => {
+                let precipitation: Vec<f64> = self.numbers(precipitation, "spi", "monthly precipitation totals", span).iter().map(Value::to_f64).collect();
+                if let Some(negative) = precipitation.iter().find(|total| **total < 0.0) {
+                    panic!("The precipitation given to spi must be zero or more, found {} on line {}, column {}.", negative, span.start.line, span.start.column);
+                }
+                let timescale = match self.evaluate(timescale).to_usize() {
+                    Some(timescale) if timescale >= 1 => timescale,
+                    _ => panic!("The timescale given to spi must be a whole number of months, at least 1 on line {}, column {}.", span.start.line, span.start.column),
+                };
+                Value::List(formulas::spi(&precipitation, timescale).into_iter().map(Value::from_f64).collect())
+            }
+            // Share of the observations from each direction sector, and their
+            // mean speed; calm observations belong to no sector
+            ASTNode::Windrose(directions, speeds, sectors) => {
+                let directions = self.numbers(directions, "windrose", "directions", span);
+                let speeds = self.numbers(speeds, "windrose", "speeds", span);
+                if directions.len() != speeds.len() {
+                    panic!("windrose expects as many speeds as directions, found {} directions and {} speeds on line {}, column {}.", directions.len(), speeds.len(), span.start.line, span.start.column);
+                }
+                let sectors = match self.evaluate(sectors).to_usize() {
+                    Some(sectors @ 1..=360) => sectors,
+                    _ => panic!("The number of sectors given to windrose must be a whole number from 1 to 360 on line {}, column {}.", span.start.line, span.start.column),
+                };
+                let speeds: Vec<f64> = speeds.iter().map(|speed| self.check_input(speed.clone(), Quantity::Speed, node).to_f64()).collect();
+                let directions: Vec<f64> = directions.iter().map(Value::to_f64).collect();
+                let rose = formulas::windrose(&directions, &speeds, sectors);
+                let width = 360.0 / sectors as f64;
+                let rows = rose.into_iter().enumerate().map(|(sector, (count, mean_speed))| {
+                    let frequency = if directions.is_empty() { 0.0 } else { 100.0 * count as f64 / directions.len() as f64 };
+                    let entries = [
+                        ("direction", Value::float(sector as f64 * width, self.numeric)),
+                        ("count", Value::int(count as i64)),
+                        ("frequency", Value::float(frequency, self.numeric)),
+                        ("mean_speed", Value::float(mean_speed, self.numeric)),
+                    ];
+                    Value::Map(entries.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+                });
+                Value::List(rows.collect())
+            }
+            // Runs of at least `min_length` values beyond the threshold, such
+            // as heat waves or (with "below") frost spells
+            ASTNode::Events(args) => {
+                let series = self.numbers(&args[0], "events", "observations", span);
+                let threshold = self.input(&args[1], Quantity::Number, node);
+                let min_length = match self.evaluate(&args[2]).to_usize() {
+                    Some(length) if length > 0 => length,
+                    _ => panic!("The minimum length given to events must be a whole number of at least 1 on line {}, column {}.", span.start.line, span.start.column),
+                };
+                let below = match args.get(3).map(|direction| self.evaluate(direction)) {
+                    None => false,
+                    Some(Value::Str(direction)) if direction == "above" => false,
+                    Some(Value::Str(direction)) if direction == "below" => true,
+                    Some(other) => panic!("Unknown direction '{}' given to events, expected \"above\" or \"below\" on line {}, column {}.", other, span.start.line, span.start.column),
+                };
+                let beyond = |value: &Value| if below { *value < threshold } else { *value > threshold };
+                let mut events = Vec::new();
+                let mut start = 0;
+                while start < series.len() {
+                    if !beyond(&series[start]) {
+                        start += 1;
+                        continue;
                     }
-                    Token::GreaterThan => {
-                        if left_val.re > right_val.re { BigRational::from_integer(BigInt::from(1)).into() } else { BigRational::from_integer(BigInt::from(0)).into() }
+                    let length = series[start..].iter().take_while(|value| beyond(value)).count();
+                    if length >= min_length {
+                        let run = &series[start..start + length];
+                        let peak = run.iter().skip(1).fold(run[0].clone(), |peak, value| {
+                            let further = if below { *value < peak } else { *value > peak };
+                            if further { value.clone() } else { peak }
+                        });
+                        let entries = [("start", Value::int(start as i64)), ("length", Value::int(length as i64)), ("peak", peak)];
+                        events.push(Value::Map(entries.into_iter().map(|(key, value)| (key.to_string(), value)).collect()));
                     }
-                    Token::LessThan => {
-                        if left_val.re < right_val.re { BigRational::from_integer(BigInt::from(1)).into() } else { BigRational::from_integer(BigInt::from(0)).into() }
+                    start += length;
+                }
+                Value::List(events)
+            }
+            ASTNode::Slr(temp) => Value::float(formulas::snow_liquid_ratio(self.input(temp, Quantity::Celsius, node).to_f64()), self.numeric),
+            ASTNode::Snowfall(liquid, temp) => {
+                let liquid = self.input(liquid, Quantity::Number, node);
+                liquid * Value::float(formulas::snow_liquid_ratio(self.input(temp, Quantity::Celsius, node).to_f64()), self.numeric)
+            }
+            ASTNode::VisConvert(value, from, to) => {
+                let value = self.input(value, Quantity::Number, node);
+                let mut metres = |unit: &Node| match self.evaluate(unit) {
+                    Value::Str(unit) if unit == "m" => Value::int(1),
+                    Value::Str(unit) if unit == "km" => Value::int(1000),
+                    Value::Str(unit) if unit == "mi" => Value::ratio(1609344, 1000),
+                    other => panic!("Unknown visibility unit '{}', expected m, km or mi on line {}, column {}.", other, span.start.line, span.start.column),
+                };
+                let (from, to) = (metres(from), metres(to));
+                value * from / to
+            }
+            ASTNode::FogCategory(args) => {
+                let visibility = self.input(&args[0], Quantity::Number, node).to_f64();
+                let humidity = args.get(1).map(|humidity| self.input(humidity, Quantity::Humidity, node).to_f64());
+                Value::Str(formulas::fog_category(visibility, humidity).to_string())
+            }
+            ASTNode::SolarZenith(latitude, longitude, timestamp) => {
+                let latitude = self.input(latitude, Quantity::Number, node).to_f64();
+                let longitude = self.input(longitude, Quantity::Number, node).to_f64();
+                let timestamp = self.input(timestamp, Quantity::Number, node).to_f64();
+                Value::float(formulas::solar_zenith(latitude, longitude, timestamp), self.numeric)
+            }
+            ASTNode::Rand => Value::float(self.random(), self.numeric),
+            ASTNode::RandInt(lowest, highest) => {
+                let lowest = self.input(lowest, Quantity::Number, node).to_f64();
+                let highest = self.input(highest, Quantity::Number, node).to_f64();
+                if lowest.fract() != 0.0 || highest.fract() != 0.0 || lowest > highest {
+                    panic!("randint expects two whole numbers, the first at most the second, found {} and {} on line {}, column {}.", lowest, highest, span.start.line, span.start.column);
+                }
+                let value = self.rng.lock().unwrap_or_else(PoisonError::into_inner).gen_range(lowest as i64..=highest as i64);
+                Value::int(value)
+            }
+            // Normally distributed by the Box-Muller transform
+            ASTNode::RandN(mean, deviation) => {
+                let mean = self.input(mean, Quantity::Number, node).to_f64();
+                let deviation = self.input(deviation, Quantity::Number, node).to_f64();
+                if deviation < 0.0 {
+                    panic!("randn expects a standard deviation of at least 0, found {} on line {}, column {}.", deviation, span.start.line, span.start.column);
+                }
+                let (uniform, angle) = (1.0 - self.random(), self.random());
+                let normal = (-2.0 * uniform.ln()).sqrt() * (2.0 * std::f64::consts::PI * angle).cos();
+                Value::float(mean + deviation * normal, self.numeric)
+            }
+            ASTNode::ToTimezone(timestamp, zone) | ASTNode::UtcOffset(timestamp, zone) => {
+                let timestamp = self.input(timestamp, Quantity::Number, node).to_f64();
+                let zone = match self.evaluate(zone) {
+                    Value::Str(zone) => zone,
+                    other => panic!("{} expects a time zone such as America/Denver, found a {} on line {}, column {}.", node.node.keyword().unwrap_or_default(), other.type_name(), span.start.line, span.start.column),
+                };
+                let value = match &node.node {
+                    // In hours, e.g. -6 for Mountain Daylight Time or 5.5 for India
+                    ASTNode::UtcOffset(..) => timezone::offset(timestamp, &zone).map(|seconds| Value::float(seconds as f64 / 3600.0, self.numeric)),
+                    _ => timezone::local(timestamp, &zone).map(Value::Str),
+                };
+                value.unwrap_or_else(|error| panic!("{} on line {}, column {}.", error, span.start.line, span.start.column))
+            }
+            ASTNode::SolarDeclination(day) => Value::float(formulas::solar_declination(self.input(day, Quantity::Number, node).to_f64()), self.numeric),
+            ASTNode::UvIndex(args) => {
+                let zenith = self.input(&args[0], Quantity::Number, node).to_f64();
+                let ozone = args.get(1).map_or(DEFAULT_OZONE, |ozone| self.input(ozone, Quantity::Number, node).to_f64());
+                Value::float(formulas::uv_index(zenith, ozone), self.numeric)
+            }
+            // Coriolis parameter f = 2Ω sin(latitude), in 1/s
+            ASTNode::Coriolis(latitude) => {
+                let sine = Value::float(self.input(latitude, Quantity::Number, node).to_f64().to_radians().sin(), self.numeric);
+                Value::int(2) * self.constant("omega") * sine
+            }
+            ASTNode::PrecipitableWater(pressures, ratios) => {
+                let pressures = self.numbers(pressures, "precipitable_water", "pressures", span);
+                let pressures: Vec<Value> = pressures.into_iter().map(|pressure| self.check_input(pressure, Quantity::Pressure, node)).collect();
+                let ratios = self.numbers(ratios, "precipitable_water", "mixing ratios", span);
+                if pressures.len() != ratios.len() || pressures.len() < 2 {
+                    panic!(
+                        "precipitable_water expects at least two levels with one mixing ratio per pressure, found {} pressures and {} mixing ratios on line {}, column {}.",
+                        pressures.len(), ratios.len(), span.start.line, span.start.column
+                    );
+                }
+                // Trapezoidal integral of the mixing ratio over pressure: with
+                // g/kg and hPa, the sum over 10 g is the depth in mm
+                let mut total = Value::int(0);
+                for level in 1..pressures.len() {
+                    let thickness = pressures[level - 1].clone() - pressures[level].clone();
+                    let thickness = if thickness < Value::int(0) { -thickness } else { thickness };
+                    total = total + (ratios[level - 1].clone() + ratios[level].clone()) / Value::int(2) * thickness;
+                }
+                total / (Value::int(10) * self.constant("g"))
+            }
+            ASTNode::SpeedOfSound(temp, humidity) => {
+                let temp = self.input(temp, Quantity::Celsius, node).to_f64();
+                let humidity = self.input(humidity, Quantity::Humidity, node).to_f64();
+                Value::float(formulas::speed_of_sound(temp, humidity, &self.constants), self.numeric)
+            }
+            ASTNode::Altimeter(pressure, elevation) => {
+                let pressure = self.input(pressure, Quantity::Pressure, node).to_f64();
+                let elevation = self.input(elevation, Quantity::Number, node).to_f64();
+                Value::float(formulas::altimeter_setting(pressure, elevation), self.numeric)
+            }
+            ASTNode::RhFromWetbulb(dry, wet, pressure) => {
+                let dry = self.input(dry, Quantity::Celsius, node).to_f64();
+                let wet = self.input(wet, Quantity::Celsius, node).to_f64();
+                let pressure = self.input(pressure, Quantity::Pressure, node).to_f64();
+                Value::float(formulas::rh_from_wetbulb(dry, wet, pressure), self.numeric)
+            }
+            ASTNode::Haversine(args) | ASTNode::Bearing(args) => {
+                let coords: Vec<f64> = args.iter().map(|arg| self.input(arg, Quantity::Number, node).to_f64()).collect();
+                let result = match &node.node {
+                    ASTNode::Haversine(_) => formulas::haversine(coords[0], coords[1], coords[2], coords[3]),
+                    _ => formulas::bearing(coords[0], coords[1], coords[2], coords[3]),
+                };
+                Value::float(result, self.numeric)
+            }
+            ASTNode::ParseCoord(text) => match self.evaluate(text) {
+                Value::Str(text) => match coords::parse(&text) {
+                    Ok(degrees) => Value::float(degrees, self.numeric),
+                    Err(error) => panic!("{} on line {}, column {}.", error, span.start.line, span.start.column),
+                },
+                other => panic!("parse_coord expects a string, found a {} on line {}, column {}.", other.type_name(), span.start.line, span.start.column),
+            },
+            ASTNode::FormatCoord(args) => {
+                let latitude = self.input(&args[0], Quantity::Number, node).to_f64();
+                let longitude = self.input(&args[1], Quantity::Number, node).to_f64();
+                let style = match args.get(2).map(|style| self.evaluate(style)) {
+                    None => "dms".to_string(),
+                    Some(Value::Str(style)) => style,
+                    Some(other) => panic!("Coordinate styles must be strings, found a {} on line {}, column {}.", other.type_name(), span.start.line, span.start.column),
+                };
+                match coords::format(latitude, longitude, &style) {
+                    Ok(text) => Value::Str(text),
+                    Err(error) => panic!("{} on line {}, column {}.", error, span.start.line, span.start.column),
+                }
+            }
+            ASTNode::Station(id) => {
+                // WMO ids are numbers to most people, but have leading zeros
+                let id = match self.evaluate(id) {
+                    Value::Str(id) => id,
+                    number if number.is_number() && number.to_usize().is_some() => format!("{:05}", number.to_usize().unwrap()),
+                    other => panic!("Station ids must be strings or WMO numbers, found {} on line {}, column {}.", other, span.start.line, span.start.column),
+                };
+                stations::find(&id)
+                    .unwrap_or_else(|| panic!("Unknown station '{}' on line {}, column {}.", id, span.start.line, span.start.column))
+                    .to_value()
+            }
+            ASTNode::Cloudbase(args) => {
+                let spread = self.input(&args[0], Quantity::Celsius, node) - self.input(&args[1], Quantity::Celsius, node);
+                // A rising parcel closes the temperature-dew point spread by
+                // about 1 °C every 125 m
+                let metres = spread * Value::int(125);
+                match args.get(2).map(|unit| self.evaluate(unit)) {
+                    None => metres,
+                    Some(Value::Str(unit)) if unit == "m" => metres,
+                    Some(Value::Str(unit)) if unit == "ft" => metres * Value::ratio(10000, 3048),
+                    Some(other) => panic!("Unknown height unit '{}', expected m or ft on line {}, column {}.", other, span.start.line, span.start.column),
+                }
+            }
+            ASTNode::Convert(args) => {
+                let value = match self.evaluate(&args[0]) {
+                    quantity @ Value::Quantity(..) => quantity,
+                    other => self.check_input(other, Quantity::Number, node),
+                };
+                let mut unit = |unit: &Node| match self.evaluate(unit) {
+                    Value::Str(name) => Compound::parse(&name)
+                        .unwrap_or_else(|| panic!("Unknown unit '{}' given to convert on line {}, column {}.", name, span.start.line, span.start.column)),
+                    other => panic!("Units given to convert must be strings, found a {} on line {}, column {}.", other.type_name(), span.start.line, span.start.column),
+                };
+                // A quantity knows its unit, so only the target is given
+                let (value, from, to) = match (value, args.len()) {
+                    (Value::Quantity(magnitude, from), 2) => (*magnitude, compound(&from), unit(&args[1])),
+                    (Value::Quantity(magnitude, from), _) => {
+                        panic!("{} {} already has a unit, convert it with convert(value, to) on line {}, column {}.", magnitude, from, span.start.line, span.start.column)
                     }
-                    _ => panic!("Unexpected operator: {:?}", op),
+                    (_, 2) => panic!("convert needs the unit to convert from, as in convert(value, from, to), unless the value has a unit on line {}, column {}.", span.start.line, span.start.column),
+                    (value, _) => (value, unit(&args[1]), unit(&args[2])),
+                };
+                if from.dimension() != to.dimension() {
+                    panic!(
+                        "Cannot convert {} in '{}' to {} in '{}' on line {}, column {}.",
+                        from.dimension().name(), from.name(), to.dimension().name(), to.name(), span.start.line, span.start.column
+                    );
+                }
+                if from.dimension() == Dimension::TEMPERATURE && from.to_base(value.clone()) < Value::int(0) {
+                    panic!("The temperature given to convert must be above absolute zero, found {} {} on line {}, column {}.", value, from.name(), span.start.line, span.start.column);
                 }
+                let converted = from.convert(value, &to);
+                match args.len() {
+                    2 => Value::Quantity(Box::new(converted), to.name()),
+                    _ => converted,
+                }
+            }
+            ASTNode::WindComponents(speed, direction) => {
+                let speed = self.input(speed, Quantity::Speed, node).to_f64();
+                let (u, v) = formulas::wind_components(speed, self.input(direction, Quantity::Number, node).to_f64());
+                Value::List(vec![Value::float(u, self.numeric), Value::float(v, self.numeric)])
             }
-            ASTNode::DewPoint(temp, humidity) => {
-                let temp = self.evaluate(*temp);
-                let humidity = self.evaluate(*humidity);
-                // Dew point calculation formula
-                let a = BigRational::new(BigInt::from(1727), BigInt::from(100));
-                let b = BigRational::new(BigInt::from(2377), BigInt::from(10));
-                let temp_re = temp.re.clone();
-                let alpha = ((a.clone() * temp_re.clone()) / (b.clone() + temp_re)) + BigRational::from_float(humidity.to_f64().unwrap().ln()).unwrap();
-                ((b * alpha.clone()) / (a - alpha)).into()
+            ASTNode::WindFromComponents(u, v) => {
+                let (u, v) = (self.input(u, Quantity::Number, node).to_f64(), self.input(v, Quantity::Number, node).to_f64());
+                let (speed, direction) = formulas::wind_from_components(u, v);
+                Value::List(vec![Value::float(speed, self.numeric), Value::float(direction, self.numeric)])
             }
             ASTNode::FToC(fahrenheit) => {
-                let fahrenheit = self.evaluate(*fahrenheit);
-                (fahrenheit - BigRational::from_integer(BigInt::from(32))) * BigRational::new(BigInt::from(5), BigInt::from(9))
+                let fahrenheit = self.input(fahrenheit, Quantity::Fahrenheit, node);
+                (fahrenheit - Value::int(32)) * Value::ratio(5, 9)
             }
             ASTNode::CToF(celsius) => {
-                let celsius = self.evaluate(*celsius);
-                (celsius * BigRational::new(BigInt::from(9), BigInt::from(5))) + BigRational::from_integer(BigInt::from(32))
+                let celsius = self.input(celsius, Quantity::Celsius, node);
+                (celsius * Value::ratio(9, 5)) + Value::int(32)
             }
             ASTNode::CToK(celsius) => {
-                let celsius = self.evaluate(*celsius);
-                celsius + kelvin_constant()
+                let celsius = self.input(celsius, Quantity::Celsius, node);
+                celsius + Value::number(kelvin_constant(), self.numeric)
             }
             ASTNode::KToC(kelvin) => {
-                let kelvin = self.evaluate(*kelvin);
-                kelvin - kelvin_constant()
+                let kelvin = self.input(kelvin, Quantity::Kelvin, node);
+                kelvin - Value::number(kelvin_constant(), self.numeric)
             }
             ASTNode::FToK(fahrenheit) => {
-                let fahrenheit = self.evaluate(*fahrenheit);
-                (fahrenheit - BigRational::from_integer(BigInt::from(32))) * BigRational::new(BigInt::from(5), BigInt::from(9)) + kelvin_constant()
+                let fahrenheit = self.input(fahrenheit, Quantity::Fahrenheit, node);
+                (fahrenheit - Value::int(32)) * Value::ratio(5, 9) + Value::number(kelvin_constant(), self.numeric)
             }
             ASTNode::KToF(kelvin) => {
-                let kelvin = self.evaluate(*kelvin);
-                (kelvin - kelvin_constant()) * BigRational::new(BigInt::from(9), BigInt::from(5)) + BigRational::from_integer(BigInt::from(32))
+                let kelvin = self.input(kelvin, Quantity::Kelvin, node);
+                (kelvin - Value::number(kelvin_constant(), self.numeric)) * Value::ratio(9, 5) + Value::int(32)
             }
             ASTNode::PauliX(qubit) => {
-                let qubit = self.evaluate(*qubit);
-                if qubit == BigRational::from_integer(BigInt::from(0)).into() {
-                    BigRational::from_integer(BigInt::from(1)).into()
+                let qubit = self.evaluate(qubit);
+                if qubit == Value::int(0) {
+                    Value::int(1)
                 } else {
-                    BigRational::from_integer(BigInt::from(0)).into()
+                    Value::int(0)
                 }
             }
             ASTNode::PauliY(qubit) => {
-                let qubit = self.evaluate(*qubit);
-                if qubit == BigRational::from_integer(BigInt::from(0)).into() {
-                    BigRational::from_integer(BigInt::from(1)).into()
+                let qubit = self.evaluate(qubit);
+                if qubit == Value::int(0) {
+                    Value::int(1)
                 } else {
-                    BigRational::from_integer(BigInt::from(-1)).into()
+                    Value::int(-1)
                 }
             }
-            ASTNode::PauliZ(qubit) => {
-                let qubit = self.evaluate(*qubit);
-                qubit
-            }
+            ASTNode::PauliZ(qubit) => self.evaluate(qubit),
             ASTNode::Hadamard(qubit) => {
-                let qubit = self.evaluate(*qubit);
-                (qubit + BigRational::from_integer(BigInt::from(1))) / BigRational::from_integer(BigInt::from(2))
+                let qubit = self.evaluate(qubit);
+                (qubit + Value::int(1)) / Value::int(2)
             }
             ASTNode::CNot(control, target) => {
-                let control = self.evaluate(*control);
-                let target = self.evaluate(*target);
-                if control == BigRational::from_integer(BigInt::from(1)).into() {
-                    if target == BigRational::from_integer(BigInt::from(0)).into() {
-                        BigRational::from_integer(BigInt::from(1)).into()
+                let control = self.evaluate(control);
+                let target = self.evaluate(target);
+                if control == Value::int(1) {
+                    if target == Value::int(0) {
+                        Value::int(1)
                     } else {
-                        BigRational::from_integer(BigInt::from(0)).into()
+                        Value::int(0)
                     }
                 } else {
                     target
@@ -219,134 +1675,272 @@ impl Interpreter {
             }
             // Create number of qubits with the given state
             ASTNode::Qubit(state, num_qubits) => {
-                let state = self.evaluate(*state);
-                let num_qubits = self.evaluate(*num_qubits);
-                let mut result = BigRational::from_integer(BigInt::from(0));
-                for _ in 0..num_qubits.to_usize().unwrap() {
-                    result = (result * BigRational::from_integer(BigInt::from(2))) + state.re.clone();
+                let state = self.evaluate(state);
+                let num_qubits = self.evaluate(num_qubits);
+                let mut result = Value::int(0);
+                for _ in 0..num_qubits.to_usize().expect("Number of qubits must be a non-negative integer") {
+                    result = (result * Value::int(2)) + state.clone();
                 }
-                result.into()
+                result
             }
             ASTNode::MeasureQubit(qubit) => {
-                let qubit = self.evaluate(*qubit);
-                if qubit == BigRational::from_integer(BigInt::from(0)).into() {
-                    BigRational::from_integer(BigInt::from(0)).into()
+                let qubit = self.evaluate(qubit);
+                if qubit == Value::int(0) {
+                    Value::int(0)
                 } else {
-                    BigRational::from_integer(BigInt::from(1)).into()
+                    Value::int(1)
                 }
             }
             ASTNode::ResetQubit(qubit) => {
-                let _ = self.evaluate(*qubit);
-                BigRational::from_integer(BigInt::from(0)).into()
+                let _ = self.evaluate(qubit);
+                Value::int(0)
             }
             ASTNode::Toffoli(control1, control2, target) => {
-                let control1 = self.evaluate(*control1);
-                let control2 = self.evaluate(*control2);
-                let target = self.evaluate(*target);
-                if control1 == BigRational::from_integer(BigInt::from(1)).into() && control2 == BigRational::from_integer(BigInt::from(1)).into() {
-                    if target == BigRational::from_integer(BigInt::from(0)).into() {
-                        BigRational::from_integer(BigInt::from(1)).into()
+                let control1 = self.evaluate(control1);
+                let control2 = self.evaluate(control2);
+                let target = self.evaluate(target);
+                if control1 == Value::int(1) && control2 == Value::int(1) {
+                    if target == Value::int(0) {
+                        Value::int(1)
                     } else {
-                        BigRational::from_integer(BigInt::from(0)).into()
+                        Value::int(0)
                     }
                 } else {
                     target
                 }
             }
             ASTNode::SWAP(qubit1_node, qubit2_node) => {
-                let qubit1 = self.evaluate(*qubit1_node);
-                let qubit2 = self.evaluate(*qubit2_node);
-                &qubit1 + &qubit2 - (&qubit1 * &qubit2 * BigRational::from_integer(BigInt::from(2)))
+                let qubit1 = self.evaluate(qubit1_node);
+                let qubit2 = self.evaluate(qubit2_node);
+                qubit1.clone() + qubit2.clone() - (qubit1 * qubit2 * Value::int(2))
             }
             ASTNode::Phase(qubit) => {
-                let qubit = self.evaluate(*qubit);
-                qubit * BigRational::from_integer(BigInt::from(-1))
+                let qubit = self.evaluate(qubit);
+                qubit * Value::int(-1)
             }
             ASTNode::SGate(qubit) => {
                 // S gate applies a phase shift of π/2 (multiplication by i)
-                let q = self.evaluate(*qubit);
-                q * Complex::new(BigRational::from_integer(<BigInt as num_traits::Zero>::zero()), BigRational::from_integer(<BigInt as num_traits::One>::one()))
+                let q = self.evaluate(qubit);
+                q * Value::Number(Complex::new(BigRational::from_integer(<BigInt as num_traits::Zero>::zero()), BigRational::from_integer(<BigInt as num_traits::One>::one())))
             }
             
             ASTNode::TGate(qubit) => {
                 // T gate applies a phase shift of π/4
-                let q = self.evaluate(*qubit);
+                let q = self.evaluate(qubit);
                 let one = BigRational::from_integer(<BigInt as num_traits::One>::one());
                 let sqrt_two = BigRational::from_float(2f64.sqrt()).unwrap();
                 let sqrt_two_over_two = &one / &sqrt_two;
                 let phase = Complex::new(sqrt_two_over_two.clone(), sqrt_two_over_two);
-                q * phase
+                q * Value::Number(phase)
             }
             ASTNode::Fredkin(control, target1, target2) => {
-                let control = self.evaluate(*control);
-                let target1 = self.evaluate(*target1);
-                let target2 = self.evaluate(*target2);
-                if control == BigRational::from_integer(BigInt::from(1)).into() {
+                let control = self.evaluate(control);
+                let target1 = self.evaluate(target1);
+                let target2 = self.evaluate(target2);
+                if control == Value::int(1) {
                     target2
                 } else {
                     target1
                 }
             }
             ASTNode::Call(name, args) => {
-                let function = self.functions.get(&name).expect("Undefined function").clone();
-                if let ASTNode::Function(_, params, body) = function {
-                    let mut variables = self.variables.clone();
-                    for (param, arg) in params.iter().zip(args.iter()) {
-                        let value = self.evaluate(arg.clone());
-                        variables.insert(param.clone(), value);
+                if !self.functions.contains_key(name) {
+                    if let Some(value) = self.call_native(name, args, span) {
+                        return value;
                     }
-                    let mut interpreter = Interpreter {
-                        variables,
-                        functions: self.functions.clone(),
-                    };
-                    interpreter.evaluate(*body)
-                } else {
-                    panic!("Expected function, got {:?}", function);
-                }
-            }
-            ASTNode::Import(module_name) => {
-                // Load and parse the module file
-                let module_content = std::fs::read_to_string(module_name).expect("Failed to read module file");
-                let lexer = crate::lexer::Lexer::new(module_content);
-                let mut parser = crate::parser::Parser::new(lexer);
-                let nodes = parser.parse();
-
-                // Execute the parsed nodes
-                let imported_interpreter = Arc::new(Mutex::new(Interpreter::new()));
-                let results: Vec<BigRational> = nodes.into_iter().map(|node| {
-                                                    Interpreter::execute(imported_interpreter.clone(), node.clone());
-                                                    imported_interpreter.lock().unwrap().evaluate(node).re
-                                                }).collect();
-                results.last().cloned().unwrap_or_else(|| BigRational::from_integer(BigInt::from(0))).into()
-            }
-            ASTNode::Pi => pi_constant().into(),
-            ASTNode::Kelvin => kelvin_constant().into(),
-            ASTNode::RD => rd_constant().into(),
-            ASTNode::CP => cp_constant().into(),
-            ASTNode::P0 => p0_constant().into(),
-            ASTNode::LV => lv_constant().into(),
-            ASTNode::CW => cw_constant().into(),
-            ASTNode::RhoAir => rho_air_constant().into(),
-            ASTNode::RhoWater => rho_water_constant().into(),
-            ASTNode::G => g_constant().into(),
+                }
+                self.call_function(name, args, span, |callee, body| callee.evaluate(body))
+            }
+            ASTNode::Import(module_name, names) => {
+                self.import(module_name, names.as_deref(), span);
+                Value::Nil
+            }
+            ASTNode::StringLiteral(value) => Value::Str(value.clone()),
+            ASTNode::Args => Value::List(self.args.as_ref().clone()),
+            ASTNode::Len(value) => match self.evaluate(value) {
+                Value::List(values) => Value::int(values.len() as i64),
+                Value::Map(entries) => Value::int(entries.len() as i64),
+                Value::Str(text) => Value::int(text.chars().count() as i64),
+                other => panic!("Cannot take the length of a {} on line {}, column {}.", other.type_name(), span.start.line, span.start.column),
+            },
+            ASTNode::Env(name) => match self.evaluate(name) {
+                Value::Str(name) => std::env::var(name).map_or(Value::Nil, Value::Str),
+                other => panic!("Environment variable names must be strings, found a {} on line {}, column {}.", other.type_name(), span.start.line, span.start.column),
+            },
+            ASTNode::ParseJson(text) => match self.evaluate(text) {
+                Value::Str(text) => parse_json(&text, span),
+                other => panic!("parse_json expects a string, found a {} on line {}, column {}.", other.type_name(), span.start.line, span.start.column),
+            },
+            ASTNode::ToJson(value) => match self.evaluate(value).to_json() {
+                Ok(json) => Value::Str(json.to_string()),
+                Err(error) => panic!("{} on line {}, column {}.", error, span.start.line, span.start.column),
+            },
+            ASTNode::Fetch(url) => {
+                let url = match self.evaluate(url) {
+                    Value::Str(url) => url,
+                    other => panic!("fetch expects a URL, found a {} on line {}, column {}.", other.type_name(), span.start.line, span.start.column),
+                };
+                self.download(&url, "fetch", span)
+            }
+            ASTNode::Forecast(latitude, longitude) => {
+                let latitude = self.input(latitude, Quantity::Number, node).to_f64();
+                let longitude = self.input(longitude, Quantity::Number, node).to_f64();
+                if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+                    panic!("forecast expects a latitude from -90 to 90 and a longitude from -180 to 180, found {} and {} on line {}, column {}.", latitude, longitude, span.start.line, span.start.column);
+                }
+                self.download(&remote::forecast_url(latitude, longitude), "forecast", span)
+            }
+            ASTNode::ReadJson(path) => {
+                let path = self.path(path, "readjson", span);
+                let text = std::fs::read_to_string(&path)
+                    .unwrap_or_else(|error| panic!("Failed to read {}: {} on line {}, column {}.", path, error, span.start.line, span.start.column));
+                parse_json(&text, span)
+            }
+            // A map of the field's rows of values, with nil where missing, and
+            // on latitude/longitude grids the latitude of each row and the
+            // longitude of each column
+            #[cfg(feature = "grib")]
+            ASTNode::ReadGrib(path, parameter, level) => {
+                let path = self.path(path, "readgrib", span);
+                let parameter = match self.evaluate(parameter) {
+                    Value::Str(parameter) => parameter,
+                    other => panic!("readgrib expects a parameter name such as TMP, found a {} on line {}, column {}.", other.type_name(), span.start.line, span.start.column),
+                };
+                // A bare number is a pressure level in hPa
+                let level = match self.evaluate(level) {
+                    Value::Str(level) => level,
+                    other if other.is_number() => format!("{} mb", other.to_f64()),
+                    other => panic!("readgrib expects a level such as 500 mb, found a {} on line {}, column {}.", other.type_name(), span.start.line, span.start.column),
+                };
+                let data = std::fs::read(&path)
+                    .unwrap_or_else(|error| panic!("Failed to read {}: {} on line {}, column {}.", path, error, span.start.line, span.start.column));
+                let field = crate::grib::read(&data, &parameter, &level)
+                    .unwrap_or_else(|error| panic!("{} in {} on line {}, column {}.", error, path, span.start.line, span.start.column));
+                let numbers = |numbers: Vec<f64>| Value::List(numbers.into_iter().map(|number| if number.is_nan() { Value::Nil } else { Value::Float(number) }).collect());
+                let mut entries = vec![("values", Value::List(field.rows.into_iter().map(numbers).collect()))];
+                if let (Some(latitudes), Some(longitudes)) = (field.latitudes, field.longitudes) {
+                    entries.push(("lat", numbers(latitudes)));
+                    entries.push(("lon", numbers(longitudes)));
+                }
+                Value::Map(entries.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+            }
+            #[cfg(not(feature = "grib"))]
+            ASTNode::ReadGrib(..) => {
+                panic!("readgrib needs qprime built with the grib feature (cargo install qprime --features grib) on line {}, column {}.", span.start.line, span.start.column)
+            }
+            #[cfg(feature = "netcdf")]
+            ASTNode::ReadNc(path, variable) => {
+                let path = self.path(path, "readnc", span);
+                let variable = match self.evaluate(variable) {
+                    Value::Str(variable) => variable,
+                    other => panic!("readnc expects a variable name, found a {} on line {}, column {}.", other.type_name(), span.start.line, span.start.column),
+                };
+                let data = std::fs::read(&path)
+                    .unwrap_or_else(|error| panic!("Failed to read {}: {} on line {}, column {}.", path, error, span.start.line, span.start.column));
+                crate::netcdf::read(&data, &variable).unwrap_or_else(|error| panic!("{} in {} on line {}, column {}.", error, path, span.start.line, span.start.column))
+            }
+            #[cfg(not(feature = "netcdf"))]
+            ASTNode::ReadNc(..) => {
+                panic!("readnc needs qprime built with the netcdf feature (cargo install qprime --features netcdf) on line {}, column {}.", span.start.line, span.start.column)
+            }
+            ASTNode::Secret(name) => match self.evaluate(name) {
+                Value::Str(name) => match self.lookup_secret(&name) {
+                    Some(secret) => Value::Secret(Secret::new(secret)),
+                    None => panic!(
+                        "Secret '{}' is not set: define {} in the environment, add it under [secrets] in {} or store it in the system keyring for service '{}' on line {}, column {}.",
+                        name, name.to_uppercase(), CONFIG_FILE, KEYRING_SERVICE, span.start.line, span.start.column
+                    ),
+                },
+                other => panic!("Secret names must be strings, found a {} on line {}, column {}.", other.type_name(), span.start.line, span.start.column),
+            },
+            ASTNode::Format(value, digits) => {
+                let value = self.evaluate(value);
+                let digits = self.evaluate(digits);
+                if !value.is_number() {
+                    panic!("Cannot format a {} on line {}, column {}.", value.type_name(), span.start.line, span.start.column);
+                }
+                match digits.to_usize() {
+                    Some(digits @ 1..=MAX_PRECISION) => Value::Str(value.rounded(digits)),
+                    _ => panic!("Expected between 1 and {} significant digits, found {} on line {}, column {}.", MAX_PRECISION, digits, span.start.line, span.start.column),
+                }
+            }
+            ASTNode::Pmap(function, list) => {
+                let values = match self.evaluate(list) {
+                    Value::List(values) => values,
+                    other => panic!("pmap expects a list, found a {} on line {}, column {}.", other.type_name(), span.start.line, span.start.column),
+                };
+                let call = pmap_call(function, span);
+                // One worker per thread, each taking a contiguous run of items
+                // so the results come back in order
+                let chunk_size = values.len().div_ceil(rayon::current_num_threads()).max(1);
+                let workers: Vec<Interpreter> = values.chunks(chunk_size).map(|_| self.worker()).collect();
+                let results: Vec<Vec<Value>> = workers
+                    .into_par_iter()
+                    .zip(values.par_chunks(chunk_size))
+                    .map(|(mut worker, items)| {
+                        items.iter().map(|item| {
+                            worker.scope.variables.insert(PMAP_ITEM.to_string(), item.clone());
+                            worker.evaluate(&call)
+                        }).collect()
+                    })
+                    .collect();
+                Value::List(results.concat())
+            }
+            ASTNode::Index(list, index) => {
+                let list = self.evaluate(list);
+                let index = self.evaluate(index);
+                match (list, index) {
+                    (Value::List(values), index) => index.to_usize()
+                        .and_then(|position| values.get(position).cloned())
+                        .unwrap_or_else(|| panic!("Index {} is out of range for a list of length {} on line {}, column {}.", index, values.len(), span.start.line, span.start.column)),
+                    (Value::Map(entries), Value::Str(key)) => entries.get(&key)
+                        .cloned()
+                        .unwrap_or_else(|| panic!("No key '{}' in the map on line {}, column {}.", key, span.start.line, span.start.column)),
+                    (Value::Map(_), index) => panic!("Maps are indexed by strings, found a {} on line {}, column {}.", index.type_name(), span.start.line, span.start.column),
+                    (list, _) => panic!("Cannot index a {} on line {}, column {}.", list.type_name(), span.start.line, span.start.column),
+                }
+            }
+            ASTNode::IsNaN(value) => {
+                let value = self.evaluate(value);
+                Value::bool(value.is_nan())
+            }
+            ASTNode::Pi => Value::number(pi_constant(), self.numeric),
+            ASTNode::Kelvin => Value::number(kelvin_constant(), self.numeric),
+            ASTNode::RD => self.constant("rd"),
+            ASTNode::CP => self.constant("cp"),
+            ASTNode::P0 => self.constant("p0"),
+            ASTNode::LV => self.constant("lv"),
+            ASTNode::CW => self.constant("cw"),
+            ASTNode::RhoAir => self.constant("rho_air"),
+            ASTNode::RhoWater => self.constant("rho_water"),
+            ASTNode::G => self.constant("g"),
+            ASTNode::Omega => self.constant("omega"),
+            ASTNode::Rv => self.constant("rv"),
+            // Follows changes to either gas constant
+            ASTNode::Epsilon => self.constant("rd") / self.constant("rv"),
+            ASTNode::Sigma => self.constant("sigma"),
+            ASTNode::Solar => self.constant("solar"),
+            ASTNode::Lf => self.constant("lf"),
             ASTNode::GreaterThan(left, right) => {
-                let left_val = self.evaluate(*left);
-                let right_val = self.evaluate(*right);
-                if left_val.re > right_val.re { BigRational::from_integer(BigInt::from(1)).into() } else { BigRational::from_integer(BigInt::from(0)).into() }
+                let left_val = self.evaluate(left);
+                let right_val = self.evaluate(right);
+                Value::bool(left_val > right_val)
             }
             ASTNode::LessThan(left, right) => {
-                let left_val = self.evaluate(*left);
-                let right_val = self.evaluate(*right);
-                if left_val.re < right_val.re { BigRational::from_integer(BigInt::from(1)).into() } else { BigRational::from_integer(BigInt::from(0)).into() }
+                let left_val = self.evaluate(left);
+                let right_val = self.evaluate(right);
+                Value::bool(left_val < right_val)
             }
-            _ => panic!("Unexpected AST node: {:?}", node),
+            other => panic!("Unexpected AST node {:?} on line {}, column {}.", other, span.start.line, span.start.column),
         }
     }
 
-    pub fn interpret(&mut self, nodes: Vec<ASTNode>) {
-        let interpreter = Arc::new(Mutex::new(Interpreter::new()));
-        nodes.into_iter().for_each(|node| {
-            Interpreter::execute(interpreter.clone(), node);
-        });
+    // Runs the statements against this interpreter's own state. If a
+    // statement fails, the state it reached is kept as the panic unwinds,
+    // so the REPL can carry on after an error.
+    pub fn interpret(&mut self, nodes: Vec<Node>) {
+        for node in &nodes {
+            self.execute(node);
+        }
     }
 }
\ No newline at end of file
@@ -1,11 +1,157 @@
+use crate::span::{Position, Span, Spanned};
 use crate::token::Token;
+use crate::units;
 use num_bigint::BigInt;
 use num_rational::BigRational;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+// Keywords, builtin functions and builtin constants
+pub const KEYWORDS: &[(&str, Token)] = &[
+    ("call", Token::Call),
+    ("print", Token::Print),
+    ("if", Token::If),
+    ("else", Token::Else),
+    ("and", Token::And),
+    ("or", Token::Or),
+    ("not", Token::Not),
+    ("dewpoint", Token::DewPoint),
+    ("apparent_temp", Token::ApparentTemp),
+    ("svp", Token::Svp),
+    ("mixing_ratio", Token::MixingRatio),
+    ("specific_humidity", Token::SpecificHumidity),
+    ("pressure_at_altitude", Token::PressureAtAltitude),
+    ("isa", Token::Isa),
+    ("dalr", Token::Dalr),
+    ("malr", Token::Malr),
+    ("temp_along_dry_adiabat", Token::DryAdiabat),
+    ("et0", Token::Et0),
+    ("hdd", Token::Hdd),
+    ("cdd", Token::Cdd),
+    ("wind_components", Token::WindComponents),
+    ("wind_from_components", Token::WindFromComponents),
+    ("mmtoin", Token::MmToIn),
+    ("intomm", Token::InToMm),
+    ("mmhtoinh", Token::MmhToInh),
+    ("inhtommh", Token::InhToMmh),
+    ("accumulation", Token::Accumulation),
+    ("running_total", Token::RunningTotal),
+    ("percentile", Token::Percentile),
+    ("percentile_rank", Token::PercentileRank),
+    ("anomaly", Token::Anomaly),
+    ("rolling_mean", Token::RollingMean),
+    ("spi", Token::Spi),
+    ("windrose", Token::Windrose),
+    ("slr", Token::Slr),
+    ("snowfall", Token::Snowfall),
+    ("visconvert", Token::VisConvert),
+    ("fog_category", Token::FogCategory),
+    ("solar_zenith", Token::SolarZenith),
+    ("solar_declination", Token::SolarDeclination),
+    ("uv_index", Token::UvIndex),
+    ("coriolis", Token::Coriolis),
+    ("precipitable_water", Token::PrecipitableWater),
+    ("speed_of_sound", Token::SpeedOfSound),
+    ("altimeter", Token::Altimeter),
+    ("rh_from_wetbulb", Token::RhFromWetbulb),
+    ("haversine", Token::Haversine),
+    ("parse_coord", Token::ParseCoord),
+    ("format_coord", Token::FormatCoord),
+    ("cloudbase", Token::Cloudbase),
+    ("ftoc", Token::FToC),
+    ("ctof", Token::CToF),
+    ("ctok", Token::CToK),
+    ("ktoc", Token::KToC),
+    ("ftok", Token::FToK),
+    ("ktof", Token::KToF),
+    ("isnan", Token::IsNaN),
+    ("assert", Token::Assert),
+    ("set_constant", Token::SetConstant),
+    ("assert_approx", Token::AssertApprox),
+    ("len", Token::Len),
+    ("secret", Token::Secret),
+    ("parse_json", Token::ParseJson),
+    ("to_json", Token::ToJson),
+    ("readjson", Token::ReadJson),
+    ("readgrib", Token::ReadGrib),
+    ("readnc", Token::ReadNc),
+    ("to_timezone", Token::ToTimezone),
+    ("utc_offset", Token::UtcOffset),
+    ("randint", Token::RandInt),
+    ("randn", Token::RandN),
+    ("sleep", Token::Sleep),
+    ("writejson", Token::WriteJson),
+    ("fetch", Token::Fetch),
+    ("forecast", Token::Forecast),
+    ("pmap", Token::Pmap),
+    ("pauli_x", Token::PauliX),
+    ("pauli_y", Token::PauliY),
+    ("pauli_z", Token::PauliZ),
+    ("hadamard", Token::Hadamard),
+    ("cnot", Token::CNot),
+    ("qubit", Token::Qubit),
+    ("toffoli", Token::Toffoli),
+    ("phase", Token::Phase),
+    ("t_gate", Token::TGate),
+    ("s_gate", Token::SGate),
+    ("fredkin", Token::Fredkin),
+    ("swap_qubits", Token::SWAP),
+    ("reset_qubit", Token::ResetQubit),
+    ("measure", Token::MeasureQubit),
+    ("fn", Token::Function),
+    ("import", Token::Import),
+    ("export", Token::Export),
+    ("nan", Token::NaN),
+    ("inf", Token::Inf),
+    ("nil", Token::Nil),
+    ("_pi_", Token::Pi),
+    ("_kelvin_", Token::Kelvin),
+    ("_rd_", Token::RD),
+    ("_cp_", Token::CP),
+    ("_p0_", Token::P0),
+    ("_lv_", Token::LV),
+    ("_cw_", Token::CW),
+    ("_rho_air_", Token::RhoAir),
+    ("_rho_water_", Token::RhoWater),
+    ("_g_", Token::G),
+    ("_omega_", Token::Omega),
+    ("_rv_", Token::Rv),
+    ("_epsilon_", Token::Epsilon),
+    ("_sigma_", Token::Sigma),
+    ("_solar_", Token::Solar),
+    ("_lf_", Token::Lf),
+];
+
+pub fn keyword(identifier: &str) -> Option<Token> {
+    KEYWORDS.iter().find(|(name, _)| *name == identifier).map(|(_, token)| token.clone())
+}
+
+// Builtin functions with names that are common words. They are only
+// builtins when called, as in `station("KJFK")`, which the parser sees, so
+// the names still work as variables and parameters.
+pub const CALL_NAMES: &[(&str, Token)] = &[
+    ("station", Token::Station),
+    ("format", Token::Format),
+    ("events", Token::Events),
+    ("bearing", Token::Bearing),
+    ("convert", Token::Convert),
+    ("rand", Token::Rand),
+    ("seed", Token::Seed),
+    ("args", Token::Args),
+    ("env", Token::Env),
+];
+
+pub fn call_name(identifier: &str) -> Option<Token> {
+    CALL_NAMES.iter().find(|(name, _)| *name == identifier).map(|(_, token)| token.clone())
+}
 
 pub struct Lexer {
     input: Vec<char>,
     position: usize,
     pub line: usize,
+    pub column: usize,
+    // `#` comments seen so far (text after the `#`), kept for the formatter
+    pub comments: Vec<Spanned<String>>,
 }
 
 impl Lexer {
@@ -14,24 +160,59 @@ impl Lexer {
             input: input.chars().collect(),
             position: 0,
             line: 1,
+            column: 1,
+            comments: Vec::new(),
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        Position::new(self.line, self.column)
+    }
+
+    // Consume one character, keeping line and column in sync. Columns count
+    // characters as they are seen, so combining marks (the accent of a
+    // decomposed `é`) share the column of the letter before them.
+    fn advance(&mut self) -> char {
+        let ch = self.input[self.position];
+        self.position += 1;
+        if ch == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else if !is_combining_mark(ch) {
+            self.column += 1;
+        }
+        ch
+    }
+
+    // Consumes the next character if it is `expected`
+    fn match_char(&mut self, expected: char) -> bool {
+        if self.position < self.input.len() && self.input[self.position] == expected {
+            self.advance();
+            true
+        } else {
+            false
         }
     }
 
-    pub fn next_token(&mut self) -> Token {
+    pub fn next_token(&mut self) -> Spanned<Token> {
         self.skip_whitespace();
+        let start = self.position();
+        let token = self.read_token();
+        Spanned::new(token, Span::new(start, self.position()))
+    }
+
+    fn read_token(&mut self) -> Token {
         if self.position >= self.input.len() {
             return Token::EOF;
         }
 
-        let ch = self.input[self.position];
-        self.position += 1;
+        let ch = self.advance();
 
         match ch {
             '+' => Token::Plus,
             '-' => Token::Minus,
             '*' => {
-                if self.position < self.input.len() && self.input[self.position] == '*' {
-                    self.position += 1;
+                if self.match_char('*') {
                     Token::StarStar
                 } else {
                     Token::Star
@@ -39,37 +220,40 @@ impl Lexer {
             }
             '/' => Token::Slash,
             '%' => Token::Modulo,
-            '>' => Token::GreaterThan,
-            '<' => Token::LessThan,
-            '=' => Token::Assign,
+            '>' => if self.match_char('=') { Token::GreaterEqual } else { Token::GreaterThan },
+            '<' => if self.match_char('=') { Token::LessEqual } else { Token::LessThan },
+            '=' => if self.match_char('=') { Token::EqualEqual } else { Token::Assign },
+            '!' if self.match_char('=') => Token::NotEqual,
             '{' => Token::LBrace,
             '}' => Token::RBrace,
             '(' => Token::LParen,
             ')' => Token::RParen,
+            '[' => Token::LBracket,
+            ']' => Token::RBracket,
             '"' => self.read_string_literal(),
             '0'..='9' | '.' => self.read_number(ch),
-            'a'..='z' | 'A'..='Z' | '_' => self.read_identifier(ch),
             ',' => Token::Comma,
-            '#' => {
-                while self.position < self.input.len() && self.input[self.position] != '\n' {
-                    self.position += 1;
-                }
-                self.next_token()
-            }
-            '\n' => {
-                self.line += 1;
-                self.next_token()
-            }
-            _ => panic!("Unexpected character '{}' on line {}.", ch, self.line),
+            _ if ch == '_' || unicode_ident::is_xid_start(ch) => self.read_identifier(ch),
+            _ => panic!("Unexpected character '{}' on line {}, column {}.", ch, self.line, self.column - 1),
         }
     }
 
+    // Skips whitespace and `#` comments
     pub fn skip_whitespace(&mut self) {
-        while self.position < self.input.len() && self.input[self.position].is_whitespace() {
-            if self.input[self.position] == '\n' {
-                self.line += 1;
+        while self.position < self.input.len() {
+            if self.input[self.position].is_whitespace() {
+                self.advance();
+            } else if self.input[self.position] == '#' {
+                let start = self.position();
+                self.advance();
+                let mut text = String::new();
+                while self.position < self.input.len() && self.input[self.position] != '\n' {
+                    text.push(self.advance());
+                }
+                self.comments.push(Spanned::new(text, Span::new(start, self.position())));
+            } else {
+                break;
             }
-            self.position += 1;
         }
     }
 
@@ -77,76 +261,76 @@ impl Lexer {
         let mut number = first_char.to_string();
         let mut is_float = false;
 
-        while self.position < self.input.len() && (self.input[self.position].is_digit(10) || self.input[self.position] == '.') {
+        while self.position < self.input.len() && (self.input[self.position].is_ascii_digit() || self.input[self.position] == '.') {
             if self.input[self.position] == '.' {
                 is_float = true;
             }
-            number.push(self.input[self.position]);
-            self.position += 1;
+            number.push(self.advance());
         }
 
-        if is_float {
-            Token::Float(BigRational::from_float(number.parse::<f64>().unwrap()).unwrap())
+        let value = if is_float {
+            BigRational::from_float(number.parse::<f64>().unwrap()).unwrap()
         } else {
-            Token::Float(BigRational::from_integer(number.parse::<BigInt>().unwrap()))
+            BigRational::from_integer(number.parse::<BigInt>().unwrap())
+        };
+        match self.read_unit() {
+            Some(unit) => Token::Quantity(value, unit),
+            None => Token::Float(value),
+        }
+    }
+
+    // Unit written right after a number, as in `25C` or `10m/s`: the longest
+    // unit name there that isn't followed by more of an identifier, so
+    // `10min` is not 10 miles
+    fn read_unit(&mut self) -> Option<String> {
+        let rest = &self.input[self.position..];
+        let name = units::UNITS
+            .iter()
+            .flat_map(|unit| unit.names.iter())
+            .map(|name| name.chars().collect::<Vec<char>>())
+            .filter(|name| rest.starts_with(name) && rest.get(name.len()).is_none_or(|next| !unicode_ident::is_xid_continue(*next)))
+            .max_by_key(|name| name.len())?;
+        for _ in 0..name.len() {
+            self.advance();
         }
+        Some(name.into_iter().collect())
     }
 
+    // Identifiers follow Unicode's identifier syntax (as in Rust and Python),
+    // so `température` or `µg` are valid names. They are NFC-normalized, so
+    // the same name typed with a precomposed or decomposed accent matches.
     pub fn read_identifier(&mut self, first_char: char) -> Token {
         let mut identifier = first_char.to_string();
-        while self.position < self.input.len() && (self.input[self.position].is_alphanumeric() || self.input[self.position] == '_') {
-            identifier.push(self.input[self.position]);
-            self.position += 1;
-        }
-        match identifier.as_str() {
-            "call" => Token::Call,
-            "print" => Token::Print,
-            "if" => Token::If,
-            "else" => Token::Else,
-            "dewpoint" => Token::DewPoint,
-            "ftoc" => Token::FToC,
-            "ctof" => Token::CToF,
-            "ctok" => Token::CToK,
-            "ktoc" => Token::KToC,
-            "ftok" => Token::FToK,
-            "ktof" => Token::KToF,
-            "pauli_x" => Token::PauliX,
-            "pauli_y" => Token::PauliY,
-            "pauli_z" => Token::PauliZ,
-            "hadamard" => Token::Hadamard,
-            "cnot" => Token::CNot,
-            "qubit" => Token::Qubit,
-            "toffoli" => Token::Toffoli,
-            "phase" => Token::Phase,
-            "t_gate" => Token::TGate,
-            "s_gate" => Token::SGate,
-            "fredkin" => Token::Fredkin,
-            "swap_qubits" => Token::SWAP,
-            "reset_qubit" => Token::ResetQubit,
-            "measure" => Token::MeasureQubit,
-            "fn" => Token::Function,
-            "import" => Token::Import,
-            "_pi_" => Token::Pi,
-            "_kelvin_" => Token::Kelvin,
-            "_rd_" => Token::RD,
-            "_cp_" => Token::CP,
-            "_p0_" => Token::P0,
-            "_lv_" => Token::LV,
-            "_cw_" => Token::CW,
-            "_rho_air_" => Token::RhoAir,
-            "_rho_water_" => Token::RhoWater,
-            "_g_" => Token::G,
-            _ => Token::Identifier(identifier),
+        while self.position < self.input.len() && unicode_ident::is_xid_continue(self.input[self.position]) {
+            identifier.push(self.advance());
         }
+        let identifier: String = identifier.nfc().collect();
+        keyword(&identifier).unwrap_or(Token::Identifier(identifier))
     }
 
+    // Text up to the closing quote, where `\"`, `\\`, `\n` and `\t` stand for
+    // a quote, a backslash, a newline and a tab
     pub fn read_string_literal(&mut self) -> Token {
+        let start_line = self.line;
         let mut string = String::new();
         while self.position < self.input.len() && self.input[self.position] != '"' {
-            string.push(self.input[self.position]);
-            self.position += 1;
+            let ch = self.advance();
+            if ch != '\\' || self.position >= self.input.len() {
+                string.push(ch);
+                continue;
+            }
+            string.push(match self.advance() {
+                '"' => '"',
+                '\\' => '\\',
+                'n' => '\n',
+                't' => '\t',
+                other => panic!("Unknown escape '\\{}' in string on line {}, column {}.", other, self.line, self.column - 2),
+            });
+        }
+        if self.position >= self.input.len() {
+            panic!("Unterminated string starting at line {}.", start_line);
         }
-        self.position += 1; // Consume closing quote
+        self.advance(); // Consume closing quote
         Token::StringLiteral(string)
     }
 }
\ No newline at end of file
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/* ==== + ==== */
+pub mod token;     //
+pub mod ast;       //
+pub mod constants; //
+pub mod formulas;  //
+pub mod units;     //
+pub mod coords;    //
+pub mod stations;  //
+pub mod timezone;  //
+pub mod configs;   //
+pub mod value;     //
+pub mod span;      //
+pub mod dump;      //
+pub mod strict;    //
+pub mod lint;      //
+pub mod format;    //
+pub mod debugger;  //
+pub mod trace;     //
+pub mod profile;   //
+pub mod testing;   //
+pub mod bench;     //
+pub mod doc;       //
+pub mod incremental; //
+pub mod stack;     //
+#[cfg(not(target_arch = "wasm32"))]
+pub mod repl;      //
+pub mod error;     //
+pub mod serialize; //
+pub mod optimize;  //
+pub mod bundle;    //
+pub mod remote;    //
+pub mod stdlib;    //
+pub mod transpile; //
+pub mod project;   //
+#[cfg(not(target_arch = "wasm32"))]
+pub mod package;   //
+#[cfg(not(target_arch = "wasm32"))]
+pub mod plugin;    //
+#[cfg(feature = "wasm")]
+pub mod wasm;      //
+#[cfg(feature = "cdylib")]
+pub mod ffi;       //
+#[cfg(feature = "grib")]
+pub mod grib;      //
+#[cfg(feature = "netcdf")]
+pub mod netcdf;    //
+/* ==== + ==== */
+
+pub mod lexer;
+pub use lexer::Lexer;
+
+pub mod parser;
+pub use parser::Parser;
+
+pub mod interpreter;
+pub use interpreter::{Interpreter, Limits};
+
+pub use serialize::Snapshot;
+
+pub use value::{Numeric, Value};
+
+pub use error::{ErrorKind, WeatherError};
+
+// What a script leaves behind once it has run
+pub struct Output {
+    pub variables: HashMap<String, Value>,
+    // Everything the script printed, one line per `print`
+    pub printed: String,
+}
+
+// Lexes, parses and runs a script in a fresh interpreter, collecting its
+// printed output instead of writing it to stdout. Script errors are
+// raised as panics and returned here as a `WeatherError`; the panic hook
+// still runs, so embedders that don't want them on stderr should install
+// their own with `std::panic::set_hook`.
+pub fn run_source(source: &str) -> Result<Output, WeatherError> {
+    let nodes = error::catch(ErrorKind::Parse, || Parser::new(Lexer::new(source.to_string())).parse())?;
+    let printed = Arc::new(Mutex::new(String::new()));
+    let mut interpreter = Interpreter::new();
+    let sink = printed.clone();
+    interpreter.set_output(move |line| {
+        let mut printed = sink.lock().unwrap();
+        printed.push_str(line);
+        printed.push('\n');
+    });
+    error::catch(ErrorKind::Runtime, || interpreter.interpret(nodes))?;
+    let printed = printed.lock().unwrap().clone();
+    Ok(Output { variables: interpreter.variables(), printed })
+}
@@ -0,0 +1,94 @@
+use crate::ast::{ASTNode, Node};
+use crate::span::Span;
+use crate::stack;
+use crate::strict::shadowed_builtin;
+use std::collections::HashSet;
+
+pub struct Warning {
+    pub span: Span,
+    pub message: String,
+}
+
+// Warns about unused variables and parameters and names shadowing builtins.
+// Functions see their caller's variables, so a variable counts as used if it
+// is read anywhere in the script.
+pub fn lint(nodes: &[Node]) -> Vec<Warning> {
+    let mut reads = HashSet::new();
+    collect_reads(nodes, &mut reads);
+    let mut linter = Linter {
+        reads,
+        reported: HashSet::new(),
+        warnings: Vec::new(),
+    };
+    for node in nodes {
+        linter.lint_node(node);
+    }
+    linter.warnings.sort_by_key(|warning| warning.span.start);
+    linter.warnings
+}
+
+struct Linter {
+    reads: HashSet<String>,
+    // Variables already reported as unused, so reassignments aren't repeated
+    reported: HashSet<String>,
+    warnings: Vec<Warning>,
+}
+
+impl Linter {
+    fn warn(&mut self, span: Span, message: String) {
+        self.warnings.push(Warning { span, message });
+    }
+
+    fn check_shadowing(&mut self, name: &str, span: Span) {
+        if let Some(builtin) = shadowed_builtin(name) {
+            self.warn(span, format!("'{}' shadows the builtin '{}'", name, builtin));
+        }
+    }
+
+    fn lint_node(&mut self, node: &Node) {
+        stack::grow(|| {
+            match &node.node {
+                ASTNode::Assignment(name, value) => {
+                    if !self.reads.contains(name) && self.reported.insert(name.clone()) {
+                        self.warn(node.span, format!("variable '{}' is assigned but never used", name));
+                    }
+                    self.check_shadowing(name, node.span);
+                    self.lint_node(value);
+                }
+                ASTNode::Function(name, params, body) => {
+                    self.check_shadowing(name, node.span);
+                    let mut body_reads = HashSet::new();
+                    collect_reads([body.as_ref()], &mut body_reads);
+                    for param in params {
+                        if !body_reads.contains(param) {
+                            self.warn(node.span, format!("parameter '{}' of function '{}' is never used", param, name));
+                        }
+                        self.check_shadowing(param, node.span);
+                    }
+                    self.lint_node(body);
+                }
+                // Exported variables are for importers to read
+                ASTNode::Export(inner) => {
+                    if let ASTNode::Assignment(name, _) = &inner.node {
+                        self.reads.insert(name.clone());
+                    }
+                    self.lint_node(inner);
+                }
+                other => {
+                    for child in other.children() {
+                        self.lint_node(child);
+                    }
+                }
+            }
+        })
+    }
+}
+
+fn collect_reads<'a>(nodes: impl IntoIterator<Item = &'a Node>, reads: &mut HashSet<String>) {
+    for node in nodes {
+        if let ASTNode::Identifier(name) = &node.node {
+            reads.insert(name.clone());
+        }
+        collect_reads(node.node.children(), reads);
+    }
+}
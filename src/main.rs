@@ -1,33 +1,255 @@
 use std::env;
 use std::fs;
+use std::path::Path;
+use std::panic;
+use std::process;
+use std::time::Duration;
 
-/* ==== + ==== */
-mod token;     //
-mod ast;       //
-mod constants; //
-mod configs;   //
-/* ==== + ==== */
+mod cli;
 
-mod lexer;
-use lexer::Lexer;
+use qprime::{ast, bench, bundle, debugger, doc, dump, error, format, lint, optimize, package, plugin, profile, project, repl, serialize, strict, testing, trace, transpile};
+use qprime::{ErrorKind, Interpreter, Lexer, Limits, Parser, Value, WeatherError};
 
-mod parser;
-use parser::Parser;
+use cli::{Command, RunArgs, Target};
 
-mod interpreter;
-use interpreter::Interpreter;
+fn read_script(path: &Path) -> String {
+    fs::read_to_string(path)
+        .unwrap_or_else(|error| WeatherError::new(ErrorKind::Runtime, format!("Failed to read '{}': {}", path.display(), error)).exit())
+}
 
+fn parse_script(script: String) -> (Vec<ast::Node>, Parser) {
+    error::catch(ErrorKind::Parse, || {
+        let mut parser = Parser::new(Lexer::new(script));
+        (parser.parse(), parser)
+    })
+    .unwrap_or_else(|error| error.exit())
+}
+
+// Lexes and parses each script without running it, printing one line per
+// problem. Returns whether every script is clean.
+fn check_files(files: &[String], strict: bool) -> bool {
+    let mut clean = true;
+    for path in files {
+        let script = read_script(Path::new(path));
+        match error::catch(ErrorKind::Parse, || Parser::new(Lexer::new(script)).parse()) {
+            Ok(nodes) if strict => {
+                for error in strict::check(&nodes) {
+                    eprintln!("{}: {}", path, error);
+                    clean = false;
+                }
+            }
+            Ok(_) => {}
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                clean = false;
+            }
+        }
+    }
+    clean
+}
+
+// Exit status: 0 on success, 1 for runtime errors, 2 when the script is
+// rejected before running (syntax or strict mode errors) and 64 for bad usage.
+// Errors are raised as panics and reported here as one line on stderr.
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: {} <script.{}>", args[0], configs::FILE_EXTENSION);
+    panic::set_hook(Box::new(|_| {}));
+    error::catch(ErrorKind::Runtime, run).unwrap_or_else(|error| error.exit());
+}
+
+fn run() {
+    // An executable made by `qprime build` runs its script, not the CLI
+    if let Some(bundle) = bundle::embedded() {
+        run_bundle(bundle);
         return;
     }
+    let cli = cli::parse();
+    // Only commands that use `weather.toml` read it, so a broken one in a
+    // parent directory doesn't get in the way of the others
+    match cli.command {
+        // Without a subcommand, arguments or options, start the REPL
+        None if cli.run == RunArgs::default() => repl::run(),
+        None => run_script(cli.run, false),
+        Some(Command::Run(args)) => run_script(args, false),
+        Some(Command::Debug(args)) => run_script(args, true),
+        Some(Command::Repl) => repl::run(),
+        Some(Command::Lint { script }) => {
+            let (nodes, _) = parse_script(read_script(&script));
+            let warnings = lint::lint(&nodes);
+            for warning in &warnings {
+                println!("{}:{}: warning: {}", script.display(), warning.span.start, warning.message);
+            }
+            if !warnings.is_empty() {
+                process::exit(1);
+            }
+        }
+        Some(Command::Fmt { check, script: path }) => {
+            let script = read_script(&path);
+            let (nodes, parser) = parse_script(script.clone());
+            let formatted = format::format_program(&nodes, parser.comments());
+            if check {
+                if formatted != script {
+                    eprintln!("{}: would be reformatted", path.display());
+                    process::exit(1);
+                }
+            } else if formatted != script {
+                fs::write(&path, formatted).expect("Failed to write script");
+            }
+        }
+        Some(Command::Doc { markdown, script }) => {
+            let (nodes, parser) = parse_script(read_script(&script));
+            let docs = doc::collect(&nodes, parser.comments());
+            let title = script.display().to_string();
+            if markdown {
+                print!("{}", doc::markdown(&title, &docs));
+            } else {
+                print!("{}", doc::terminal(&title, &docs));
+            }
+        }
+        Some(Command::Test { directory }) => {
+            let tests = testing::discover(&directory);
+            if testing::run(&tests) > 0 {
+                process::exit(1);
+            }
+        }
+        Some(Command::Build { script, include, output }) => {
+            let config = project::Config::discover();
+            let search_paths = project::search_paths(&[include, config.include].concat());
+            let bundle = error::catch(ErrorKind::Parse, || bundle::Bundle::collect(&script, &search_paths)).unwrap_or_else(|error| error.exit());
+            let output = output.unwrap_or_else(|| {
+                let stem = script.file_stem().unwrap_or(script.as_os_str());
+                Path::new(stem).with_extension(env::consts::EXE_EXTENSION)
+            });
+            bundle::build(&bundle, &output);
+            println!("{}: {} module(s) bundled into {}", script.display(), bundle.modules.len(), output.display());
+        }
+        Some(Command::Transpile { target, script, include, output }) => {
+            let (nodes, _) = parse_script(read_script(&script));
+            let config = project::Config::discover();
+            let search_paths = project::search_paths(&[include, config.include].concat());
+            let bundle = error::catch(ErrorKind::Parse, || bundle::Bundle::collect(&script, &search_paths)).unwrap_or_else(|error| error.exit());
+            let code = match target {
+                Target::Python => error::catch(ErrorKind::Parse, || transpile::python(&nodes, &bundle.modules)).unwrap_or_else(|error| error.exit()),
+            };
+            match output {
+                Some(output) => fs::write(&output, code).unwrap_or_else(|error| {
+                    WeatherError::new(ErrorKind::Runtime, format!("Failed to write '{}': {}", output.display(), error)).exit()
+                }),
+                None => print!("{}", code),
+            }
+        }
+        Some(Command::Bench { iters, baseline, save_baseline, script }) => {
+            let summary = bench::run(&script, iters);
+            let baseline = baseline.map(|baseline| bench::Summary::load(&baseline));
+            println!("{}: {} iterations", script.display(), iters);
+            print!("{}", summary.report(baseline.as_ref()));
+            if let Some(save) = save_baseline {
+                summary.save(&save);
+            }
+        }
+        Some(Command::Add { source, name }) => {
+            let installed = match source {
+                Some(source) => package::add(&source, name.as_deref()).map(|package| vec![package]),
+                None => package::install(),
+            };
+            let installed = installed.unwrap_or_else(|error| WeatherError::new(ErrorKind::Runtime, error).exit());
+            for package in installed {
+                println!("Installed {} ({}) at {}", package.name, package.source, &package.commit[..package.commit.len().min(12)]);
+            }
+        }
+    }
+}
 
-    let script = fs::read_to_string(&args[1]).expect("Failed to read script");
-    let lexer = Lexer::new(script);
-    let mut parser = Parser::new(lexer);
-    let nodes = parser.parse();
+// Runs the script bundled into this executable with every command-line
+// argument passed to it
+fn run_bundle(bundle: bundle::Bundle) {
+    let (nodes, _) = parse_script(bundle.script);
     let mut interpreter = Interpreter::new();
+    interpreter.set_args(env::args().skip(1).map(|arg| Value::parse(&arg)).collect());
+    interpreter.set_modules(bundle.modules);
     interpreter.interpret(nodes);
-}
\ No newline at end of file
+}
+
+// Runs a script file, or the code given with -e, passing it the remaining
+// arguments. `--check` and the dump flags stop before running.
+fn run_script(args: RunArgs, debug: bool) {
+    if args.check {
+        if args.args.is_empty() {
+            cli::usage_error("--check needs at least one script");
+        }
+        if !check_files(&args.args, args.strict) {
+            process::exit(2);
+        }
+        return;
+    }
+    let config = project::Config::discover();
+    let args = args.with_config(&config);
+    let (script, script_args) = match &args.eval {
+        Some(code) => (code.clone(), &args.args[..]),
+        None => match args.args.split_first() {
+            Some((path, rest)) => (read_script(Path::new(path)), rest),
+            None => cli::usage_error("a script or -e CODE is required"),
+        },
+    };
+    if args.tokens {
+        let mut lexer = Lexer::new(script);
+        let tokens = error::catch(ErrorKind::Parse, || dump::dump_tokens(&mut lexer)).unwrap_or_else(|error| error.exit());
+        print!("{}", tokens);
+        return;
+    }
+    let (nodes, _) = parse_script(script.clone());
+    if args.strict {
+        let errors = strict::check(&nodes);
+        if !errors.is_empty() {
+            for error in errors {
+                eprintln!("error: {}", error);
+            }
+            process::exit(2);
+        }
+    }
+    let numeric = args.numeric.unwrap_or_default();
+    let nodes = if args.opt { optimize::fold(nodes, numeric) } else { nodes };
+    if args.ast {
+        print!("{}", dump::dump_ast(&nodes));
+        return;
+    }
+    if args.ast_dot {
+        print!("{}", dump::dump_ast_dot(&nodes));
+        return;
+    }
+    if args.ast_json {
+        println!("{}", serialize::to_json(&nodes));
+        return;
+    }
+    let mut interpreter = Interpreter::new();
+    interpreter.set_numeric(numeric);
+    if let Some(precision) = args.precision {
+        interpreter.set_precision(precision);
+    }
+    interpreter.set_args(script_args.iter().map(|arg| Value::parse(arg)).collect());
+    interpreter.set_search_paths(project::search_paths(&args.include));
+    interpreter.set_secrets(config.secrets.clone());
+    interpreter.set_constants(config.constants.clone());
+    interpreter.set_allow_net(args.allow_net);
+    for library in &args.plugin {
+        plugin::load(library, &mut interpreter);
+    }
+    interpreter.set_limits(Limits {
+        max_depth: args.max_depth,
+        max_steps: args.max_steps,
+        timeout: args.timeout.map(Duration::from_secs_f64),
+    });
+    if debug {
+        interpreter.add_hook(Box::new(debugger::Debugger::new(&script)));
+    }
+    if args.trace {
+        interpreter.add_hook(Box::new(trace::Tracer::new()));
+    }
+    let profiler = profile::Profiler::new();
+    if args.profile {
+        interpreter.add_hook(Box::new(profiler.clone()));
+    }
+    interpreter.interpret(nodes);
+    if args.profile {
+        eprint!("{}", profiler.report());
+    }
+}
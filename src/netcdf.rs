@@ -0,0 +1,263 @@
+use crate::value::Value;
+use std::collections::BTreeMap;
+
+// Variables from NetCDF files in the classic formats (CDF-1, the 64-bit
+// offset CDF-2 and the 64-bit data CDF-5), as written by most reanalysis
+// and climate model archives. NetCDF-4 files are HDF5 underneath and are
+// not read.
+
+struct Variable {
+    name: String,
+    dimensions: Vec<usize>,
+    attributes: Vec<(String, Value)>,
+    kind: u32,
+    size: u64,
+    begin: u64,
+}
+
+struct Header {
+    records: usize,
+    // Names and lengths, the record dimension having length 0
+    dimensions: Vec<(String, usize)>,
+    variables: Vec<Variable>,
+}
+
+// Bytes of each external type of the format
+fn width(kind: u32) -> Result<usize, String> {
+    match kind {
+        1 | 2 | 7 => Ok(1),
+        3 | 8 => Ok(2),
+        4 | 5 | 9 => Ok(4),
+        6 | 10 | 11 => Ok(8),
+        _ => Err(format!("The NetCDF file has a value of unknown type {}", kind)),
+    }
+}
+
+// One big-endian value of an external type as a number
+fn number(kind: u32, bytes: &[u8]) -> f64 {
+    let bits = bytes.iter().fold(0u64, |bits, byte| bits << 8 | *byte as u64);
+    match kind {
+        1 => bits as u8 as i8 as f64,
+        3 => bits as u16 as i16 as f64,
+        4 => bits as u32 as i32 as f64,
+        5 => f32::from_bits(bits as u32) as f64,
+        6 => f64::from_bits(bits),
+        10 => bits as i64 as f64,
+        _ => bits as f64,
+    }
+}
+
+// Values of an external type: text for characters, whole numbers for the
+// integer types and floats for the others
+fn values(kind: u32, bytes: &[u8]) -> Result<Vec<Value>, String> {
+    if kind == 2 {
+        return Ok(vec![Value::Str(String::from_utf8_lossy(bytes).trim_end_matches('\0').to_string())]);
+    }
+    let width = width(kind)?;
+    Ok(bytes
+        .chunks_exact(width)
+        .map(|bytes| match kind {
+            5 | 6 => Value::Float(number(kind, bytes)),
+            _ => Value::int(number(kind, bytes) as i64),
+        })
+        .collect())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+    version: u8,
+}
+
+impl Reader<'_> {
+    fn bytes(&mut self, count: usize) -> Result<&[u8], String> {
+        let bytes = self.data.get(self.position..self.position + count).ok_or("The NetCDF file is truncated")?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn unsigned(&mut self, count: usize) -> Result<u64, String> {
+        Ok(self.bytes(count)?.iter().fold(0, |number, byte| number << 8 | *byte as u64))
+    }
+
+    // Lengths are 64-bit in CDF-5, as are offsets in CDF-2 and CDF-5
+    fn length(&mut self) -> Result<usize, String> {
+        Ok(self.unsigned(if self.version == 5 { 8 } else { 4 })? as usize)
+    }
+
+    fn offset(&mut self) -> Result<u64, String> {
+        self.unsigned(if self.version == 1 { 4 } else { 8 })
+    }
+
+    // Text and values are padded to a multiple of four bytes
+    fn padded(&mut self, count: usize) -> Result<&[u8], String> {
+        let start = self.position;
+        self.bytes(count.div_ceil(4) * 4)?;
+        Ok(&self.data[start..start + count])
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let length = self.length()?;
+        Ok(String::from_utf8_lossy(self.padded(length)?).to_string())
+    }
+
+    // A list with its tag, which may be absent and then empty
+    fn list<T>(&mut self, tag: u64, mut item: impl FnMut(&mut Self) -> Result<T, String>) -> Result<Vec<T>, String> {
+        let found = self.unsigned(4)?;
+        let count = self.length()?;
+        if found != tag && !(found == 0 && count == 0) {
+            return Err("The NetCDF file has a malformed header".to_string());
+        }
+        (0..count).map(|_| item(self)).collect()
+    }
+
+    // A single value is a number or text; several are a list
+    fn attributes(&mut self) -> Result<Vec<(String, Value)>, String> {
+        self.list(12, |reader| {
+            let name = reader.name()?;
+            let kind = reader.unsigned(4)? as u32;
+            let count = reader.length()?;
+            let mut values = values(kind, reader.padded(count * width(kind)?)?)?;
+            let value = if values.len() == 1 { values.remove(0) } else { Value::List(values) };
+            Ok((name, value))
+        })
+    }
+
+    fn header(&mut self) -> Result<Header, String> {
+        let records = self.length()?;
+        let dimensions = self.list(10, |reader| Ok((reader.name()?, reader.length()?)))?;
+        self.attributes()?;
+        let variables = self.list(11, |reader| {
+            let name = reader.name()?;
+            let count = reader.length()?;
+            let dimensions = (0..count).map(|_| reader.length()).collect::<Result<_, _>>()?;
+            let attributes = reader.attributes()?;
+            let kind = reader.unsigned(4)? as u32;
+            let size = reader.length()? as u64;
+            let begin = reader.offset()?;
+            Ok(Variable { name, dimensions, attributes, kind, size, begin })
+        })?;
+        if variables.iter().flat_map(|variable| &variable.dimensions).any(|&dimension| dimension >= dimensions.len()) {
+            return Err("The NetCDF file has a variable with an unknown dimension".to_string());
+        }
+        Ok(Header { records, dimensions, variables })
+    }
+}
+
+fn attribute<'a>(variable: &'a Variable, name: &str) -> Option<&'a Value> {
+    variable.attributes.iter().find(|(attribute, _)| attribute == name).map(|(_, value)| value)
+}
+
+// Values as nested lists, one level per dimension
+fn nest(values: &mut impl Iterator<Item = Value>, shape: &[usize]) -> Value {
+    match shape.split_first() {
+        Some((length, rest)) => Value::List((0..*length).map(|_| nest(values, rest)).collect()),
+        None => values.next().unwrap_or(Value::Nil),
+    }
+}
+
+impl Header {
+    fn variable(&self, name: &str) -> Option<&Variable> {
+        self.variables.iter().find(|variable| variable.name == name)
+    }
+
+    fn length(&self, dimension: usize) -> usize {
+        match self.dimensions[dimension].1 {
+            0 => self.records,
+            length => length,
+        }
+    }
+
+    // The variable's values in storage order. Record variables have one
+    // slab per record, interleaved with the other record variables'.
+    fn data(&self, data: &[u8], variable: &Variable) -> Result<Vec<u8>, String> {
+        let truncated = || format!("The NetCDF file is truncated in the data of '{}'", variable.name);
+        let lengths: Vec<usize> = variable.dimensions.iter().map(|&dimension| self.length(dimension)).collect();
+        let bytes = lengths.iter().product::<usize>() * width(variable.kind)?;
+        let is_record = |variable: &Variable| variable.dimensions.first().is_some_and(|&dimension| self.dimensions[dimension].1 == 0);
+        if !is_record(variable) {
+            let start = variable.begin as usize;
+            return data.get(start..start + bytes).map(<[u8]>::to_vec).ok_or_else(truncated);
+        }
+        let slab = bytes / self.records.max(1);
+        // A lone record variable is not padded
+        let record_variables: Vec<&Variable> = self.variables.iter().filter(|variable| is_record(variable)).collect();
+        let stride = match record_variables[..] {
+            [_] => slab,
+            _ => record_variables.iter().map(|variable| variable.size as usize).sum(),
+        };
+        let mut values = Vec::with_capacity(bytes);
+        for record in 0..self.records {
+            let start = variable.begin as usize + record * stride;
+            values.extend_from_slice(data.get(start..start + slab).ok_or_else(truncated)?);
+        }
+        Ok(values)
+    }
+
+    // A variable as a map of its values, the names of its dimensions and its
+    // attributes. Packed values are unpacked with `scale_factor` and
+    // `add_offset`, and `_FillValue` and `missing_value` become nil.
+    fn value(&self, data: &[u8], variable: &Variable) -> Result<Value, String> {
+        let bytes = self.data(data, variable)?;
+        let shape: Vec<usize> = variable.dimensions.iter().map(|&dimension| self.length(dimension)).collect();
+        let values = if variable.kind == 2 {
+            // Text is stored as characters along the last dimension
+            let length = shape.last().copied().unwrap_or(1).max(1);
+            let text = bytes.chunks(length).map(|chunk| values(2, chunk)).collect::<Result<Vec<_>, _>>()?;
+            nest(&mut text.into_iter().flatten(), &shape[..shape.len().saturating_sub(1)])
+        } else {
+            let missing: Vec<f64> = ["_FillValue", "missing_value"]
+                .iter()
+                .filter_map(|name| attribute(variable, name))
+                .flat_map(|value| match value {
+                    Value::List(values) => values.clone(),
+                    value => vec![value.clone()],
+                })
+                .filter(Value::is_number)
+                .map(|value| value.to_f64())
+                .collect();
+            let scale = attribute(variable, "scale_factor").filter(|value| value.is_number());
+            let offset = attribute(variable, "add_offset").filter(|value| value.is_number());
+            let packed = scale.is_some() || offset.is_some();
+            let (scale, offset) = (scale.map_or(1.0, Value::to_f64), offset.map_or(0.0, Value::to_f64));
+            let mut unpacked = values(variable.kind, &bytes)?.into_iter().map(|value| match value.to_f64() {
+                raw if missing.contains(&raw) || raw.is_nan() => Value::Nil,
+                raw if packed => Value::Float(raw * scale + offset),
+                _ => value,
+            });
+            nest(&mut unpacked, &shape)
+        };
+        let dimensions = variable.dimensions.iter().map(|&dimension| Value::Str(self.dimensions[dimension].0.clone())).collect();
+        let attributes = variable.attributes.iter().cloned().collect::<BTreeMap<_, _>>();
+        let entries = [("values", values), ("dimensions", Value::List(dimensions)), ("attributes", Value::Map(attributes))];
+        Ok(Value::Map(entries.into_iter().map(|(key, value)| (key.to_string(), value)).collect()))
+    }
+}
+
+// A variable as `readnc` returns it: the map of `Header::value` with, under
+// "coordinates", the same map for each of its dimensions that has a
+// coordinate variable, such as `lat`, `lon` or `time`
+pub fn read(data: &[u8], name: &str) -> Result<Value, String> {
+    let version = match data.get(..4) {
+        Some([b'C', b'D', b'F', version @ (1 | 2 | 5)]) => *version,
+        Some([0x89, b'H', b'D', b'F']) => return Err("NetCDF-4 files are not supported, convert them to the classic format with `nccopy -k classic`".to_string()),
+        _ => return Err("Not a NetCDF file".to_string()),
+    };
+    let header = Reader { data, position: 4, version }.header()?;
+    let Some(variable) = header.variable(name) else {
+        let names: Vec<&str> = header.variables.iter().map(|variable| variable.name.as_str()).collect();
+        return Err(format!("The NetCDF file has no variable '{}', only {}", name, names.join(", ")));
+    };
+    let mut value = header.value(data, variable)?;
+    let mut coordinates = BTreeMap::new();
+    for &dimension in &variable.dimensions {
+        let dimension = &header.dimensions[dimension].0;
+        if let Some(coordinate) = header.variable(dimension).filter(|coordinate| coordinate.dimensions.len() == 1 && coordinate.name != name) {
+            coordinates.insert(dimension.clone(), header.value(data, coordinate)?);
+        }
+    }
+    if let Value::Map(entries) = &mut value {
+        entries.insert("coordinates".to_string(), Value::Map(coordinates));
+    }
+    Ok(value)
+}
@@ -0,0 +1,113 @@
+use crate::ast::{ASTNode, Node};
+use crate::interpreter::Interpreter;
+use crate::stack;
+use crate::value::{Numeric, Value};
+use num_rational::BigRational;
+use num_traits::Zero;
+use std::panic::{self, AssertUnwindSafe};
+
+// Replaces constant subexpressions, such as `ftoc(212)` or `2 * _pi_`, with
+// their value before the script runs. Expressions are evaluated in the
+// numeric mode the script will run in, and only finite real results are
+// folded, so the optimized program gives the same output; expressions that
+// would fail are left for the interpreter to report when (and if) they run.
+pub fn fold(mut nodes: Vec<Node>, numeric: Numeric) -> Vec<Node> {
+    for node in nodes.iter_mut() {
+        fold_node(node, numeric);
+    }
+    nodes
+}
+
+fn fold_node(node: &mut Node, numeric: Numeric) {
+    stack::grow(|| {
+        for child in node.node.children_mut() {
+            fold_node(child, numeric);
+        }
+        let constant = is_pure(&node.node)
+            && node.node.children().iter().all(|child| matches!(child.node, ASTNode::Float(_)));
+        if !constant {
+            return;
+        }
+        let expression = node.clone();
+        let value = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut interpreter = Interpreter::new();
+            interpreter.set_numeric(numeric);
+            interpreter.evaluate(&expression)
+        }));
+        // A folded f64 is stored exactly, so it reads back as the same f64
+        match value {
+            Ok(Value::Number(value)) if value.im.is_zero() => node.node = ASTNode::Float(value.re),
+            Ok(Value::Float(value)) => {
+                if let Some(value) = BigRational::from_float(value) {
+                    node.node = ASTNode::Float(value);
+                }
+            }
+            _ => {}
+        }
+    })
+}
+
+// Expressions whose value depends only on their operands. Physical
+// constants such as `_g_`, and the builtins that use them, are left out
+// because `set_constant` and `weather.toml` can change them.
+fn is_pure(node: &ASTNode) -> bool {
+    matches!(
+        node,
+        ASTNode::BinaryOp(..)
+            | ASTNode::UnaryOp(..)
+            | ASTNode::DewPoint(..)
+            | ASTNode::ApparentTemp(..)
+            | ASTNode::Svp(_)
+            | ASTNode::MixingRatio(..)
+            | ASTNode::SpecificHumidity(..)
+            | ASTNode::Isa(_)
+            | ASTNode::Et0(_)
+            | ASTNode::Hdd(..)
+            | ASTNode::Cdd(..)
+            | ASTNode::WindComponents(..)
+            | ASTNode::WindFromComponents(..)
+            | ASTNode::MmToIn(_)
+            | ASTNode::InToMm(_)
+            | ASTNode::MmhToInh(_)
+            | ASTNode::InhToMmh(_)
+            | ASTNode::Accumulation(..)
+            | ASTNode::RunningTotal(_)
+            | ASTNode::Events(_)
+            | ASTNode::Percentile(..)
+            | ASTNode::PercentileRank(..)
+            | ASTNode::Anomaly(..)
+            | ASTNode::RollingMean(..)
+            | ASTNode::Spi(..)
+            | ASTNode::Windrose(..)
+            | ASTNode::ParseJson(_)
+            | ASTNode::ToJson(_)
+            | ASTNode::Slr(_)
+            | ASTNode::Snowfall(..)
+            | ASTNode::VisConvert(..)
+            | ASTNode::FogCategory(_)
+            | ASTNode::SolarZenith(..)
+            | ASTNode::SolarDeclination(_)
+            | ASTNode::ToTimezone(..)
+            | ASTNode::UtcOffset(..)
+            | ASTNode::UvIndex(_)
+            | ASTNode::Altimeter(..)
+            | ASTNode::RhFromWetbulb(..)
+            | ASTNode::Haversine(_)
+            | ASTNode::Bearing(_)
+            | ASTNode::ParseCoord(_)
+            | ASTNode::FormatCoord(_)
+            | ASTNode::Station(_)
+            | ASTNode::Cloudbase(_)
+            | ASTNode::Convert(_)
+            | ASTNode::FToC(_)
+            | ASTNode::CToF(_)
+            | ASTNode::CToK(_)
+            | ASTNode::KToC(_)
+            | ASTNode::FToK(_)
+            | ASTNode::KToF(_)
+            | ASTNode::GreaterThan(..)
+            | ASTNode::LessThan(..)
+            | ASTNode::Pi
+            | ASTNode::Kelvin
+    )
+}
@@ -0,0 +1,170 @@
+use crate::configs::{LOCK_FILE, MODULES_DIR};
+use crate::project;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+// Packages installed with `qprime add`, as recorded in `weather.lock`
+#[derive(Default, Serialize, Deserialize)]
+pub struct Lockfile {
+    #[serde(rename = "package", default)]
+    pub packages: Vec<Package>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Package {
+    // Directory under `weather_modules`, and the name scripts import
+    pub name: String,
+    // Git URL the package was cloned from
+    pub source: String,
+    // Commit that was installed
+    pub commit: String,
+}
+
+impl Lockfile {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(path).map_err(|error| format!("Failed to read {}: {}", path.display(), error))?;
+        toml::from_str(&text).map_err(|error| format!("Invalid {}: {}", path.display(), error.message()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = toml::to_string_pretty(self).map_err(|error| error.to_string())?;
+        fs::write(path, text).map_err(|error| format!("Failed to write {}: {}", path.display(), error))
+    }
+
+    // Adds a package, replacing an earlier one of the same name
+    fn insert(&mut self, package: Package) {
+        self.packages.retain(|existing| existing.name != package.name);
+        self.packages.push(package);
+        self.packages.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+}
+
+// Git URL for a package source: URLs and `git@host:path` are used as they
+// are, local repositories by their absolute path, and `user/repo` is a
+// GitHub repository
+pub fn git_url(source: &str) -> Result<String, String> {
+    if source.contains("://") || source.starts_with("git@") {
+        return Ok(source.to_string());
+    }
+    if let Ok(path) = fs::canonicalize(source) {
+        return Ok(path.to_string_lossy().into_owned());
+    }
+    match source.split_once('/') {
+        Some((user, repo)) if !user.is_empty() && !repo.is_empty() && !repo.contains('/') => {
+            Ok(format!("https://github.com/{}/{}.git", user, repo.trim_end_matches(".git")))
+        }
+        _ => Err(format!("'{}' is not a git URL or a GitHub 'user/repo' name", source)),
+    }
+}
+
+// Package name for a source: its last path segment without `.git`
+pub fn package_name(source: &str) -> Result<String, String> {
+    let name = source.trim_end_matches('/').rsplit(['/', ':']).next().unwrap_or_default().trim_end_matches(".git");
+    check_name(name).map_err(|_| format!("Cannot tell the package name of '{}'; give one with --name", source))?;
+    Ok(name.to_string())
+}
+
+// A package is installed in the directory of its name, which is replaced
+// on every install, so the name must be a single plain directory name
+fn check_name(name: &str) -> Result<(), String> {
+    let plain = matches!(Path::new(name).components().collect::<Vec<_>>()[..], [Component::Normal(_)]);
+    if !plain || name.contains(['/', '\\', ':']) || name.starts_with(['-', '.']) {
+        return Err(format!("'{}' is not a valid package name", name));
+    }
+    Ok(())
+}
+
+// Sources and commits come from the command line or `weather.lock` and go
+// to git as arguments, so they must not look like options
+fn check_package(url: &str, commit: Option<&str>) -> Result<(), String> {
+    if url.starts_with('-') {
+        return Err(format!("'{}' is not a valid package source", url));
+    }
+    match commit {
+        Some(commit) if commit.is_empty() || !commit.chars().all(|ch| ch.is_ascii_hexdigit()) => {
+            Err(format!("'{}' is not a valid commit", commit))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn git(args: &[&str], dir: Option<&Path>) -> Result<String, String> {
+    let mut command = Command::new("git");
+    if let Some(dir) = dir {
+        command.arg("-C").arg(dir);
+    }
+    let output = command.args(args).output().map_err(|error| format!("Failed to run git: {}", error))?;
+    if !output.status.success() {
+        return Err(format!("git {} failed: {}", args[0], String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// Clones `url` into `dir` at `commit` (the latest one when not given) and
+// returns the installed commit. The git metadata is dropped, so the package
+// is plain files.
+fn fetch(url: &str, dir: &Path, commit: Option<&str>) -> Result<String, String> {
+    check_package(url, commit)?;
+    if dir.exists() {
+        fs::remove_dir_all(dir).map_err(|error| format!("Failed to remove {}: {}", dir.display(), error))?;
+    }
+    let target = dir.to_string_lossy();
+    match commit {
+        Some(commit) => {
+            git(&["clone", "--quiet", "--", url, &target], None)?;
+            git(&["checkout", "--quiet", commit, "--"], Some(dir))?;
+        }
+        None => {
+            git(&["clone", "--quiet", "--depth", "1", "--", url, &target], None)?;
+        }
+    }
+    let commit = git(&["rev-parse", "HEAD"], Some(dir))?;
+    fs::remove_dir_all(dir.join(".git")).map_err(|error| format!("Failed to remove {}: {}", dir.join(".git").display(), error))?;
+    Ok(commit)
+}
+
+fn modules_dir(root: &Path) -> Result<PathBuf, String> {
+    let dir = root.join(MODULES_DIR);
+    fs::create_dir_all(&dir).map_err(|error| format!("Failed to create {}: {}", dir.display(), error))?;
+    Ok(dir)
+}
+
+// Installs the latest version of a package into the project's
+// `weather_modules` and records it in `weather.lock`
+pub fn add(source: &str, name: Option<&str>) -> Result<Package, String> {
+    let root = project::root();
+    let url = git_url(source)?;
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => package_name(&url)?,
+    };
+    check_name(&name)?;
+    let commit = fetch(&url, &modules_dir(&root)?.join(&name), None)?;
+    let lock_path = root.join(LOCK_FILE);
+    let mut lockfile = Lockfile::load(&lock_path)?;
+    let package = Package { name, source: url, commit };
+    lockfile.insert(package.clone());
+    lockfile.save(&lock_path)?;
+    Ok(package)
+}
+
+// Installs every package in `weather.lock` at its recorded commit
+pub fn install() -> Result<Vec<Package>, String> {
+    let root = project::root();
+    let lockfile = Lockfile::load(&root.join(LOCK_FILE))?;
+    let dir = modules_dir(&root)?;
+    // A tampered lockfile is rejected before anything is replaced
+    for package in &lockfile.packages {
+        check_name(&package.name)?;
+        check_package(&package.source, Some(&package.commit))?;
+    }
+    for package in &lockfile.packages {
+        fetch(&package.source, &dir.join(&package.name), Some(&package.commit))?;
+    }
+    Ok(lockfile.packages)
+}
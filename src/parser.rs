@@ -1,56 +1,166 @@
-use crate::lexer::Lexer;
+use crate::lexer::{call_name, Lexer, CALL_NAMES, KEYWORDS};
 use crate::token::Token;
-use crate::ast::ASTNode;
+use crate::ast::{ASTNode, Node};
+use crate::span::{Position, Span, Spanned};
+use crate::stack;
+
+// Operator precedence, from loosest to tightest binding:
+//
+//   | Operators                  | Associativity |
+//   |----------------------------|---------------|
+//   | or                         | left          |
+//   | and                        | left          |
+//   | not (prefix)               | -             |
+//   | == != < <= > >=            | left          |
+//   | + -                        | left          |
+//   | * / %                      | left          |
+//   | - (prefix)                 | -             |
+//   | **                         | right         |
+//   | [index] (postfix)          | -             |
+//
+// Binary operators map to a (left, right) binding power pair; a right power
+// lower than the left one makes the operator right-associative.
+pub fn infix_binding_power(token: &Token) -> Option<(u8, u8)> {
+    match token {
+        Token::Or => Some((1, 2)),
+        Token::And => Some((3, 4)),
+        Token::EqualEqual
+        | Token::NotEqual
+        | Token::LessThan
+        | Token::LessEqual
+        | Token::GreaterThan
+        | Token::GreaterEqual => Some((7, 8)),
+        Token::Plus | Token::Minus => Some((9, 10)),
+        Token::Star | Token::Slash | Token::Modulo => Some((11, 12)),
+        Token::StarStar => Some((16, 15)),
+        _ => None,
+    }
+}
+
+// Binding power of the operand of prefix operators
+pub const NOT_BINDING_POWER: u8 = 5;
+pub const NEGATE_BINDING_POWER: u8 = 13;
 
 pub struct Parser {
     lexer: Lexer,
+    // Token after the current one, when it had to be read early
+    peeked: Option<Spanned<Token>>,
     current_token: Token,
+    current_span: Span,
+    previous_end: Position,
     line: usize,
 }
 
 impl Parser {
-    pub fn new(mut lexer: Lexer) -> Self {
-        let current_token = lexer.next_token();
-        let line = lexer.line;
-        Self { lexer, current_token , line }
+    pub fn new(lexer: Lexer) -> Self {
+        let mut parser = Self { lexer, peeked: None, current_token: Token::EOF, current_span: Span::default(), previous_end: Position::default(), line: 1 };
+        let token = parser.next_token();
+        parser.line = token.span.start.line;
+        parser.current_token = token.node;
+        parser.current_span = token.span;
+        parser
+    }
+
+    // The next token, with the name of a builtin in `CALL_NAMES` turned into
+    // the builtin's token when it is followed by `(`
+    fn next_token(&mut self) -> Spanned<Token> {
+        let mut token = self.peeked.take().unwrap_or_else(|| self.lexer.next_token());
+        if let Some(builtin) = match &token.node {
+            Token::Identifier(name) => call_name(name),
+            _ => None,
+        } {
+            let next = self.lexer.next_token();
+            if next.node == Token::LParen {
+                token.node = builtin;
+            }
+            self.peeked = Some(next);
+        }
+        token
     }
 
     fn consume(&mut self, expected: Token) {
         if self.current_token == expected {
-            self.current_token = self.lexer.next_token();
-            self.line = self.lexer.line;
+            let token = self.next_token();
+            self.previous_end = self.current_span.end;
+            self.current_token = token.node;
+            self.current_span = token.span;
+            self.line = self.current_span.start.line;
         } else {
             panic!("Expected token '{:?}', found '{:?}' on line {}.", expected, self.current_token, self.line);
         }
     }
 
-    pub fn parse_expression(&mut self) -> ASTNode {
-        let mut node = self.parse_term();
-        while matches!(self.current_token, Token::Plus | Token::Minus | Token::GreaterThan | Token::LessThan) {
-            let token = self.current_token.clone();
-            self.consume(token.clone());
-            node = ASTNode::BinaryOp(Box::new(node), token, Box::new(self.parse_term()));
-        }
-        node
+    // Comments skipped by the lexer so far, in source order
+    pub fn comments(&self) -> &[Spanned<String>] {
+        &self.lexer.comments
+    }
+
+    // Wraps a node with the span from `start` to the end of the last consumed token
+    fn spanned(&self, start: Position, node: ASTNode) -> Node {
+        Spanned::new(node, Span::new(start, self.previous_end))
     }
 
-    pub fn parse_term(&mut self) -> ASTNode {
-        let mut node = self.parse_factor();
-        while matches!(self.current_token, Token::Star | Token::Slash | Token::StarStar | Token::Modulo) {
-            let token = self.current_token.clone();
-            self.consume(token.clone());
-            node = ASTNode::BinaryOp(Box::new(node), token, Box::new(self.parse_factor()));
+    pub fn parse_expression(&mut self) -> Node {
+        stack::grow(|| self.parse_expression_bp(0))
+    }
+
+    // Parses input that must consist of exactly one expression
+    pub fn parse_standalone_expression(&mut self) -> Node {
+        let expression = self.parse_expression();
+        if self.current_token != Token::EOF {
+            panic!("Unexpected token '{:?}' on line {}.", self.current_token, self.line);
+        }
+        expression
+    }
+
+    // Precedence climbing: parses operators binding at least as tightly as `min_bp`
+    fn parse_expression_bp(&mut self, min_bp: u8) -> Node {
+        let start = self.current_span.start;
+        let mut node = match self.current_token {
+            Token::Minus | Token::Not => {
+                let op = self.current_token.clone();
+                let bp = if op == Token::Not { NOT_BINDING_POWER } else { NEGATE_BINDING_POWER };
+                self.consume(op.clone());
+                let operand = stack::grow(|| self.parse_expression_bp(bp));
+                self.spanned(start, ASTNode::UnaryOp(op, Box::new(operand)))
+            }
+            _ => self.parse_factor(),
+        };
+        loop {
+            // Indexing binds tighter than any operator
+            if self.current_token == Token::LBracket {
+                self.consume(Token::LBracket);
+                let index = self.parse_expression();
+                self.consume(Token::RBracket);
+                node = self.spanned(start, ASTNode::Index(Box::new(node), Box::new(index)));
+                continue;
+            }
+            let Some((left_bp, right_bp)) = infix_binding_power(&self.current_token) else {
+                break;
+            };
+            if left_bp < min_bp {
+                break;
+            }
+            let op = self.current_token.clone();
+            self.consume(op.clone());
+            let right = stack::grow(|| self.parse_expression_bp(right_bp));
+            node = self.spanned(start, ASTNode::BinaryOp(Box::new(node), op, Box::new(right)));
         }
         node
     }
 
-    pub fn parse_factor(&mut self) -> ASTNode {
-        match self.current_token.clone() {
+    pub fn parse_factor(&mut self) -> Node {
+        let start = self.current_span.start;
+        let node = match self.current_token.clone() {
             Token::Float(value) => {
                 let value_clone = value.clone();
                 self.consume(Token::Float(value));
                 ASTNode::Float(value_clone)
             }
+            Token::Quantity(value, unit) => {
+                self.consume(Token::Quantity(value.clone(), unit.clone()));
+                ASTNode::Quantity(value, unit)
+            }
             Token::Identifier(name) => {
                 self.consume(Token::Identifier(name.clone()));
                 if self.current_token == Token::LParen {
@@ -73,13 +183,95 @@ impl Parser {
                 self.consume(Token::StringLiteral(value.clone()));
                 ASTNode::StringLiteral(value)
             }
-            Token::DewPoint => self.parse_dew_point(),
+            // `dewpoint(temperature, humidity)`, optionally followed by the formula
+            Token::DewPoint => ASTNode::DewPoint(self.parse_optional_args(Token::DewPoint, 2, 3)),
+            Token::ApparentTemp => self.parse_three_args(Token::ApparentTemp, ASTNode::ApparentTemp),
+            // `svp(temperature)`, optionally followed by the formula and the surface
+            Token::Svp => ASTNode::Svp(self.parse_optional_args(Token::Svp, 1, 3)),
+            Token::MixingRatio => self.parse_three_args(Token::MixingRatio, ASTNode::MixingRatio),
+            Token::SpecificHumidity => self.parse_three_args(Token::SpecificHumidity, ASTNode::SpecificHumidity),
+            Token::PressureAtAltitude => self.parse_three_args(Token::PressureAtAltitude, ASTNode::PressureAtAltitude),
             Token::FToC => self.parse_ftoc(),
             Token::CToF => self.parse_ctof(),
             Token::CToK => self.parse_ctok(),
             Token::KToC => self.parse_ktoc(),
             Token::FToK => self.parse_ftok(),
             Token::KToF => self.parse_ktof(),
+            Token::IsNaN => self.parse_isnan(),
+            Token::Isa => self.parse_isa(),
+            Token::Dalr => {
+                self.consume(Token::Dalr);
+                self.consume(Token::LParen);
+                self.consume(Token::RParen);
+                ASTNode::Dalr
+            }
+            Token::Malr => self.parse_malr(),
+            Token::DryAdiabat => self.parse_three_args(Token::DryAdiabat, ASTNode::DryAdiabat),
+            Token::Et0 => ASTNode::Et0(self.parse_args(Token::Et0, 5)),
+            Token::Hdd => self.parse_two_args(Token::Hdd, ASTNode::Hdd),
+            Token::Cdd => self.parse_two_args(Token::Cdd, ASTNode::Cdd),
+            Token::WindComponents => self.parse_two_args(Token::WindComponents, ASTNode::WindComponents),
+            Token::WindFromComponents => self.parse_two_args(Token::WindFromComponents, ASTNode::WindFromComponents),
+            Token::MmToIn => self.parse_one_arg(Token::MmToIn, ASTNode::MmToIn),
+            Token::InToMm => self.parse_one_arg(Token::InToMm, ASTNode::InToMm),
+            Token::MmhToInh => self.parse_one_arg(Token::MmhToInh, ASTNode::MmhToInh),
+            Token::InhToMmh => self.parse_one_arg(Token::InhToMmh, ASTNode::InhToMmh),
+            Token::Accumulation => self.parse_two_args(Token::Accumulation, ASTNode::Accumulation),
+            Token::RunningTotal => self.parse_one_arg(Token::RunningTotal, ASTNode::RunningTotal),
+            Token::Percentile => self.parse_two_args(Token::Percentile, ASTNode::Percentile),
+            Token::PercentileRank => self.parse_two_args(Token::PercentileRank, ASTNode::PercentileRank),
+            Token::Anomaly => self.parse_two_args(Token::Anomaly, ASTNode::Anomaly),
+            Token::RollingMean => self.parse_two_args(Token::RollingMean, ASTNode::RollingMean),
+            Token::Spi => self.parse_two_args(Token::Spi, ASTNode::Spi),
+            Token::Windrose => self.parse_three_args(Token::Windrose, ASTNode::Windrose),
+            Token::Events => ASTNode::Events(self.parse_optional_args(Token::Events, 3, 4)),
+            Token::Slr => self.parse_one_arg(Token::Slr, ASTNode::Slr),
+            Token::Snowfall => self.parse_two_args(Token::Snowfall, ASTNode::Snowfall),
+            Token::VisConvert => self.parse_three_args(Token::VisConvert, ASTNode::VisConvert),
+            Token::FogCategory => ASTNode::FogCategory(self.parse_optional_args(Token::FogCategory, 1, 2)),
+            Token::SolarZenith => self.parse_three_args(Token::SolarZenith, ASTNode::SolarZenith),
+            Token::SolarDeclination => self.parse_one_arg(Token::SolarDeclination, ASTNode::SolarDeclination),
+            Token::UvIndex => ASTNode::UvIndex(self.parse_optional_args(Token::UvIndex, 1, 2)),
+            Token::Coriolis => self.parse_one_arg(Token::Coriolis, ASTNode::Coriolis),
+            Token::PrecipitableWater => self.parse_two_args(Token::PrecipitableWater, ASTNode::PrecipitableWater),
+            Token::SpeedOfSound => self.parse_two_args(Token::SpeedOfSound, ASTNode::SpeedOfSound),
+            Token::Altimeter => self.parse_two_args(Token::Altimeter, ASTNode::Altimeter),
+            Token::RhFromWetbulb => self.parse_three_args(Token::RhFromWetbulb, ASTNode::RhFromWetbulb),
+            Token::Haversine => ASTNode::Haversine(self.parse_args(Token::Haversine, 4)),
+            Token::Bearing => ASTNode::Bearing(self.parse_args(Token::Bearing, 4)),
+            Token::ParseCoord => self.parse_one_arg(Token::ParseCoord, ASTNode::ParseCoord),
+            Token::FormatCoord => ASTNode::FormatCoord(self.parse_optional_args(Token::FormatCoord, 2, 3)),
+            Token::Station => self.parse_one_arg(Token::Station, ASTNode::Station),
+            Token::Cloudbase => ASTNode::Cloudbase(self.parse_optional_args(Token::Cloudbase, 2, 3)),
+            Token::Convert => ASTNode::Convert(self.parse_optional_args(Token::Convert, 2, 3)),
+            Token::Args => {
+                self.consume(Token::Args);
+                self.consume(Token::LParen);
+                self.consume(Token::RParen);
+                ASTNode::Args
+            }
+            Token::Len => self.parse_len(),
+            Token::Env => self.parse_env(),
+            Token::Secret => self.parse_secret(),
+            Token::ParseJson => self.parse_one_arg(Token::ParseJson, ASTNode::ParseJson),
+            Token::ToJson => self.parse_one_arg(Token::ToJson, ASTNode::ToJson),
+            Token::ReadJson => self.parse_one_arg(Token::ReadJson, ASTNode::ReadJson),
+            Token::ReadGrib => self.parse_three_args(Token::ReadGrib, ASTNode::ReadGrib),
+            Token::ReadNc => self.parse_two_args(Token::ReadNc, ASTNode::ReadNc),
+            Token::ToTimezone => self.parse_two_args(Token::ToTimezone, ASTNode::ToTimezone),
+            Token::UtcOffset => self.parse_two_args(Token::UtcOffset, ASTNode::UtcOffset),
+            Token::Rand => {
+                self.consume(Token::Rand);
+                self.consume(Token::LParen);
+                self.consume(Token::RParen);
+                ASTNode::Rand
+            }
+            Token::RandInt => self.parse_two_args(Token::RandInt, ASTNode::RandInt),
+            Token::RandN => self.parse_two_args(Token::RandN, ASTNode::RandN),
+            Token::Fetch => self.parse_one_arg(Token::Fetch, ASTNode::Fetch),
+            Token::Forecast => self.parse_two_args(Token::Forecast, ASTNode::Forecast),
+            Token::Format => self.parse_format(),
+            Token::Pmap => self.parse_pmap(),
             Token::PauliX => self.parse_paulix(),
             Token::PauliY => self.parse_pauliy(),
             Token::PauliZ => self.parse_pauliz(),
@@ -94,6 +286,18 @@ impl Parser {
             Token::TGate => self.parse_tgate(),
             Token::SGate => self.parse_sgate(),
             Token::Fredkin => self.parse_fredkin(),
+            Token::NaN => {
+                self.consume(Token::NaN);
+                ASTNode::NaN
+            }
+            Token::Inf => {
+                self.consume(Token::Inf);
+                ASTNode::Inf
+            }
+            Token::Nil => {
+                self.consume(Token::Nil);
+                ASTNode::Nil
+            }
             Token::Pi => {
                 self.consume(Token::Pi);
                 ASTNode::Pi
@@ -134,19 +338,40 @@ impl Parser {
                 self.consume(Token::G);
                 ASTNode::G
             }
+            Token::Omega => {
+                self.consume(Token::Omega);
+                ASTNode::Omega
+            }
+            Token::Rv => {
+                self.consume(Token::Rv);
+                ASTNode::Rv
+            }
+            Token::Epsilon => {
+                self.consume(Token::Epsilon);
+                ASTNode::Epsilon
+            }
+            Token::Sigma => {
+                self.consume(Token::Sigma);
+                ASTNode::Sigma
+            }
+            Token::Solar => {
+                self.consume(Token::Solar);
+                ASTNode::Solar
+            }
+            Token::Lf => {
+                self.consume(Token::Lf);
+                ASTNode::Lf
+            }
             Token::LParen => {
                 self.consume(Token::LParen);
                 let expr = self.parse_expression();
                 self.consume(Token::RParen);
-                expr
-            }
-            Token::LBrace => {
-                self.consume(Token::LBrace);
-                let block = self.parse_block();
-                ASTNode::Block(block)
+                expr.node
             }
+            Token::LBrace => return self.parse_braced_block(),
             _ => panic!("Unexpected token '{:?}' on line {}.", self.current_token, self.line),
-        }
+        };
+        self.spanned(start, node)
     }
 
     pub fn parse_function_definition(&mut self) -> ASTNode {
@@ -171,19 +396,44 @@ impl Parser {
             }
         }
         self.consume(Token::RParen);
-        self.consume(Token::LBrace);
-        let body = self.parse_block();
-        ASTNode::Function(name, params, Box::new(ASTNode::Block(body)))
+        let body = self.parse_braced_block();
+        ASTNode::Function(name, params, Box::new(body))
+    }
+
+    // Arguments of a builtin that takes `count` of them
+    fn parse_args(&mut self, token: Token, count: usize) -> Vec<Node> {
+        self.parse_optional_args(token, count, count)
     }
 
-    fn parse_dew_point(&mut self) -> ASTNode {
-        self.consume(Token::DewPoint);
+    // Arguments of a builtin that takes `required` of them and optionally
+    // more, up to `max`
+    fn parse_optional_args(&mut self, token: Token, required: usize, max: usize) -> Vec<Node> {
+        self.consume(token);
         self.consume(Token::LParen);
-        let temp = self.parse_expression();
+        let mut args = vec![self.parse_expression()];
+        for _ in 1..required {
+            self.consume(Token::Comma);
+            args.push(self.parse_expression());
+        }
+        while self.current_token == Token::Comma && args.len() < max {
+            self.consume(Token::Comma);
+            args.push(self.parse_expression());
+        }
+        self.consume(Token::RParen);
+        args
+    }
+
+    // Builtin with three arguments, e.g. `mixing_ratio(temperature, humidity, pressure)`
+    fn parse_three_args(&mut self, token: Token, node: fn(Box<Node>, Box<Node>, Box<Node>) -> ASTNode) -> ASTNode {
+        self.consume(token);
+        self.consume(Token::LParen);
+        let first = self.parse_expression();
         self.consume(Token::Comma);
-        let humidity = self.parse_expression();
+        let second = self.parse_expression();
+        self.consume(Token::Comma);
+        let third = self.parse_expression();
         self.consume(Token::RParen);
-        ASTNode::DewPoint(Box::new(temp), Box::new(humidity))
+        node(Box::new(first), Box::new(second), Box::new(third))
     }
 
     fn parse_ftoc(&mut self) -> ASTNode {
@@ -234,6 +484,98 @@ impl Parser {
         ASTNode::KToF(Box::new(kelvin))
     }
 
+    fn parse_isnan(&mut self) -> ASTNode {
+        self.consume(Token::IsNaN);
+        self.consume(Token::LParen);
+        let value = self.parse_expression();
+        self.consume(Token::RParen);
+        ASTNode::IsNaN(Box::new(value))
+    }
+
+    fn parse_isa(&mut self) -> ASTNode {
+        self.consume(Token::Isa);
+        self.consume(Token::LParen);
+        let altitude = self.parse_expression();
+        self.consume(Token::RParen);
+        ASTNode::Isa(Box::new(altitude))
+    }
+
+    fn parse_malr(&mut self) -> ASTNode {
+        self.consume(Token::Malr);
+        self.consume(Token::LParen);
+        let temp = self.parse_expression();
+        self.consume(Token::Comma);
+        let pressure = self.parse_expression();
+        self.consume(Token::RParen);
+        ASTNode::Malr(Box::new(temp), Box::new(pressure))
+    }
+
+    // Builtin with one argument, e.g. `mmtoin(depth)`
+    fn parse_one_arg(&mut self, token: Token, node: fn(Box<Node>) -> ASTNode) -> ASTNode {
+        let mut args = self.parse_args(token, 1);
+        node(Box::new(args.remove(0)))
+    }
+
+    // Builtin with two arguments, e.g. `hdd(temperatures, base)`
+    fn parse_two_args(&mut self, token: Token, node: fn(Box<Node>, Box<Node>) -> ASTNode) -> ASTNode {
+        let mut args = self.parse_args(token, 2).into_iter().map(Box::new);
+        node(args.next().unwrap(), args.next().unwrap())
+    }
+
+    fn parse_len(&mut self) -> ASTNode {
+        self.consume(Token::Len);
+        self.consume(Token::LParen);
+        let value = self.parse_expression();
+        self.consume(Token::RParen);
+        ASTNode::Len(Box::new(value))
+    }
+
+    fn parse_env(&mut self) -> ASTNode {
+        self.consume(Token::Env);
+        self.consume(Token::LParen);
+        let name = self.parse_expression();
+        self.consume(Token::RParen);
+        ASTNode::Env(Box::new(name))
+    }
+
+    fn parse_secret(&mut self) -> ASTNode {
+        self.consume(Token::Secret);
+        self.consume(Token::LParen);
+        let name = self.parse_expression();
+        self.consume(Token::RParen);
+        ASTNode::Secret(Box::new(name))
+    }
+
+    fn parse_format(&mut self) -> ASTNode {
+        self.consume(Token::Format);
+        self.consume(Token::LParen);
+        let value = self.parse_expression();
+        self.consume(Token::Comma);
+        let digits = self.parse_expression();
+        self.consume(Token::RParen);
+        ASTNode::Format(Box::new(value), Box::new(digits))
+    }
+
+    // The function is named without parentheses: a user function, a native
+    // function, or a builtin such as `ctof`
+    fn parse_pmap(&mut self) -> ASTNode {
+        self.consume(Token::Pmap);
+        self.consume(Token::LParen);
+        let token = self.current_token.clone();
+        let function = match &token {
+            Token::Identifier(name) => name.clone(),
+            token => match KEYWORDS.iter().chain(CALL_NAMES).find(|(_, keyword)| keyword == token) {
+                Some((name, _)) => name.to_string(),
+                None => panic!("Expected function name on line {}.", self.line),
+            },
+        };
+        self.consume(token);
+        self.consume(Token::Comma);
+        let list = self.parse_expression();
+        self.consume(Token::RParen);
+        ASTNode::Pmap(function, Box::new(list))
+    }
+
     fn parse_paulix(&mut self) -> ASTNode {
         self.consume(Token::PauliX);
         self.consume(Token::LParen);
@@ -389,21 +731,26 @@ impl Parser {
         ASTNode::Call(name, args)
     }
 
-    pub fn parse_statement(&mut self) -> ASTNode {
-        match self.current_token.clone() {
+    pub fn parse_statement(&mut self) -> Node {
+        let start = self.current_span.start;
+        let node = match self.current_token.clone() {
             Token::Identifier(_) => self.parse_assignment(),
             Token::Print => self.parse_print(),
+            Token::Assert => self.parse_assert(),
+            Token::AssertApprox => self.parse_assert_approx(),
+            Token::SetConstant => self.parse_set_constant(),
+            Token::WriteJson => self.parse_write_json(),
+            Token::Seed => self.parse_one_arg(Token::Seed, ASTNode::Seed),
+            Token::Sleep => self.parse_one_arg(Token::Sleep, ASTNode::Sleep),
             Token::If => self.parse_if(),
             Token::Function => self.parse_function_definition(),
             Token::Import => self.parse_import(),
+            Token::Export => self.parse_export(),
             Token::Call => self.parse_call(),
-            Token::LBrace => {
-                self.consume(Token::LBrace);
-                let block = self.parse_block();
-                ASTNode::Block(block)
-            }
+            Token::LBrace => return self.parse_braced_block(),
             _ => panic!("Unexpected token '{:?}' on line {}.", self.current_token, self.line),
-        }
+        };
+        self.spanned(start, node)
     }
 
     pub fn parse_assignment(&mut self) -> ASTNode {
@@ -424,15 +771,91 @@ impl Parser {
         self.consume(Token::RParen);
         ASTNode::Print(Box::new(expr))
     }
+
+    fn parse_assert(&mut self) -> ASTNode {
+        self.consume(Token::Assert);
+        self.consume(Token::LParen);
+        let condition = self.parse_expression();
+        self.consume(Token::Comma);
+        let message = self.parse_expression();
+        self.consume(Token::RParen);
+        ASTNode::Assert(Box::new(condition), Box::new(message))
+    }
+
+    fn parse_set_constant(&mut self) -> ASTNode {
+        self.consume(Token::SetConstant);
+        self.consume(Token::LParen);
+        let name = self.parse_expression();
+        self.consume(Token::Comma);
+        let value = self.parse_expression();
+        self.consume(Token::RParen);
+        ASTNode::SetConstant(Box::new(name), Box::new(value))
+    }
+
+    fn parse_write_json(&mut self) -> ASTNode {
+        self.consume(Token::WriteJson);
+        self.consume(Token::LParen);
+        let path = self.parse_expression();
+        self.consume(Token::Comma);
+        let value = self.parse_expression();
+        self.consume(Token::RParen);
+        ASTNode::WriteJson(Box::new(path), Box::new(value))
+    }
+
+    fn parse_assert_approx(&mut self) -> ASTNode {
+        self.consume(Token::AssertApprox);
+        self.consume(Token::LParen);
+        let actual = self.parse_expression();
+        self.consume(Token::Comma);
+        let expected = self.parse_expression();
+        self.consume(Token::Comma);
+        let tolerance = self.parse_expression();
+        self.consume(Token::RParen);
+        ASTNode::AssertApprox(Box::new(actual), Box::new(expected), Box::new(tolerance))
+    }
     pub fn parse_import(&mut self) -> ASTNode {
         self.consume(Token::Import);
         let module_name = if let Token::StringLiteral(name) = self.current_token.clone() {
             self.consume(Token::StringLiteral(name.clone()));
-            name + "." + crate::configs::FILE_EXTENSION
+            // URLs name the file exactly, extension included
+            if crate::remote::is_url(&name) {
+                name
+            } else {
+                name + "." + crate::configs::FILE_EXTENSION
+            }
         } else {
             panic!("Expected module name on line {}.", self.line);
         };
-        ASTNode::Import(module_name)
+        // `import "module" (a, b)` only brings in the names listed
+        let mut names = None;
+        if self.current_token == Token::LParen {
+            self.consume(Token::LParen);
+            let names = names.insert(Vec::new());
+            while self.current_token != Token::RParen {
+                if let Token::Identifier(name) = self.current_token.clone() {
+                    self.consume(Token::Identifier(name.clone()));
+                    names.push(name);
+                    if self.current_token == Token::Comma {
+                        self.consume(Token::Comma);
+                    }
+                } else {
+                    panic!("Expected name to import on line {}.", self.line);
+                }
+            }
+            self.consume(Token::RParen);
+        }
+        ASTNode::Import(module_name, names)
+    }
+
+    pub fn parse_export(&mut self) -> ASTNode {
+        self.consume(Token::Export);
+        let start = self.current_span.start;
+        let node = match self.current_token {
+            Token::Function => self.parse_function_definition(),
+            Token::Identifier(_) => self.parse_assignment(),
+            _ => panic!("Expected function or assignment after 'export' on line {}.", self.line),
+        };
+        ASTNode::Export(Box::new(self.spanned(start, node)))
     }
 
     pub fn parse_if(&mut self) -> ASTNode {
@@ -440,29 +863,34 @@ impl Parser {
         self.consume(Token::LParen);
         let condition = self.parse_expression();
         self.consume(Token::RParen);
-        self.consume(Token::LBrace);
-        let then_branch = self.parse_block();
+        let then_branch = self.parse_braced_block();
         let else_branch = if self.current_token == Token::Else {
             self.consume(Token::Else);
-            self.consume(Token::LBrace);
-            let else_branch = self.parse_block();
-            Some(Box::new(ASTNode::Block(else_branch)))
+            Some(Box::new(self.parse_braced_block()))
         } else {
             None
         };
-        ASTNode::If(Box::new(condition), Box::new(ASTNode::Block(then_branch)), else_branch)
+        ASTNode::If(Box::new(condition), Box::new(then_branch), else_branch)
     }
 
-    pub fn parse_block(&mut self) -> Vec<ASTNode> {
+    // Parses `{ ... }` into a Block node spanning both braces
+    pub fn parse_braced_block(&mut self) -> Node {
+        let start = self.current_span.start;
+        self.consume(Token::LBrace);
+        let block = self.parse_block();
+        self.spanned(start, ASTNode::Block(block))
+    }
+
+    pub fn parse_block(&mut self) -> Vec<Node> {
         let mut nodes = Vec::new();
         while self.current_token != Token::RBrace && self.current_token != Token::EOF {
-            nodes.push(self.parse_statement());
+            nodes.push(stack::grow(|| self.parse_statement()));
         }
         self.consume(Token::RBrace);
         nodes
     }
 
-    pub fn parse(&mut self) -> Vec<ASTNode> {
+    pub fn parse(&mut self) -> Vec<Node> {
         let mut nodes = Vec::new();
         while self.current_token != Token::EOF {
             nodes.push(self.parse_statement());
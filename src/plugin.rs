@@ -0,0 +1,54 @@
+use crate::interpreter::Interpreter;
+use crate::value::Value;
+use libloading::{Library, Symbol};
+use std::ffi::{c_char, c_int, c_void, CStr};
+use std::path::Path;
+use std::ptr;
+
+// The plugin ABI (see include/qprime_plugin.h) only uses C types, so plugins
+// can be written in any language and built with any compiler version.
+// Functions take and return numbers; a non-zero status is an error, with an
+// optional static message.
+type PluginFunction = unsafe extern "C" fn(args: *const f64, count: usize, result: *mut f64, error: *mut *const c_char) -> c_int;
+type RegisterFunction = unsafe extern "C" fn(host: *mut c_void, name: *const c_char, function: PluginFunction);
+type InitFunction = unsafe extern "C" fn(host: *mut c_void, register: RegisterFunction);
+
+const INIT_SYMBOL: &[u8] = b"qprime_plugin_init";
+
+unsafe extern "C" fn register(host: *mut c_void, name: *const c_char, function: PluginFunction) {
+    let functions = &mut *(host as *mut Vec<(String, PluginFunction)>);
+    if !name.is_null() {
+        functions.push((CStr::from_ptr(name).to_string_lossy().into_owned(), function));
+    }
+}
+
+// Loads a shared library and registers the builtins it provides. The
+// library stays loaded until the process exits.
+pub fn load(path: &Path, interpreter: &mut Interpreter) {
+    let fail = |error: &dyn std::fmt::Display| -> ! { panic!("Failed to load plugin '{}': {}", path.display(), error) };
+    // SAFETY: loading runs the library's initializers; plugins are trusted
+    // code chosen by the user
+    let library = unsafe { Library::new(path) }.unwrap_or_else(|error| fail(&error));
+    let mut functions: Vec<(String, PluginFunction)> = Vec::new();
+    unsafe {
+        let init: Symbol<InitFunction> = library.get(INIT_SYMBOL).unwrap_or_else(|error| fail(&error));
+        init(&mut functions as *mut _ as *mut c_void, register);
+    }
+    std::mem::forget(library);
+    for (name, function) in functions {
+        let plugin_name = name.clone();
+        interpreter.register_native(&name, move |args: &[Value]| {
+            let args: Vec<f64> = args.iter().map(Value::to_f64).collect();
+            let mut result = 0.0;
+            let mut error = ptr::null();
+            // SAFETY: the arguments point to `count` numbers and the outputs
+            // to valid locations, as the ABI requires
+            let status = unsafe { function(args.as_ptr(), args.len(), &mut result, &mut error) };
+            match status {
+                0 => Ok(Value::from_f64(result)),
+                _ if error.is_null() => Err(format!("'{}' returned status {}", plugin_name, status)),
+                _ => Err(unsafe { CStr::from_ptr(error) }.to_string_lossy().into_owned()),
+            }
+        });
+    }
+}
@@ -0,0 +1,71 @@
+use crate::ast::Node;
+use crate::interpreter::{ExecutionHook, Interpreter};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+struct Entry {
+    builtin: bool,
+    calls: usize,
+    total: Duration,
+}
+
+// Records call counts and wall-clock time per user function and builtin.
+// Clones share their statistics, so one copy can be handed to the
+// interpreter as a hook and the other used to print the report.
+#[derive(Clone, Default)]
+pub struct Profiler {
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+    // Start time of each call currently executing, innermost last
+    started: Vec<Instant>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Summary table sorted by total time, slowest first
+    pub fn report(&self) -> String {
+        let entries = self.entries.lock().unwrap();
+        let mut rows: Vec<_> = entries.iter().collect();
+        rows.sort_by(|a, b| b.1.total.cmp(&a.1.total).then_with(|| a.0.cmp(b.0)));
+
+        let mut out = String::new();
+        writeln!(out, "{:<24} {:<9} {:>8} {:>12} {:>12}", "name", "kind", "calls", "total (ms)", "mean (us)").unwrap();
+        for (name, entry) in rows {
+            let kind = if entry.builtin { "builtin" } else { "function" };
+            let total = entry.total.as_secs_f64();
+            writeln!(
+                out,
+                "{:<24} {:<9} {:>8} {:>12.3} {:>12.3}",
+                name,
+                kind,
+                entry.calls,
+                total * 1e3,
+                total * 1e6 / entry.calls as f64
+            )
+            .unwrap();
+        }
+        out
+    }
+}
+
+impl ExecutionHook for Profiler {
+    fn before_statement(&mut self, _node: &Node, _interpreter: &Interpreter) {}
+
+    fn enter_function(&mut self, _name: &str, _builtin: bool) {
+        self.started.push(Instant::now());
+    }
+
+    fn exit_function(&mut self, name: &str, builtin: bool) {
+        let elapsed = self.started.pop().map_or(Duration::ZERO, |start| start.elapsed());
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.entry(name.to_string()).or_default();
+        entry.builtin = builtin;
+        entry.calls += 1;
+        entry.total += elapsed;
+    }
+}
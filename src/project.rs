@@ -0,0 +1,98 @@
+use crate::configs::{CONFIG_FILE, MAX_PRECISION, MODULES_DIR, SEARCH_PATH_VAR};
+use crate::constants::Constants;
+use crate::value::Numeric;
+use num_rational::BigRational;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// Defaults from `weather.toml`, merged with the command-line flags: flags
+// that switch something on can't be switched off again, values given on the
+// command line win, and lists are combined. The file may come from any
+// parent directory, so network access and plugins are only ever granted on
+// the command line.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub strict: bool,
+    pub numeric: Option<Numeric>,
+    pub precision: Option<usize>,
+    pub opt: bool,
+    pub trace: bool,
+    pub profile: bool,
+    pub max_depth: Option<usize>,
+    pub max_steps: Option<u64>,
+    pub timeout: Option<f64>,
+    // Directories searched for imports after the current directory
+    pub include: Vec<PathBuf>,
+    // Values for `secret()`, from the `[secrets]` table
+    pub secrets: HashMap<String, String>,
+    // Values from the `[constants]` table, as with `set_constant`
+    #[serde(rename = "constants")]
+    constant_values: HashMap<String, f64>,
+    #[serde(skip)]
+    pub constants: Constants,
+}
+
+impl Config {
+    // Reads the nearest `weather.toml`, or the defaults if there is none.
+    // Paths in the file are relative to its directory.
+    pub fn discover() -> Self {
+        match nearest(CONFIG_FILE, Path::is_file) {
+            Some(path) => Self::load(&path),
+            None => Self::default(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Self {
+        let text = fs::read_to_string(path).unwrap_or_else(|error| panic!("Failed to read '{}': {}", path.display(), error));
+        let mut config: Config = toml::from_str(&text).unwrap_or_else(|error| match error.span() {
+            Some(span) => panic!("Invalid configuration in '{}' on line {}: {}", path.display(), text[..span.start].matches('\n').count() + 1, error.message().trim().replace('\n', ", ")),
+            None => panic!("Invalid configuration in '{}': {}", path.display(), error.message().trim().replace('\n', ", ")),
+        });
+        if config.precision.is_some_and(|precision| !(1..=MAX_PRECISION).contains(&precision)) {
+            panic!("Invalid configuration in '{}': precision must be between 1 and {}", path.display(), MAX_PRECISION);
+        }
+        if config.timeout.is_some_and(|timeout| !(timeout > 0.0 && Duration::try_from_secs_f64(timeout).is_ok())) {
+            panic!("Invalid configuration in '{}': timeout must be a positive number of seconds", path.display());
+        }
+        for (name, value) in &config.constant_values {
+            let value = BigRational::from_float(*value).unwrap_or_else(|| panic!("Invalid configuration in '{}': the constant '{}' must be finite", path.display(), name));
+            config.constants.set(name, value).unwrap_or_else(|error| panic!("Invalid configuration in '{}': {}", path.display(), error));
+        }
+        let dir = path.parent().unwrap_or(Path::new("."));
+        for path in config.include.iter_mut() {
+            *path = dir.join(&*path);
+        }
+        config
+    }
+}
+
+// `name` in the current directory or the closest of its parents that has it
+fn nearest(name: &str, found: impl Fn(&Path) -> bool) -> Option<PathBuf> {
+    let cwd = env::current_dir().ok()?;
+    cwd.ancestors().map(|dir| dir.join(name)).find(|path| found(path))
+}
+
+// Directory holding the project's `weather.toml`, or the current directory
+// when there is none
+pub fn root() -> PathBuf {
+    nearest(CONFIG_FILE, Path::is_file)
+        .and_then(|path| path.parent().map(Path::to_path_buf))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+// Directories searched for imports not found in the current directory:
+// `include` (from --include and the configuration), the installed packages
+// and then `WEATHER_PATH`
+pub fn search_paths(include: &[PathBuf]) -> Vec<PathBuf> {
+    let mut paths = include.to_vec();
+    paths.extend(nearest(MODULES_DIR, Path::is_dir));
+    if let Some(value) = env::var_os(SEARCH_PATH_VAR) {
+        paths.extend(env::split_paths(&value).filter(|path| !path.as_os_str().is_empty()));
+    }
+    paths
+}
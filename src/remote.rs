@@ -0,0 +1,105 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::configs::MODULE_CACHE_DIR;
+#[cfg(not(target_arch = "wasm32"))]
+use sha2::{Digest, Sha256};
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
+
+// Longest wait for a server before a download fails
+#[cfg(not(target_arch = "wasm32"))]
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+// Imports starting with one of these are downloaded instead of read from disk
+pub fn is_url(name: &str) -> bool {
+    name.starts_with("https://") || name.starts_with("http://")
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn sha256(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+// Downloaded modules are kept under the user's cache directory: pinned ones
+// by their content hash, the others by a hash of their URL
+#[cfg(not(target_arch = "wasm32"))]
+fn cache_path(address: &str, pin: Option<&str>) -> Option<PathBuf> {
+    let dir = dirs::cache_dir()?.join(MODULE_CACHE_DIR);
+    Some(match pin {
+        Some(hash) => dir.join("sha256").join(hash),
+        None => dir.join("url").join(sha256(address.as_bytes())),
+    })
+}
+
+// Source of the module at `url`. A `#sha256=<hex>` suffix pins the module:
+// a download with different content is rejected. Once downloaded, a module
+// is read from the cache, so scripts keep working offline; delete the cache
+// to fetch unpinned modules again. Unpinned modules are only downloaded
+// with `allow_net`, and never over plain HTTP.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn fetch(url: &str, allow_net: bool) -> Result<String, String> {
+    let (address, pin) = match url.split_once("#sha256=") {
+        Some((address, hash)) => (address, Some(hash.to_ascii_lowercase())),
+        None => (url, None),
+    };
+    if pin.is_none() && address.starts_with("http://") {
+        return Err(format!("'{}' is not downloaded over HTTPS, so it must be pinned with #sha256=<hash>", address));
+    }
+    let cache = cache_path(address, pin.as_deref());
+    if let Some(source) = cache.as_ref().and_then(|path| fs::read_to_string(path).ok()) {
+        if pin.as_ref().is_none_or(|hash| sha256(source.as_bytes()) == *hash) {
+            return Ok(source);
+        }
+    }
+    if pin.is_none() && !allow_net {
+        return Err(format!("Importing '{}' needs network access, which scripts only have when run with --allow-net, or a #sha256=<hash> pin", address));
+    }
+    let failed = |error: &dyn std::fmt::Display| format!("Failed to download '{}': {}", address, error);
+    let response = ureq::get(address).timeout(TIMEOUT).call().map_err(|error| failed(&error))?;
+    let source = response.into_string().map_err(|error| failed(&error))?;
+    if let Some(hash) = &pin {
+        let actual = sha256(source.as_bytes());
+        if actual != *hash {
+            return Err(format!("'{}' has SHA-256 {}, but the import pins {}", address, actual, hash));
+        }
+    }
+    // A module that can't be cached still runs; it is downloaded again next time
+    if let Some(path) = &cache {
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let _ = fs::write(path, &source);
+    }
+    Ok(source)
+}
+
+// Hourly forecast of the variables the weather builtins take, in their
+// units (°C, %, hPa, m/s, mm), from Open-Meteo, which needs no API key
+pub fn forecast_url(latitude: f64, longitude: f64) -> String {
+    format!(
+        "https://api.open-meteo.com/v1/forecast?latitude={}&longitude={}&hourly={}&wind_speed_unit=ms&timeformat=unixtime",
+        latitude, longitude, "temperature_2m,relative_humidity_2m,dew_point_2m,surface_pressure,wind_speed_10m,wind_direction_10m,precipitation"
+    )
+}
+
+// Body of the response to a GET request to `url`, for `fetch`; nothing is
+// cached, so scripts see live data
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get(url: &str) -> Result<String, String> {
+    let failed = |error: &dyn std::fmt::Display| format!("Failed to fetch '{}': {}", url, error);
+    let response = ureq::get(url).timeout(TIMEOUT).call().map_err(|error| failed(&error))?;
+    response.into_string().map_err(|error| failed(&error))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn get(url: &str) -> Result<String, String> {
+    Err(format!("Cannot fetch '{}' in the browser", url))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn fetch(url: &str, _allow_net: bool) -> Result<String, String> {
+    Err(format!("Cannot download '{}' in the browser", url))
+}
@@ -0,0 +1,244 @@
+use crate::ast::{ASTNode, Node};
+use crate::configs::HISTORY_FILE;
+use crate::dump::dump_ast;
+use crate::error::panic_message;
+use crate::incremental::{is_complete, IncrementalParser};
+use crate::interpreter::Interpreter;
+use crate::lexer::{call_name, Lexer, CALL_NAMES, KEYWORDS};
+use crate::parser::Parser;
+use crate::serialize::Snapshot;
+use crate::token::Token;
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::collections::HashMap;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+
+const PROMPT: &str = ">>> ";
+const CONTINUATION_PROMPT: &str = "... ";
+
+const HELP: &str = "Meta-commands:
+  :vars         show all variables
+  :funcs        show all functions
+  :ast <code>   show the syntax tree of a statement or expression
+  :load <file>  run a script in this session, or resume a saved session
+  :save <file>  save the session's variables and functions
+  :reset        forget all variables and functions
+  :quit         leave the REPL
+  :help         show this help";
+
+enum Command {
+    Continue,
+    Quit,
+}
+
+// Tab-completes keywords, builtins, constants and the session's variables
+// and functions
+#[derive(Default)]
+struct ReplHelper {
+    names: Vec<String>,
+}
+
+impl ReplHelper {
+    fn refresh(&mut self, interpreter: &Interpreter) {
+        self.names = KEYWORDS.iter().chain(CALL_NAMES).map(|(name, _)| name.to_string()).collect();
+        self.names.extend(interpreter.variables().keys().cloned());
+        self.names.extend(interpreter.functions().keys().cloned());
+        self.names.sort();
+        self.names.dedup();
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let start = line[..pos]
+            .rfind(|ch: char| !(ch.is_alphanumeric() || ch == '_'))
+            .map_or(0, |index| index + 1);
+        let prefix = &line[start..pos];
+        let candidates = self.names
+            .iter()
+            .filter(|name| !prefix.is_empty() && name.starts_with(prefix))
+            .map(|name| Pair { display: name.clone(), replacement: name.clone() })
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+fn history_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(HISTORY_FILE))
+}
+
+// Reads statements from stdin and runs them against one interpreter, so
+// variables and functions carry over between entries. Bare expressions have
+// their value printed. Input continues over several lines until brackets
+// and strings are closed. History is kept in `~/.qprime_history`.
+pub fn run() {
+    // Errors are reported as one line instead of aborting the session
+    panic::set_hook(Box::new(|info| eprintln!("error: {}", panic_message(info.payload()))));
+
+    let mut interpreter = Interpreter::new();
+    let mut editor: Editor<ReplHelper, DefaultHistory> = Editor::new().expect("Failed to start the line editor");
+    let mut helper = ReplHelper::default();
+    helper.refresh(&interpreter);
+    editor.set_helper(Some(helper));
+    let history = history_path();
+    if let Some(path) = &history {
+        // A missing history file just means a first session
+        let _ = editor.load_history(path);
+    }
+    // Parses of the scripts run with `:load`, so loading one again after
+    // editing it only re-parses the statements that changed
+    let mut parsers = HashMap::new();
+    let mut entry = String::new();
+    loop {
+        let line = match editor.readline(if entry.is_empty() { PROMPT } else { CONTINUATION_PROMPT }) {
+            Ok(line) => line,
+            // Ctrl-C abandons the entry being typed
+            Err(ReadlineError::Interrupted) => {
+                entry.clear();
+                continue;
+            }
+            Err(_) => break,
+        };
+        if entry.is_empty() && line.trim_start().starts_with(':') {
+            let _ = editor.add_history_entry(line.trim());
+            let command = panic::catch_unwind(AssertUnwindSafe(|| meta_command(&mut interpreter, &mut parsers, line.trim())));
+            if let Ok(Command::Quit) = command {
+                break;
+            }
+            if let Some(helper) = editor.helper_mut() {
+                helper.refresh(&interpreter);
+            }
+            continue;
+        }
+        entry.push_str(&line);
+        entry.push('\n');
+        if entry.trim().is_empty() {
+            entry.clear();
+            continue;
+        }
+        // An entry that fails to lex is complete; running it reports the error
+        if !panic::catch_unwind(|| is_complete(&entry)).unwrap_or(true) {
+            continue;
+        }
+        let source = std::mem::take(&mut entry);
+        let _ = editor.add_history_entry(source.trim_end());
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| run_entry(&mut interpreter, source)));
+        if let Some(helper) = editor.helper_mut() {
+            helper.refresh(&interpreter);
+        }
+    }
+    if let Some(path) = &history {
+        if let Err(error) = editor.save_history(path) {
+            eprintln!("Failed to save history to {}: {}", path.display(), error);
+        }
+    }
+}
+
+fn meta_command(interpreter: &mut Interpreter, parsers: &mut HashMap<String, IncrementalParser>, line: &str) -> Command {
+    let (name, argument) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let argument = argument.trim();
+    match name {
+        ":vars" => {
+            let mut variables: Vec<_> = interpreter.variables().into_iter().collect();
+            variables.sort_by(|(left, _), (right, _)| left.cmp(right));
+            for (name, value) in variables {
+                println!("{} = {}", name, value);
+            }
+        }
+        ":funcs" => {
+            let mut names: Vec<_> = interpreter.functions().keys().collect();
+            names.sort();
+            for name in names {
+                if let ASTNode::Function(name, params, _) = &interpreter.functions()[name].node {
+                    println!("{}({})", name, params.join(", "));
+                }
+            }
+        }
+        ":ast" if !argument.is_empty() => print!("{}", dump_ast(&parse_entry(argument.to_string()))),
+        ":load" if !argument.is_empty() => {
+            let source = fs::read_to_string(argument).unwrap_or_else(|error| panic!("Failed to read '{}': {}", argument, error));
+            match Snapshot::from_json(&source) {
+                Ok(snapshot) => interpreter.restore(snapshot),
+                Err(_) => interpreter.interpret(parsers.entry(argument.to_string()).or_default().parse(&source)),
+            }
+        }
+        ":save" if !argument.is_empty() => {
+            fs::write(argument, interpreter.snapshot().to_json()).unwrap_or_else(|error| panic!("Failed to write '{}': {}", argument, error));
+        }
+        ":reset" => *interpreter = Interpreter::new(),
+        ":quit" => return Command::Quit,
+        ":help" => println!("{}", HELP),
+        ":ast" | ":load" | ":save" => println!("Usage: {} <{}>", name, if name == ":ast" { "code" } else { "file" }),
+        _ => println!("Unknown command '{}', type ':help' for a list of commands", name),
+    }
+    Command::Continue
+}
+
+// Parses an entry as statements, or as a single expression
+fn parse_entry(source: String) -> Vec<Node> {
+    if is_statement(&source) {
+        Parser::new(Lexer::new(source)).parse()
+    } else {
+        vec![Parser::new(Lexer::new(source)).parse_standalone_expression()]
+    }
+}
+
+fn run_entry(interpreter: &mut Interpreter, source: String) {
+    if is_statement(&source) {
+        let nodes = Parser::new(Lexer::new(source)).parse();
+        interpreter.interpret(nodes);
+    } else {
+        let expression = Parser::new(Lexer::new(source)).parse_standalone_expression();
+        let value = interpreter.evaluate(&expression);
+        println!("{}", value.rounded(interpreter.precision()));
+    }
+}
+
+// Whether the entry starts with a statement rather than being an expression
+fn is_statement(source: &str) -> bool {
+    let mut lexer = Lexer::new(source.to_string());
+    let token = match lexer.next_token().node {
+        Token::Identifier(name) => match lexer.next_token().node {
+            Token::Assign => return true,
+            // Statements such as `seed(1)` are named like variables
+            Token::LParen => call_name(&name).unwrap_or(Token::Identifier(name)),
+            _ => return false,
+        },
+        token => token,
+    };
+    matches!(
+        token,
+        Token::Print
+            | Token::If
+            | Token::Function
+            | Token::Import
+            | Token::Export
+            | Token::Call
+            | Token::Assert
+            | Token::AssertApprox
+            | Token::SetConstant
+            | Token::WriteJson
+            | Token::Seed
+            | Token::Sleep
+            | Token::LBrace
+    )
+}
@@ -0,0 +1,82 @@
+use crate::ast::Node;
+use crate::value::Value;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Exact numbers are written as `"numerator/denominator"` strings (or just
+// `"numerator"` for integers) so no precision is lost, whatever the format
+pub mod rational {
+    use num_rational::BigRational;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &BigRational, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BigRational, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(|_| D::Error::custom(format!("invalid number '{}'", text)))
+    }
+}
+
+// Complex numbers are a `[real, imaginary]` pair of exact numbers
+pub mod complex {
+    use num_complex::Complex;
+    use num_rational::BigRational;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct Parts(#[serde(with = "super::rational")] BigRational, #[serde(with = "super::rational")] BigRational);
+
+    pub fn serialize<S: Serializer>(value: &Complex<BigRational>, serializer: S) -> Result<S::Ok, S::Error> {
+        Parts(value.re.clone(), value.im.clone()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Complex<BigRational>, D::Error> {
+        let Parts(re, im) = Parts::deserialize(deserializer)?;
+        Ok(Complex::new(re, im))
+    }
+}
+
+// Floats are strings, since JSON has no NaN or infinity
+pub mod float {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(|_| D::Error::custom(format!("invalid number '{}'", text)))
+    }
+}
+
+// Variables and function definitions of an interpreter, saved so a session
+// can be resumed later (see `Interpreter::snapshot`)
+#[derive(Default, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub variables: BTreeMap<String, Value>,
+    pub functions: Vec<Node>,
+}
+
+impl Snapshot {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Failed to serialize the session")
+    }
+
+    pub fn from_json(json: &str) -> Result<Snapshot, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+// The parsed program as JSON, for caching and external tools
+pub fn to_json(nodes: &[Node]) -> String {
+    serde_json::to_string_pretty(nodes).expect("Failed to serialize the syntax tree")
+}
+
+pub fn from_json(json: &str) -> Result<Vec<Node>, serde_json::Error> {
+    serde_json::from_str(json)
+}
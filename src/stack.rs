@@ -0,0 +1,11 @@
+// Recursive walks over the syntax tree (parsing, evaluation, checks and
+// dropping it) call `grow`, which moves them onto a new heap-allocated
+// stack segment when the current one is nearly used up. Deeply nested
+// expressions, such as long generated chains of `+`, then run instead of
+// overflowing the native stack.
+const RED_ZONE: usize = 128 * 1024;
+const SEGMENT_SIZE: usize = 4 * 1024 * 1024;
+
+pub fn grow<R>(f: impl FnOnce() -> R) -> R {
+    stacker::maybe_grow(RED_ZONE, SEGMENT_SIZE, f)
+}
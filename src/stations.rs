@@ -0,0 +1,58 @@
+use crate::value::Value;
+use std::collections::BTreeMap;
+
+// Airports and their weather stations, one per line with a header line:
+// ICAO id, WMO id, name, latitude and longitude in degrees (east and north
+// positive) and elevation in m
+const STATIONS: &str = include_str!("../data/stations.csv");
+
+pub struct Station<'a> {
+    pub icao: &'a str,
+    pub wmo: &'a str,
+    pub name: &'a str,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub elevation: f64,
+}
+
+impl Station<'_> {
+    // The station as a map, as `station()` returns it
+    pub fn to_value(&self) -> Value {
+        let entries = [
+            ("icao", Value::Str(self.icao.to_string())),
+            ("wmo", Value::Str(self.wmo.to_string())),
+            ("name", Value::Str(self.name.to_string())),
+            ("lat", Value::from_f64(self.latitude)),
+            ("lon", Value::from_f64(self.longitude)),
+            ("elevation", Value::from_f64(self.elevation)),
+        ];
+        Value::Map(entries.into_iter().map(|(key, value)| (key.to_string(), value)).collect::<BTreeMap<_, _>>())
+    }
+}
+
+pub fn all() -> impl Iterator<Item = Station<'static>> {
+    STATIONS.lines().skip(1).filter(|line| !line.is_empty()).map(|line| {
+        let fields: Vec<&str> = line.split(',').collect();
+        let number = |index: usize| fields[index].parse().expect("Invalid number in the station table");
+        Station { icao: fields[0], wmo: fields[1], name: fields[2], latitude: number(3), longitude: number(4), elevation: number(5) }
+    })
+}
+
+// A station by its ICAO id, in any case, or its five-digit WMO id
+pub fn find(id: &str) -> Option<Station<'static>> {
+    all().find(|station| station.icao.eq_ignore_ascii_case(id) || station.wmo == id)
+}
+
+// The table as a Python dict from each ICAO id to the station's fields,
+// for the transpiled `station`
+pub fn python_table() -> String {
+    let entries: Vec<String> = all()
+        .map(|station| {
+            format!(
+                "    \"{}\": {{\"icao\": \"{}\", \"wmo\": \"{}\", \"name\": {:?}, \"lat\": {}, \"lon\": {}, \"elevation\": {}}},",
+                station.icao, station.icao, station.wmo, station.name, station.latitude, station.longitude, station.elevation
+            )
+        })
+        .collect();
+    format!("_STATIONS = {{\n{}\n}}\n", entries.join("\n"))
+}
@@ -0,0 +1,13 @@
+// Standard library modules, compiled into the binary so every installation
+// has them; scripts import them as `import "std/thermo"`
+const MODULES: &[(&str, &str)] = &[
+    ("std/thermo.qpr", include_str!("../std/thermo.qpr")),
+    ("std/wind.qpr", include_str!("../std/wind.qpr")),
+    ("std/pressure.qpr", include_str!("../std/pressure.qpr")),
+    ("std/stats.qpr", include_str!("../std/stats.qpr")),
+];
+
+// Source of a standard library module, by import path
+pub fn source(name: &str) -> Option<&'static str> {
+    MODULES.iter().find(|(path, _)| *path == name).map(|(_, source)| *source)
+}
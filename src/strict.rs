@@ -0,0 +1,137 @@
+use crate::ast::{ASTNode, Node};
+use crate::lexer::keyword;
+use crate::span::Span;
+use crate::stack;
+use crate::token::Token;
+use std::collections::HashSet;
+
+// Static checks enabled by `--strict`, run before any code executes:
+// - `if` conditions and `and`/`or`/`not` operands must be comparisons or
+//   logical expressions instead of relying on implicit truthiness
+// - variables must be assigned before they are read
+// - names may not shadow builtins (e.g. `pi` for the `_pi_` constant)
+pub fn check(nodes: &[Node]) -> Vec<String> {
+    let mut checker = StrictChecker {
+        globals: HashSet::new(),
+        imported: false,
+        errors: Vec::new(),
+    };
+    collect_assignments(nodes, &mut checker.globals);
+    let mut defined = HashSet::new();
+    for node in nodes {
+        checker.check_node(node, &mut defined);
+    }
+    checker.errors
+}
+
+struct StrictChecker {
+    // Every name assigned at the top level, visible to function bodies
+    globals: HashSet<String>,
+    // Set once an import has run; modules aren't read here, so any name
+    // may have come from one
+    imported: bool,
+    errors: Vec<String>,
+}
+
+impl StrictChecker {
+    fn error(&mut self, span: Span, message: String) {
+        self.errors.push(format!("Strict mode: {} on line {}, column {}.", message, span.start.line, span.start.column));
+    }
+
+    fn check_name(&mut self, name: &str, span: Span) {
+        if let Some(builtin) = shadowed_builtin(name) {
+            self.error(span, format!("'{}' shadows the builtin '{}'", name, builtin));
+        }
+    }
+
+    fn check_condition(&mut self, node: &Node) {
+        if !is_boolean(&node.node) {
+            self.error(node.span, "condition is not a comparison or logical expression".to_string());
+        }
+    }
+
+    fn check_node(&mut self, node: &Node, defined: &mut HashSet<String>) {
+        stack::grow(|| {
+            match &node.node {
+                ASTNode::Identifier(name) => {
+                    if !defined.contains(name) && !self.imported {
+                        self.error(node.span, format!("variable '{}' is used before it is assigned", name));
+                    }
+                }
+                ASTNode::Assignment(name, value) => {
+                    self.check_node(value, defined);
+                    self.check_name(name, node.span);
+                    defined.insert(name.clone());
+                }
+                ASTNode::Function(name, params, body) => {
+                    self.check_name(name, node.span);
+                    let mut scope = self.globals.clone();
+                    for param in params {
+                        self.check_name(param, node.span);
+                        scope.insert(param.clone());
+                    }
+                    self.check_node(body, &mut scope);
+                }
+                ASTNode::Import(_, Some(names)) => defined.extend(names.iter().cloned()),
+                ASTNode::Import(_, None) => self.imported = true,
+                ASTNode::If(condition, _, _) => {
+                    self.check_condition(condition);
+                    for child in node.node.children() {
+                        self.check_node(child, defined);
+                    }
+                }
+                ASTNode::BinaryOp(left, Token::And | Token::Or, right) => {
+                    self.check_condition(left);
+                    self.check_condition(right);
+                    self.check_node(left, defined);
+                    self.check_node(right, defined);
+                }
+                ASTNode::UnaryOp(Token::Not, operand) => {
+                    self.check_condition(operand);
+                    self.check_node(operand, defined);
+                }
+                other => {
+                    for child in other.children() {
+                        self.check_node(child, defined);
+                    }
+                }
+            }
+        })
+    }
+}
+
+fn collect_assignments<'a>(nodes: impl IntoIterator<Item = &'a Node>, names: &mut HashSet<String>) {
+    for node in nodes {
+        match &node.node {
+            ASTNode::Assignment(name, _) => {
+                names.insert(name.clone());
+            }
+            ASTNode::Function(..) => {}
+            other => collect_assignments(other.children(), names),
+        }
+    }
+}
+
+fn is_boolean(node: &ASTNode) -> bool {
+    match node {
+        ASTNode::BinaryOp(_, op, _) => matches!(
+            op,
+            Token::EqualEqual
+                | Token::NotEqual
+                | Token::LessThan
+                | Token::LessEqual
+                | Token::GreaterThan
+                | Token::GreaterEqual
+                | Token::And
+                | Token::Or
+        ),
+        ASTNode::UnaryOp(Token::Not, _) | ASTNode::IsNaN(_) | ASTNode::GreaterThan(..) | ASTNode::LessThan(..) => true,
+        _ => false,
+    }
+}
+
+// Builtin constants are spelled `_name_`, so a bare `pi` or `g` shadows one
+pub fn shadowed_builtin(name: &str) -> Option<String> {
+    let constant = format!("_{}_", name);
+    keyword(&constant).map(|_| constant)
+}
@@ -0,0 +1,56 @@
+use crate::configs::FILE_EXTENSION;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Suffix of the file stem that marks a script as a test, e.g. `dewpoint_test.qpr`
+const TEST_SUFFIX: &str = "_test";
+
+// Finds test scripts under `dir`, recursively and in path order
+pub fn discover(dir: &Path) -> Vec<PathBuf> {
+    let mut tests = Vec::new();
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .unwrap_or_else(|error| panic!("Failed to read directory '{}': {}", dir.display(), error))
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            tests.extend(discover(&path));
+        } else if is_test(&path) {
+            tests.push(path);
+        }
+    }
+    tests
+}
+
+fn is_test(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == FILE_EXTENSION)
+        && path.file_stem().and_then(|stem| stem.to_str()).is_some_and(|stem| stem.ends_with(TEST_SUFFIX))
+}
+
+// Runs each test in its own process so a failing assertion only stops that
+// script. Prints one line per test and a summary; returns the failure count.
+pub fn run(tests: &[PathBuf]) -> usize {
+    let program = env::current_exe().expect("Failed to locate the interpreter executable");
+    let mut failed = 0;
+    for test in tests {
+        let output = Command::new(&program)
+            .arg(test)
+            .output()
+            .expect("Failed to run test script");
+        if output.status.success() {
+            println!("test {} ... ok", test.display());
+        } else {
+            failed += 1;
+            println!("test {} ... FAILED", test.display());
+            for line in String::from_utf8_lossy(&output.stderr).lines() {
+                println!("    {}", line);
+            }
+        }
+    }
+    println!();
+    println!("test result: {} passed; {} failed", tests.len() - failed, failed);
+    failed
+}
@@ -0,0 +1,24 @@
+use jiff::tz::TimeZone;
+use jiff::{Timestamp, Zoned};
+
+// Local time in IANA time zones such as `America/Denver`, from the tz
+// database bundled into the binary so results don't depend on the system
+
+fn zoned(timestamp: f64, zone: &str) -> Result<Zoned, String> {
+    let zone = TimeZone::get(zone).map_err(|_| format!("Unknown time zone '{}', expected an IANA name such as America/Denver", zone))?;
+    // Fractions of a second are dropped, as clocks show them
+    let moment = Timestamp::from_second(timestamp.floor() as i64).map_err(|_| format!("The timestamp {} is out of range", timestamp))?;
+    Ok(moment.to_zoned(zone))
+}
+
+// A Unix timestamp in seconds as ISO 8601 local time with its UTC offset,
+// e.g. `2024-07-04T06:00:00-06:00`
+pub fn local(timestamp: f64, zone: &str) -> Result<String, String> {
+    Ok(zoned(timestamp, zone)?.strftime("%Y-%m-%dT%H:%M:%S%:z").to_string())
+}
+
+// Seconds the zone is ahead of UTC at a Unix timestamp, negative west of
+// Greenwich; daylight saving time is included
+pub fn offset(timestamp: f64, zone: &str) -> Result<i32, String> {
+    Ok(zoned(timestamp, zone)?.offset().seconds())
+}
@@ -1,11 +1,19 @@
 use num_rational::BigRational;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum Token {
-    Float(BigRational),
+    Float(#[serde(with = "crate::serialize::rational")] BigRational),
+    // Number with a unit written right after it, e.g. `25C`
+    Quantity(#[serde(with = "crate::serialize::rational")] BigRational, String),
+    NaN,
+    Inf,
+    Nil,
     Identifier(String),
     Function,
     Import,
+    Export,
     Call,
     Plus,
     Minus,
@@ -15,6 +23,13 @@ pub enum Token {
     Modulo,
     GreaterThan,
     LessThan,
+    GreaterEqual,
+    LessEqual,
+    EqualEqual,
+    NotEqual,
+    And,
+    Or,
+    Not,
     Assign,
     Comma,
     Print,
@@ -22,16 +37,89 @@ pub enum Token {
     RBrace,
     LParen,
     RParen,
+    LBracket,
+    RBracket,
     If,
     Else,
     StringLiteral(String),
     DewPoint,
+    ApparentTemp,
+    Svp,
+    MixingRatio,
+    SpecificHumidity,
+    PressureAtAltitude,
+    Isa,
+    Dalr,
+    Malr,
+    DryAdiabat,
+    Et0,
+    Hdd,
+    Cdd,
+    WindComponents,
+    WindFromComponents,
+    MmToIn,
+    InToMm,
+    MmhToInh,
+    InhToMmh,
+    Accumulation,
+    RunningTotal,
+    Events,
+    Percentile,
+    PercentileRank,
+    Anomaly,
+    RollingMean,
+    Spi,
+    Windrose,
+    Slr,
+    Snowfall,
+    VisConvert,
+    FogCategory,
+    SolarZenith,
+    SolarDeclination,
+    UvIndex,
+    Coriolis,
+    PrecipitableWater,
+    SpeedOfSound,
+    Altimeter,
+    RhFromWetbulb,
+    Haversine,
+    Bearing,
+    ParseCoord,
+    FormatCoord,
+    Station,
+    Cloudbase,
+    Convert,
     FToC,
     CToF,
     CToK,
     KToC,
     FToK,
     KToF,
+    IsNaN,
+    Assert,
+    AssertApprox,
+    SetConstant,
+    Args,
+    Len,
+    Env,
+    Secret,
+    ParseJson,
+    ToJson,
+    ReadJson,
+    ReadGrib,
+    ReadNc,
+    ToTimezone,
+    UtcOffset,
+    Rand,
+    RandInt,
+    RandN,
+    Seed,
+    Sleep,
+    WriteJson,
+    Fetch,
+    Forecast,
+    Format,
+    Pmap,
     Pi,
     Kelvin,
     RD,
@@ -42,6 +130,12 @@ pub enum Token {
     RhoAir,
     RhoWater,
     G,
+    Omega,
+    Rv,
+    Epsilon,
+    Sigma,
+    Solar,
+    Lf,
     PauliX,
     PauliY,
     PauliZ,
@@ -0,0 +1,57 @@
+use crate::ast::{ASTNode, Node};
+use crate::format::{expression, import};
+use crate::interpreter::{ExecutionHook, Interpreter};
+use crate::span::Span;
+use crate::value::Value;
+
+// Prints each statement to stderr as it executes, followed by the binding it
+// produced or the value of its `if` condition
+#[derive(Default)]
+pub struct Tracer {
+    // Variable assigned by each statement currently executing, innermost last
+    assignments: Vec<Option<String>>,
+}
+
+impl Tracer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn describe(node: &Node) -> String {
+    match &node.node {
+        ASTNode::If(condition, _, _) => format!("if ({})", expression(condition)),
+        ASTNode::Function(name, params, _) => format!("fn {}({})", name, params.join(", ")),
+        ASTNode::Import(module, names) => import(module, names.as_deref()),
+        ASTNode::Call(..) => format!("call({})", expression(node)),
+        _ => expression(node),
+    }
+}
+
+fn indent(interpreter: &Interpreter) -> String {
+    "  ".repeat(interpreter.call_depth())
+}
+
+impl ExecutionHook for Tracer {
+    fn before_statement(&mut self, node: &Node, interpreter: &Interpreter) {
+        eprintln!("[trace] {}line {}: {}", indent(interpreter), node.span.start.line, describe(node));
+        let assigned = match &node.node {
+            ASTNode::Assignment(name, _) => Some(name.clone()),
+            _ => None,
+        };
+        self.assignments.push(assigned);
+    }
+
+    fn after_statement(&mut self, _span: Span, interpreter: &Interpreter) {
+        if let Some(Some(name)) = self.assignments.pop() {
+            if let Some(value) = interpreter.variable(&name) {
+                eprintln!("[trace] {}  => {} = {}", indent(interpreter), name, value);
+            }
+        }
+    }
+
+    fn condition_evaluated(&mut self, _span: Span, result: &Value, interpreter: &Interpreter) {
+        let branch = if result.is_truthy() { "true" } else { "false" };
+        eprintln!("[trace] {}  => condition is {} ({})", indent(interpreter), branch, result);
+    }
+}
@@ -0,0 +1,1009 @@
+use crate::ast::{ASTNode, Node};
+use crate::format::number;
+use crate::interpreter::{import_dir, locate_import};
+use crate::lexer::{call_name, Lexer};
+use crate::parser::Parser;
+use crate::remote::is_url;
+use crate::stack;
+use crate::stations;
+use crate::stdlib;
+use crate::token::Token;
+use crate::units;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+const INDENT: &str = "    ";
+
+// Python versions of the builtins and constants, emitted at the top of every
+// transpiled script so it runs on its own
+const PYTHON_RUNTIME: &str = r#"# Runtime for scripts transpiled from Q'
+import fractions as _fractions
+import json as _json
+import math as _math
+import os as _os
+import random as _random
+import sys as _sys
+
+nan = _math.nan
+inf = _math.inf
+nil = None
+_pi_ = _math.pi
+_kelvin_ = 273.15
+_rd_ = 287.05
+_cp_ = 1005
+_p0_ = 101325
+_lv_ = 2260000
+_cw_ = 4184
+_rho_air_ = 1200
+_rho_water_ = 1000
+_g_ = 9.81
+_omega_ = 7.2921159e-5
+_rv_ = 461.5
+_sigma_ = 5.670374419e-8
+_solar_ = 1361
+_lf_ = 334000
+_PRECISION = 6
+
+
+def _parse_arg(text):
+    for parse in (int, float):
+        try:
+            return parse(text)
+        except ValueError:
+            pass
+    return text
+
+
+_ARGS = [_parse_arg(arg) for arg in _sys.argv[1:]]
+
+
+def args():
+    return list(_ARGS)
+
+
+def env(name):
+    return _os.environ.get(name)
+
+
+def secret(name):
+    return _os.environ[name.upper()]
+
+
+def _show(value, digits=_PRECISION):
+    if value is None:
+        return "nil"
+    if isinstance(value, bool):
+        return "1" if value else "0"
+    if isinstance(value, int):
+        value = float(value)
+    if isinstance(value, float):
+        if _math.isnan(value):
+            return "NaN"
+        value = float("%.*e" % (digits - 1, value))
+        if value.is_integer():
+            return str(int(value))
+        return repr(value)
+    if isinstance(value, complex):
+        return _show(value.real, digits) + ("+" if value.imag >= 0 else "-") + _show(abs(value.imag), digits) + "i"
+    if isinstance(value, list):
+        return "[" + ", ".join('"' + item + '"' if isinstance(item, str) else _show(item, digits) for item in value) + "]"
+    if isinstance(value, dict):
+        return "{" + ", ".join('"%s": %s' % (key, '"' + item + '"' if isinstance(item, str) else _show(item, digits)) for key, item in sorted(value.items())) + "}"
+    return str(value)
+
+
+def _index(value, key):
+    return value[key] if isinstance(value, dict) else value[int(key)]
+
+
+def _json_value(value):
+    if isinstance(value, bool):
+        return int(value)
+    if isinstance(value, list):
+        return [_json_value(item) for item in value]
+    if isinstance(value, dict):
+        return {key: _json_value(item) for key, item in value.items()}
+    return value
+
+
+def parse_json(text):
+    return _json_value(_json.loads(text))
+
+
+def to_json(value):
+    return _json.dumps(value, separators=(",", ":"), sort_keys=True, ensure_ascii=False, allow_nan=False)
+
+
+def fetch(url):
+    import urllib.request as _request
+    with _request.urlopen(url, timeout=30) as response:
+        text = response.read().decode("utf-8")
+    try:
+        return parse_json(text)
+    except ValueError:
+        return text
+
+
+def forecast(latitude, longitude):
+    return fetch("https://api.open-meteo.com/v1/forecast?latitude=%s&longitude=%s&hourly=%s&wind_speed_unit=ms&timeformat=unixtime"
+                 % (latitude, longitude, "temperature_2m,relative_humidity_2m,dew_point_2m,surface_pressure,wind_speed_10m,wind_direction_10m,precipitation"))
+
+
+def readjson(path):
+    with open(path, encoding="utf-8") as file:
+        return parse_json(file.read())
+
+
+def writejson(path, value):
+    with open(path, "w", encoding="utf-8") as file:
+        file.write(_json.dumps(value, indent=2, sort_keys=True, ensure_ascii=False, allow_nan=False) + "\n")
+
+
+def format(value, digits):
+    return _show(value, int(digits))
+
+
+def pmap(function, values):
+    return list(map(function, values))
+
+
+# Sources of the imported modules by import path, and the namespaces of
+# those already run. Like in the interpreter, a module runs once and its
+# functions keep seeing its other functions and variables.
+_SOURCES = {}
+_MODULES = {}
+
+
+def _import(path, names, into):
+    if path not in _MODULES:
+        _MODULES[path] = None
+        module = dict(_RUNTIME)
+        exec(_SOURCES[path], module)
+        _MODULES[path] = module
+    module = _MODULES[path]
+    if module is None:
+        raise ImportError("Circular import of '%s'" % path)
+    exported = module.get("__all__")
+    defined = [name for name in module if name not in _RUNTIME and name != "__all__"]
+    if names is None:
+        names = defined if exported is None else exported
+    for name in names:
+        if name not in defined or (exported is not None and name not in exported):
+            raise ImportError("Module '%s' has no exported function or variable '%s'" % (path, name))
+        into[name] = module[name]
+
+
+def _assert(condition, message):
+    if not condition:
+        raise AssertionError("Assertion failed: " + _show(message))
+
+
+def assert_approx(actual, expected, tolerance):
+    if not abs(actual - expected) <= tolerance:
+        raise AssertionError("Assertion failed: %s is not within %s of %s" % (_show(actual), _show(tolerance), _show(expected)))
+
+
+def set_constant(name, value):
+    if name not in ("rd", "cp", "p0", "lv", "cw", "rho_air", "rho_water", "g", "omega", "rv", "sigma", "solar", "lf"):
+        raise ValueError("Cannot set unknown constant '%s'" % name)
+    if not value > 0:
+        raise ValueError("The constant '%s' must be above 0" % name)
+    globals()["_%s_" % name] = value
+
+
+def dewpoint(temperature, humidity, formula="magnus"):
+    if not 0 < humidity <= 100:
+        raise ValueError("Relative humidity must be above 0 and at most 100 percent")
+    if formula == "buck":
+        b, c, d = 18.678, 257.14, 234.5
+        gamma = _math.log(humidity / 100 * _math.exp((b - temperature / d) * (temperature / (c + temperature))))
+        return c * gamma / (b - gamma)
+    a, b = 17.27, 237.7
+    alpha = a * temperature / (b + temperature) + _math.log(humidity / 100)
+    return b * alpha / (a - alpha)
+
+
+def apparent_temp(temperature, humidity, wind):
+    vapour = humidity / 100 * 6.105 * _math.exp(17.27 * temperature / (237.7 + temperature))
+    return temperature + 0.33 * vapour - 0.70 * wind - 4.00
+
+
+def svp(temperature, formula="magnus", surface="water"):
+    ice = surface == "ice" and temperature < 0
+    if formula == "magnus":
+        if ice:
+            return 6.112 * _math.exp(22.46 * temperature / (272.62 + temperature))
+        return 6.112 * _math.exp(17.62 * temperature / (243.12 + temperature))
+    if formula == "buck":
+        if ice:
+            return 6.1115 * _math.exp((23.036 - temperature / 333.7) * (temperature / (279.82 + temperature)))
+        return 6.1121 * _math.exp((18.678 - temperature / 234.5) * (temperature / (257.14 + temperature)))
+    if formula == "goff_gratch":
+        if ice:
+            ratio = 273.16 / (temperature + 273.15)
+            return 10 ** (-9.09718 * (ratio - 1) - 3.56654 * _math.log10(ratio) + 0.876793 * (1 - 1 / ratio) + _math.log10(6.1071))
+        ratio = 373.16 / (temperature + 273.15)
+        return 10 ** (-7.90298 * (ratio - 1) + 5.02808 * _math.log10(ratio)
+                      - 1.3816e-7 * (10 ** (11.344 * (1 - 1 / ratio)) - 1)
+                      + 8.1328e-3 * (10 ** (-3.49149 * (ratio - 1)) - 1) + _math.log10(1013.246))
+    raise ValueError("Unknown saturation vapour pressure formula " + formula)
+
+
+def _vapour_pressure(temperature, humidity):
+    return humidity / 100 * svp(temperature)
+
+
+def mixing_ratio(temperature, humidity, pressure):
+    vapour = _vapour_pressure(temperature, humidity)
+    return 1000 * 0.622 * vapour / (pressure - vapour)
+
+
+def specific_humidity(temperature, humidity, pressure):
+    vapour = _vapour_pressure(temperature, humidity)
+    return 1000 * 0.622 * vapour / (pressure - 0.378 * vapour)
+
+
+def pressure_at_altitude(pressure, altitude, temperature):
+    return pressure * _math.exp(-_g_ * altitude / (_rd_ * (temperature + _kelvin_)))
+
+
+def isa(altitude):
+    if altitude > 20000:
+        raise ValueError("The standard atmosphere only covers altitudes up to 20000 m")
+    g, r, t0, lapse = 9.80665, 287.05287, 288.15, 0.0065
+    temperature = t0 - lapse * min(altitude, 11000)
+    pressure = 101325 * (temperature / t0) ** (g / (lapse * r))
+    if altitude > 11000:
+        pressure *= _math.exp(-g * (altitude - 11000) / (r * temperature))
+    return [temperature - _kelvin_, pressure / 100, pressure / (r * temperature)]
+
+
+def dalr():
+    return _g_ / _cp_ * 1000
+
+
+def malr(temperature, pressure):
+    lv = 2.501e6
+    kelvin = temperature + _kelvin_
+    ratio = mixing_ratio(temperature, 100, pressure) / 1000
+    return 1000 * _g_ * (1 + lv * ratio / (_rd_ * kelvin)) / (_cp_ + lv * lv * ratio * 0.622 / (_rd_ * kelvin * kelvin))
+
+
+def temp_along_dry_adiabat(temperature, start, end):
+    return (temperature + _kelvin_) * (end / start) ** (_rd_ / _cp_) - _kelvin_
+
+
+def et0(temperature, humidity, wind, net_radiation, pressure):
+    saturation = 0.6108 * _math.exp(17.27 * temperature / (temperature + 237.3))
+    actual = saturation * humidity / 100
+    slope = 4098 * saturation / (temperature + 237.3) ** 2
+    psychrometric = 0.665e-3 * pressure / 10
+    numerator = 0.408 * slope * net_radiation + psychrometric * 900 / (temperature + 273) * wind * (saturation - actual)
+    return numerator / (slope + psychrometric * (1 + 0.34 * wind))
+
+
+def hdd(temperatures, base):
+    return sum(max(base - temperature, 0) for temperature in temperatures)
+
+
+def cdd(temperatures, base):
+    return sum(max(temperature - base, 0) for temperature in temperatures)
+
+
+def wind_components(speed, direction):
+    radians = _math.radians(direction)
+    return [-speed * _math.sin(radians), -speed * _math.cos(radians)]
+
+
+def wind_from_components(u, v):
+    speed = _math.hypot(u, v)
+    if speed == 0:
+        return [0, 0]
+    return [speed, _math.degrees(_math.atan2(-u, -v)) % 360]
+
+
+def mmtoin(millimetres):
+    return millimetres / 25.4
+
+
+def intomm(inches):
+    return inches * 25.4
+
+
+mmhtoinh = mmtoin
+inhtommh = intomm
+
+
+def accumulation(rates, hours):
+    return sum(rate * hours for rate in rates)
+
+
+def running_total(amounts):
+    totals, total = [], 0
+    for amount in amounts:
+        total += amount
+        totals.append(total)
+    return totals
+
+
+def percentile(values, percent):
+    values = sorted(values)
+    rank = percent / 100 * (len(values) - 1)
+    lower = min(int(rank), len(values) - 1)
+    upper = min(lower + 1, len(values) - 1)
+    return values[lower] + (values[upper] - values[lower]) * (rank - lower)
+
+
+def percentile_rank(values, value):
+    return 100 * sum(1 for item in values if item <= value) / len(values)
+
+
+def anomaly(values, normals):
+    normals = normals if isinstance(normals, list) else [normals] * len(values)
+    return [value - normal for value, normal in zip(values, normals)]
+
+
+def rolling_mean(values, window):
+    length = len(values)
+    return [sum(values[(center + offset - window // 2) % length] for offset in range(window)) / window for center in range(length)]
+
+
+def _gamma_cdf(a, x):
+    if x <= 0:
+        return 0.0
+    prefactor = _math.exp(-x + a * _math.log(x) - _math.lgamma(a))
+    if x < a + 1:
+        term = total = 1 / a
+        for n in range(1, 1000):
+            term *= x / (a + n)
+            total += term
+            if abs(term) < abs(total) * 1e-15:
+                break
+        return total * prefactor
+    tiny = 1e-300
+    b = x + 1 - a
+    c, d = 1 / tiny, 1 / b
+    fraction = d
+    for i in range(1, 1000):
+        an = -i * (i - a)
+        b += 2
+        d = an * d + b
+        d = tiny if abs(d) < tiny else d
+        c = b + an / c
+        c = tiny if abs(c) < tiny else c
+        d = 1 / d
+        fraction *= d * c
+        if abs(d * c - 1) < 1e-15:
+            break
+    return 1 - prefactor * fraction
+
+
+def _normal_quantile(p):
+    if p <= 0:
+        return -_math.inf
+    if p >= 1:
+        return _math.inf
+
+    def deviate(q):
+        t = _math.sqrt(-2 * _math.log(q))
+        return t - (2.515517 + 0.802853 * t + 0.010328 * t * t) / (1 + 1.432788 * t + 0.189269 * t * t + 0.001308 * t * t * t)
+
+    return -deviate(p) if p < 0.5 else deviate(1 - p)
+
+
+def spi(precipitation, timescale):
+    totals = [_math.nan if month + 1 < timescale else sum(precipitation[month + 1 - timescale:month + 1])
+              for month in range(len(precipitation))]
+    result = [_math.nan] * len(totals)
+    for calendar_month in range(12):
+        months = [month for month in range(calendar_month, len(totals), 12) if not _math.isnan(totals[month])]
+        wet = [totals[month] for month in months if totals[month] > 0]
+        if len(wet) < 2:
+            continue
+        mean = sum(wet) / len(wet)
+        a = _math.log(mean) - sum(_math.log(total) for total in wet) / len(wet)
+        if a <= 0:
+            continue
+        shape = (1 + _math.sqrt(1 + 4 * a / 3)) / (4 * a)
+        scale = mean / shape
+        dry = (len(months) - len(wet)) / len(months)
+        for month in months:
+            result[month] = _normal_quantile(dry + (1 - dry) * _gamma_cdf(shape, totals[month] / scale))
+    return result
+
+
+def windrose(directions, speeds, sectors):
+    width = 360 / sectors
+    counts, totals = [0] * sectors, [0.0] * sectors
+    for direction, speed in zip(directions, speeds):
+        if speed == 0:
+            continue
+        sector = int((direction + width / 2) % 360 / width) % sectors
+        counts[sector] += 1
+        totals[sector] += speed
+    return [{"direction": sector * width, "count": counts[sector],
+             "frequency": 100 * counts[sector] / len(directions) if directions else 0,
+             "mean_speed": totals[sector] / counts[sector] if counts[sector] else _math.nan} for sector in range(sectors)]
+
+
+def events(series, threshold, min_length, direction="above"):
+    beyond = (lambda value: value < threshold) if direction == "below" else (lambda value: value > threshold)
+    found, start = [], 0
+    while start < len(series):
+        if not beyond(series[start]):
+            start += 1
+            continue
+        length = 0
+        while start + length < len(series) and beyond(series[start + length]):
+            length += 1
+        if length >= min_length:
+            run = series[start:start + length]
+            found.append({"start": start, "length": length, "peak": min(run) if direction == "below" else max(run)})
+        start += length
+    return found
+
+
+def slr(temperature):
+    below = 271.16 - (temperature + _kelvin_)
+    return max(12 + 2 * below if below < 0 else 12 + below, 0)
+
+
+def snowfall(liquid, temperature):
+    return liquid * slr(temperature)
+
+
+def visconvert(value, from_unit, to_unit):
+    metres = {"m": 1, "km": 1000, "mi": 1609.344}
+    return value * metres[from_unit] / metres[to_unit]
+
+
+def fog_category(visibility, humidity=None):
+    if visibility < 1000:
+        return "fog"
+    if visibility <= 5000:
+        return "haze" if humidity is not None and humidity < 80 else "mist"
+    return "clear"
+
+
+def _declination(year):
+    return (0.006918 - 0.399912 * _math.cos(year) + 0.070257 * _math.sin(year) - 0.006758 * _math.cos(2 * year)
+            + 0.000907 * _math.sin(2 * year) - 0.002697 * _math.cos(3 * year) + 0.00148 * _math.sin(3 * year))
+
+
+def solar_declination(day):
+    return _math.degrees(_declination(2 * _math.pi / 365 * (day - 1)))
+
+
+def rand():
+    return _random.random()
+
+
+def randint(lowest, highest):
+    if lowest != int(lowest) or highest != int(highest) or lowest > highest:
+        raise ValueError("randint expects two whole numbers, the first at most the second")
+    return _random.randint(int(lowest), int(highest))
+
+
+def randn(mean, deviation):
+    if deviation < 0:
+        raise ValueError("randn expects a standard deviation of at least 0")
+    return _random.gauss(mean, deviation)
+
+
+def seed(seed):
+    _random.seed(seed)
+
+
+def sleep(seconds):
+    import time as _time
+    if seconds < 0:
+        raise ValueError("The duration given to sleep must be zero or more")
+    _time.sleep(seconds)
+
+
+def _zoned(timestamp, zone):
+    import datetime as _datetime
+    import zoneinfo as _zoneinfo
+    try:
+        zone = _zoneinfo.ZoneInfo(zone)
+    except (_zoneinfo.ZoneInfoNotFoundError, ValueError):
+        raise ValueError("Unknown time zone '%s', expected an IANA name such as America/Denver" % zone)
+    return _datetime.datetime.fromtimestamp(_math.floor(timestamp), zone)
+
+
+def to_timezone(timestamp, zone):
+    return _zoned(timestamp, zone).isoformat()
+
+
+def utc_offset(timestamp, zone):
+    return _zoned(timestamp, zone).utcoffset().total_seconds() / 3600
+
+
+def solar_zenith(latitude, longitude, timestamp):
+    import datetime as _datetime
+    moment = _datetime.datetime.fromtimestamp(timestamp, _datetime.timezone.utc)
+    day = moment.timetuple().tm_yday
+    hours = moment.hour + moment.minute / 60 + (moment.second + moment.microsecond / 1e6) / 3600
+    length = 366 if moment.year % 4 == 0 and (moment.year % 100 != 0 or moment.year % 400 == 0) else 365
+    year = 2 * _math.pi / length * (day - 1 + (hours - 12) / 24)
+    equation = 229.18 * (0.000075 + 0.001868 * _math.cos(year) - 0.032077 * _math.sin(year)
+                         - 0.014615 * _math.cos(2 * year) - 0.040849 * _math.sin(2 * year))
+    hour_angle = _math.radians((hours * 60 + equation + 4 * longitude) / 4 - 180)
+    latitude, declination = _math.radians(latitude), _declination(year)
+    cosine = _math.sin(latitude) * _math.sin(declination) + _math.cos(latitude) * _math.cos(declination) * _math.cos(hour_angle)
+    return _math.degrees(_math.acos(max(-1, min(1, cosine))))
+
+
+def uv_index(zenith, ozone=300):
+    cosine = _math.cos(_math.radians(zenith))
+    if cosine <= 0:
+        return 0
+    return 12.5 * cosine ** 2.42 * (ozone / 300) ** -1.23
+
+
+def coriolis(latitude):
+    return 2 * _omega_ * _math.sin(_math.radians(latitude))
+
+
+def precipitable_water(pressures, ratios):
+    if len(pressures) != len(ratios) or len(pressures) < 2:
+        raise ValueError("precipitable_water expects at least two levels with one mixing ratio per pressure")
+    total = sum((ratios[level - 1] + ratios[level]) / 2 * abs(pressures[level - 1] - pressures[level]) for level in range(1, len(pressures)))
+    return total / (10 * _g_)
+
+
+def speed_of_sound(temperature, humidity):
+    virtual = (temperature + _kelvin_) / (1 - _vapour_pressure(temperature, humidity) / (_p0_ / 100) * 0.378)
+    return _math.sqrt(1.4 * _rd_ * virtual)
+
+
+def altimeter(pressure, elevation):
+    exponent = 0.190284
+    pressure -= 0.3
+    return pressure * (1 + 1013.25 ** exponent * 0.0065 / 288.15 * elevation / pressure ** exponent) ** (1 / exponent)
+
+
+def rh_from_wetbulb(dry, wet, pressure):
+    vapour = svp(wet) - 6.6e-4 * (1 + 0.00115 * wet) * pressure * (dry - wet)
+    return 100 * vapour / svp(dry)
+
+
+def haversine(lat1, lon1, lat2, lon2):
+    lat1, lat2 = _math.radians(lat1), _math.radians(lat2)
+    dlat, dlon = lat2 - lat1, _math.radians(lon2 - lon1)
+    a = _math.sin(dlat / 2) ** 2 + _math.cos(lat1) * _math.cos(lat2) * _math.sin(dlon / 2) ** 2
+    return 2 * 6371.0088 * _math.asin(min(1, _math.sqrt(a)))
+
+
+def bearing(lat1, lon1, lat2, lon2):
+    lat1, lat2 = _math.radians(lat1), _math.radians(lat2)
+    dlon = _math.radians(lon2 - lon1)
+    y = _math.sin(dlon) * _math.cos(lat2)
+    x = _math.cos(lat1) * _math.sin(lat2) - _math.sin(lat1) * _math.cos(lat2) * _math.cos(dlon)
+    return _math.degrees(_math.atan2(y, x)) % 360
+
+
+def parse_coord(text):
+    rest = text.strip()
+    hemisphere = None
+    if rest and rest[0] in "NSEWnsew":
+        hemisphere, rest = rest[0].upper(), rest[1:]
+    elif rest and rest[-1] in "NSEWnsew":
+        hemisphere, rest = rest[-1].upper(), rest[:-1]
+    rest = rest.strip()
+    negative = rest.startswith("-")
+    rest = rest[1:] if rest[:1] in "-+" else rest
+    if negative and hemisphere:
+        raise ValueError("'%s' is not a coordinate: it has both a sign and a hemisphere" % text)
+    for separator in "°º'\"′″:":
+        rest = rest.replace(separator, " ")
+    parts = [float(part) for part in rest.split()]
+    if not 1 <= len(parts) <= 3 or any(part < 0 for part in parts) or any(part >= 60 for part in parts[1:]):
+        raise ValueError("'%s' is not a coordinate" % text)
+    degrees = sum(part / 60 ** i for i, part in enumerate(parts))
+    if degrees > (90 if hemisphere in ("N", "S") else 180):
+        raise ValueError("'%s' is not a coordinate: it is out of range" % text)
+    return -degrees if negative or hemisphere in ("S", "W") else degrees
+
+
+def format_coord(latitude, longitude, style="dms"):
+    if style not in ("dd", "dm", "dms"):
+        raise ValueError("Unknown coordinate style '%s', expected one of dd, dm, dms" % style)
+    if not -90 <= latitude <= 90 or not -180 <= longitude <= 180:
+        raise ValueError("Coordinates out of range: %s, %s" % (latitude, longitude))
+
+    def one(value, positive, negative):
+        hemisphere = negative if value < 0 else positive
+        value = abs(value)
+        if style == "dd":
+            return "%.4f°%s" % (value, hemisphere)
+        if style == "dm":
+            hundredths = round(value * 6000)
+            return "%d°%02d.%02d'%s" % (hundredths // 6000, hundredths % 6000 // 100, hundredths % 100, hemisphere)
+        seconds = round(value * 3600)
+        return "%d°%02d'%02d\"%s" % (seconds // 3600, seconds % 3600 // 60, seconds % 60, hemisphere)
+
+    return one(latitude, "N", "S") + " " + one(longitude, "E", "W")
+
+
+def station(id):
+    id = "%05d" % id if isinstance(id, (int, float)) else id
+    for entry in _STATIONS.values():
+        if entry["icao"] == id.upper() or entry["wmo"] == id:
+            return dict(entry)
+    raise ValueError("Unknown station '%s'" % id)
+
+
+def cloudbase(temperature, dewpoint, unit="m"):
+    metres = (temperature - dewpoint) * 125
+    return metres / 0.3048 if unit == "ft" else metres
+
+
+def convert(value, from_unit, to_unit):
+    from_dimension, from_scale, from_offset = _UNITS[from_unit]
+    to_dimension, to_scale, to_offset = _UNITS[to_unit]
+    if from_dimension != to_dimension:
+        raise ValueError("Cannot convert %s in '%s' to %s in '%s'" % (from_dimension, from_unit, to_dimension, to_unit))
+    # Exact like the interpreter, so 32 °F is 0 °C rather than nearly 0
+    base = (_fractions.Fraction(value) + from_offset) * from_scale
+    if from_dimension == "temperature" and base < 0:
+        raise ValueError("The temperature given to convert must be above absolute zero")
+    return float(base / to_scale - to_offset)
+
+
+def ftoc(fahrenheit):
+    return (fahrenheit - 32) * 5 / 9
+
+
+def ctof(celsius):
+    return celsius * 9 / 5 + 32
+
+
+def ctok(celsius):
+    return celsius + _kelvin_
+
+
+def ktoc(kelvin):
+    return kelvin - _kelvin_
+
+
+def ftok(fahrenheit):
+    return ftoc(fahrenheit) + _kelvin_
+
+
+def ktof(kelvin):
+    return ctof(kelvin - _kelvin_)
+
+
+def isnan(value):
+    return isinstance(value, float) and _math.isnan(value)
+
+
+def pauli_x(qubit):
+    return 1 if qubit == 0 else 0
+
+
+def pauli_y(qubit):
+    return 1 if qubit == 0 else -1
+
+
+def pauli_z(qubit):
+    return qubit
+
+
+def hadamard(qubit):
+    return (qubit + 1) / 2
+
+
+def cnot(control, target):
+    return (1 if target == 0 else 0) if control == 1 else target
+
+
+def qubit(state, count):
+    result = 0
+    for _ in range(int(count)):
+        result = result * 2 + state
+    return result
+
+
+def measure(qubit):
+    return 0 if qubit == 0 else 1
+
+
+def reset_qubit(qubit):
+    return 0
+
+
+def toffoli(control1, control2, target):
+    return (1 if target == 0 else 0) if control1 == 1 and control2 == 1 else target
+
+
+def swap_qubits(qubit1, qubit2):
+    return qubit1 + qubit2 - qubit1 * qubit2 * 2
+
+
+def phase(qubit):
+    return -qubit
+
+
+def s_gate(qubit):
+    return qubit * 1j
+
+
+def t_gate(qubit):
+    return qubit * complex(_math.sqrt(2) / 2, _math.sqrt(2) / 2)
+
+
+"#;
+
+// Python keywords and builtins that are valid names in scripts; they get a
+// trailing underscore
+const PYTHON_RESERVED: &[&str] = &[
+    "False", "None", "True", "as", "async", "await", "break", "class", "continue", "def", "del", "elif",
+    "else", "except", "finally", "for", "from", "global", "in", "is", "lambda", "nonlocal", "pass",
+    "raise", "return", "try", "while", "with", "yield", "abs", "bool", "complex", "float", "int", "list",
+    "range", "repr", "str",
+];
+
+// Translates a script into an equivalent stand-alone Python 3 program.
+// Arithmetic uses Python floats instead of exact fractions, so results can
+// differ in the last digits. Imported modules are transpiled along with the
+// script, from `sources` (by import path, as `Bundle::collect` gives them)
+// or the standard library, and run in namespaces of their own.
+pub fn python(nodes: &[Node], sources: &HashMap<String, String>) -> String {
+    let mut nodes = nodes.to_vec();
+    nodes.iter_mut().for_each(|node| locate(node, None, sources));
+    let mut modules = BTreeMap::new();
+    let mut pending = imports(&nodes);
+    while let Some(path) = pending.pop() {
+        if modules.contains_key(&path) {
+            continue;
+        }
+        let source = sources.get(&path).map(String::as_str).or_else(|| stdlib::source(&path)).unwrap_or_else(|| panic!("Cannot find module '{}'.", path));
+        let mut module = Parser::new(Lexer::new(source.to_string())).parse();
+        let dir = import_dir(&path);
+        module.iter_mut().for_each(|node| locate(node, dir.as_deref(), sources));
+        pending.extend(imports(&module));
+        modules.insert(path, module);
+    }
+    let mut out = String::from(PYTHON_RUNTIME);
+    // The units of `convert` come from the interpreter's registry
+    out.push_str(&units::python_table());
+    out.push_str(&stations::python_table());
+    out.push_str("\n_RUNTIME = dict(globals())\n");
+    out.push_str("\n# End of runtime\n\n");
+    for (path, module) in &modules {
+        let mut code = String::new();
+        program(&mut code, module);
+        line(&mut out, 0, &format!("_SOURCES[{}] = {}", string(path), string(&code)));
+    }
+    if !modules.is_empty() {
+        out.push('\n');
+    }
+    program(&mut out, &nodes);
+    out
+}
+
+// Points each import at the path its module is stored under, found next to
+// the importer when it is there, like in a bundled executable
+fn locate(node: &mut Node, dir: Option<&str>, sources: &HashMap<String, String>) {
+    stack::grow(|| {
+        if let ASTNode::Import(name, _) = &mut node.node {
+            *name = locate_import(name, dir, |path| sources.contains_key(path));
+        }
+        for child in node.node.children_mut() {
+            locate(child, dir, sources);
+        }
+    })
+}
+
+// Modules imported anywhere in a script, except those from URLs, which are
+// not transpiled
+fn imports(nodes: &[Node]) -> Vec<String> {
+    fn walk(node: &Node, found: &mut Vec<String>) {
+        stack::grow(|| {
+            if let ASTNode::Import(name, _) = &node.node {
+                if !is_url(name) {
+                    found.push(name.clone());
+                }
+            }
+            node.node.children().into_iter().for_each(|child| walk(child, found));
+        })
+    }
+    let mut found = Vec::new();
+    nodes.iter().for_each(|node| walk(node, &mut found));
+    found
+}
+
+// Statements of a script or module, then the names it exports, which are all
+// that importing it without a list of names gives
+fn program(out: &mut String, nodes: &[Node]) {
+    for node in nodes {
+        statement(out, node, 0);
+    }
+    let exports: Vec<_> = nodes.iter().filter_map(|node| match &node.node {
+        ASTNode::Export(inner) => match &inner.node {
+            ASTNode::Function(exported, ..) | ASTNode::Assignment(exported, _) => Some(format!("\"{}\"", name(exported))),
+            _ => None,
+        },
+        _ => None,
+    }).collect();
+    if !exports.is_empty() {
+        line(out, 0, &format!("__all__ = [{}]", exports.join(", ")));
+    }
+}
+
+// Names that Python reserves, or that the runtime defines for a builtin
+// that is only one when called, get a trailing underscore
+fn name(name: &str) -> String {
+    if PYTHON_RESERVED.contains(&name) || call_name(name).is_some() {
+        format!("{}_", name)
+    } else {
+        name.to_string()
+    }
+}
+
+fn line(out: &mut String, depth: usize, text: &str) {
+    out.push_str(&INDENT.repeat(depth));
+    out.push_str(text);
+    out.push('\n');
+}
+
+fn block(out: &mut String, body: &Node, depth: usize) {
+    let start = out.len();
+    match &body.node {
+        ASTNode::Block(nodes) => nodes.iter().for_each(|node| statement(out, node, depth)),
+        _ => statement(out, body, depth),
+    }
+    if out.len() == start {
+        line(out, depth, "pass");
+    }
+}
+
+fn statement(out: &mut String, node: &Node, depth: usize) {
+    stack::grow(|| {
+        match &node.node {
+            ASTNode::Assignment(target, value) => line(out, depth, &format!("{} = {}", name(target), expression(value))),
+            ASTNode::Print(value) => line(out, depth, &format!("print({})", shown(value))),
+            ASTNode::If(condition, then_branch, else_branch) => {
+                line(out, depth, &format!("if {}:", expression(condition)));
+                block(out, then_branch, depth + 1);
+                if let Some(else_branch) = else_branch {
+                    line(out, depth, "else:");
+                    block(out, else_branch, depth + 1);
+                }
+            }
+            ASTNode::Function(function, params, body) => {
+                let params: Vec<_> = params.iter().map(|param| name(param)).collect();
+                line(out, depth, &format!("def {}({}):", name(function), params.join(", ")));
+                // A function works on a copy of the caller's variables, so names
+                // it both reads and assigns start from the script-level value
+                for variable in copied_variables(body, &params) {
+                    line(out, depth + 1, &format!("{0} = globals().get(\"{0}\")", variable));
+                }
+                block(out, body, depth + 1);
+            }
+            ASTNode::Block(_) => block(out, node, depth),
+            ASTNode::Export(inner) => statement(out, inner, depth),
+            ASTNode::Import(module, _) if is_url(module) => {
+                panic!("Cannot transpile the import of '{}' to Python on line {}, column {}.", module, node.span.start.line, node.span.start.column)
+            }
+            ASTNode::Import(module, names) => {
+                let names = match names {
+                    Some(names) => format!("[{}]", names.iter().map(|imported| string(&name(imported))).collect::<Vec<_>>().join(", ")),
+                    None => "None".to_string(),
+                };
+                line(out, depth, &format!("_import({}, {}, globals())", string(module), names));
+            }
+            ASTNode::Assert(condition, message) => {
+                line(out, depth, &format!("_assert({}, {})", expression(condition), expression(message)));
+            }
+            _ => line(out, depth, &expression(node)),
+        }
+    })
+}
+
+// Argument of `print`, converted to text the way the interpreter shows it
+fn shown(value: &Node) -> String {
+    match &value.node {
+        ASTNode::StringLiteral(_) => expression(value),
+        _ => format!("_show({})", expression(value)),
+    }
+}
+
+fn string(text: &str) -> String {
+    let escaped = text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\r', "\\r").replace('\t', "\\t");
+    format!("\"{}\"", escaped)
+}
+
+fn python_operator(op: &Token) -> &'static str {
+    match op {
+        Token::Not => "not ",
+        other => crate::format::operator(other),
+    }
+}
+
+// Operands are parenthesized whenever they are not atomic, so Python's
+// precedence rules never come into play
+fn operand(node: &Node) -> String {
+    match &node.node {
+        ASTNode::BinaryOp(..) | ASTNode::UnaryOp(..) | ASTNode::GreaterThan(..) | ASTNode::LessThan(..) => {
+            format!("({})", expression(node))
+        }
+        _ => expression(node),
+    }
+}
+
+fn arguments(args: &[&Node]) -> String {
+    args.iter().map(|arg| expression(arg)).collect::<Vec<_>>().join(", ")
+}
+
+fn expression(node: &Node) -> String {
+    stack::grow(|| {
+        match &node.node {
+            ASTNode::Float(value) => number(value),
+            ASTNode::Quantity(..) => {
+                panic!("Cannot transpile a number with a unit to Python on line {}, column {}.", node.span.start.line, node.span.start.column)
+            }
+            ASTNode::ReadGrib(..) | ASTNode::ReadNc(..) => {
+                panic!("Cannot transpile {} to Python on line {}, column {}.", node.node.keyword().unwrap_or_default(), node.span.start.line, node.span.start.column)
+            }
+            ASTNode::Identifier(variable) => name(variable),
+            ASTNode::StringLiteral(value) => string(value),
+            // `and`/`or` give 1 or 0 in scripts rather than one of the operands
+            ASTNode::BinaryOp(left, op @ (Token::And | Token::Or), right) => {
+                format!("bool({} {} {})", operand(left), python_operator(op), operand(right))
+            }
+            ASTNode::BinaryOp(left, op, right) => format!("{} {} {}", operand(left), python_operator(op), operand(right)),
+            ASTNode::UnaryOp(op, value) => format!("{}{}", python_operator(op), operand(value)),
+            ASTNode::Args => "args()".to_string(),
+            ASTNode::Dalr => "dalr()".to_string(),
+            ASTNode::Rand => "rand()".to_string(),
+            // Worked out when used, like in the interpreter, so it follows `set_constant`
+            ASTNode::Epsilon => "(_rd_ / _rv_)".to_string(),
+            ASTNode::Index(list, index) => format!("_index({}, {})", expression(list), expression(index)),
+            ASTNode::GreaterThan(left, right) => format!("{} > {}", operand(left), operand(right)),
+            ASTNode::LessThan(left, right) => format!("{} < {}", operand(left), operand(right)),
+            ASTNode::Call(function, args) => format!("{}({})", name(function), arguments(&args.iter().collect::<Vec<_>>())),
+            ASTNode::Pmap(function, list) => {
+                // Functions can't be named after builtins, so these are the builtin
+                let function = if call_name(function).is_some() { function.clone() } else { name(function) };
+                format!("pmap({}, {})", function, expression(list))
+            }
+            other => match other.keyword() {
+                Some(keyword) if other.children().is_empty() => keyword.to_string(),
+                Some(keyword) => format!("{}({})", keyword, arguments(&other.children())),
+                None => panic!("Cannot transpile {} to Python on line {}, column {}.", other.name(), node.span.start.line, node.span.start.column),
+            },
+        }
+    })
+}
+
+// Variables a function body assigns and also reads, excluding parameters
+// and nested functions
+fn copied_variables(body: &Node, params: &[String]) -> BTreeSet<String> {
+    fn walk(node: &Node, assigned: &mut BTreeSet<String>, read: &mut BTreeSet<String>) {
+        stack::grow(|| {
+            match &node.node {
+                ASTNode::Function(..) => return,
+                ASTNode::Assignment(target, _) => {
+                    assigned.insert(name(target));
+                }
+                ASTNode::Identifier(variable) => {
+                    read.insert(name(variable));
+                }
+                _ => {}
+            }
+            for child in node.node.children() {
+                walk(child, assigned, read);
+            }
+        })
+    }
+    let mut assigned = BTreeSet::new();
+    let mut read = BTreeSet::new();
+    walk(body, &mut assigned, &mut read);
+    assigned
+        .intersection(&read)
+        .filter(|variable| !params.contains(variable))
+        .cloned()
+        .collect()
+}
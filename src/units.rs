@@ -0,0 +1,288 @@
+use crate::value::Value;
+use num_rational::BigRational;
+
+// Powers of the base dimensions of a unit, e.g. speed is length / time and
+// pressure is mass / (length * time²)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Dimension {
+    length: i32,
+    mass: i32,
+    time: i32,
+    temperature: i32,
+}
+
+impl Dimension {
+    pub const NONE: Dimension = Dimension::new(0, 0, 0, 0);
+    pub const LENGTH: Dimension = Dimension::new(1, 0, 0, 0);
+    pub const TIME: Dimension = Dimension::new(0, 0, 1, 0);
+    pub const TEMPERATURE: Dimension = Dimension::new(0, 0, 0, 1);
+    pub const SPEED: Dimension = Dimension::new(1, 0, -1, 0);
+    pub const PRESSURE: Dimension = Dimension::new(-1, 1, -2, 0);
+
+    const fn new(length: i32, mass: i32, time: i32, temperature: i32) -> Self {
+        Self { length, mass, time, temperature }
+    }
+
+    fn times(self, other: Dimension, power: i32) -> Self {
+        Self::new(
+            self.length + other.length * power,
+            self.mass + other.mass * power,
+            self.time + other.time * power,
+            self.temperature + other.temperature * power,
+        )
+    }
+
+    // Name of a familiar dimension, otherwise its powers of the base ones
+    pub fn name(self) -> String {
+        let name = match self {
+            Dimension::NONE => "dimensionless",
+            Dimension::LENGTH => "length",
+            Dimension::TIME => "time",
+            Dimension::TEMPERATURE => "temperature",
+            Dimension::SPEED => "speed",
+            Dimension::PRESSURE => "pressure",
+            _ => {
+                let powers = [("length", self.length), ("mass", self.mass), ("time", self.time), ("temperature", self.temperature)];
+                let parts: Vec<String> = powers
+                    .iter()
+                    .filter(|(_, power)| *power != 0)
+                    .map(|(name, power)| if *power == 1 { name.to_string() } else { format!("{}^{}", name, power) })
+                    .collect();
+                return parts.join("*");
+            }
+        };
+        name.to_string()
+    }
+}
+
+pub struct Unit {
+    // Spellings accepted by `convert` and after numbers, the first one being
+    // the usual one
+    pub names: &'static [&'static str],
+    pub dimension: Dimension,
+    // A value in the unit is (value + offset) * scale in the SI base unit
+    // of its dimension (K, Pa, m/s, m or s). Both are exact (numerator,
+    // denominator) pairs so exact mode stays exact.
+    pub scale: (i64, i64),
+    pub offset: (i64, i64),
+}
+
+impl Unit {
+    // The usual spelling, which values in this unit are shown with
+    pub fn name(&self) -> &'static str {
+        self.names[0]
+    }
+
+    // Whether zero in this unit is not zero in the base unit, as for °C
+    pub fn has_offset(&self) -> bool {
+        self.offset.0 != 0
+    }
+}
+
+fn ratio((numer, denom): (i64, i64)) -> Value {
+    Value::ratio(numer, denom)
+}
+
+// Exact integer power
+fn power(value: Value, exponent: i32) -> Value {
+    let repeated = (0..exponent.unsigned_abs()).fold(Value::int(1), |product, _| product * value.clone());
+    if exponent < 0 { Value::int(1) / repeated } else { repeated }
+}
+
+const fn unit(names: &'static [&'static str], dimension: Dimension, scale: (i64, i64)) -> Unit {
+    Unit { names, dimension, scale, offset: (0, 1) }
+}
+
+// Units known to `convert` and written after number literals, as in `25C`
+// or `10m/s`
+pub const UNITS: &[Unit] = &[
+    unit(&["K", "kelvin"], Dimension::TEMPERATURE, (1, 1)),
+    Unit { names: &["degC", "C", "°C", "celsius"], dimension: Dimension::TEMPERATURE, scale: (1, 1), offset: (27315, 100) },
+    Unit { names: &["degF", "F", "°F", "fahrenheit"], dimension: Dimension::TEMPERATURE, scale: (5, 9), offset: (45967, 100) },
+    unit(&["Pa"], Dimension::PRESSURE, (1, 1)),
+    unit(&["hPa", "mb", "mbar"], Dimension::PRESSURE, (100, 1)),
+    unit(&["kPa"], Dimension::PRESSURE, (1000, 1)),
+    unit(&["bar"], Dimension::PRESSURE, (100000, 1)),
+    unit(&["atm"], Dimension::PRESSURE, (101325, 1)),
+    unit(&["inHg"], Dimension::PRESSURE, (3386389, 1000)),
+    unit(&["mmHg"], Dimension::PRESSURE, (133322387415, 1000000000)),
+    unit(&["torr"], Dimension::PRESSURE, (101325, 760)),
+    unit(&["psi"], Dimension::PRESSURE, (6894757293168, 1000000000)),
+    unit(&["m/s", "mps"], Dimension::SPEED, (1, 1)),
+    unit(&["km/h", "kph", "kmh"], Dimension::SPEED, (5, 18)),
+    unit(&["mph"], Dimension::SPEED, (44704, 100000)),
+    unit(&["kt", "kn", "knot", "knots"], Dimension::SPEED, (463, 900)),
+    unit(&["ft/s"], Dimension::SPEED, (3048, 10000)),
+    unit(&["m"], Dimension::LENGTH, (1, 1)),
+    unit(&["km"], Dimension::LENGTH, (1000, 1)),
+    unit(&["cm"], Dimension::LENGTH, (1, 100)),
+    unit(&["mm"], Dimension::LENGTH, (1, 1000)),
+    unit(&["mi"], Dimension::LENGTH, (1609344, 1000)),
+    unit(&["nmi"], Dimension::LENGTH, (1852, 1)),
+    unit(&["ft"], Dimension::LENGTH, (3048, 10000)),
+    unit(&["in"], Dimension::LENGTH, (254, 10000)),
+    unit(&["yd"], Dimension::LENGTH, (9144, 10000)),
+    // Precipitation rates are speeds too
+    unit(&["mm/h", "mm/hr"], Dimension::SPEED, (1, 3600000)),
+    unit(&["in/h", "in/hr"], Dimension::SPEED, (254, 36000000)),
+    unit(&["mm/day", "mm/d"], Dimension::SPEED, (1, 86400000)),
+    unit(&["in/day", "in/d"], Dimension::SPEED, (254, 864000000)),
+    unit(&["s"], Dimension::TIME, (1, 1)),
+    unit(&["min"], Dimension::TIME, (60, 1)),
+    unit(&["h", "hr"], Dimension::TIME, (3600, 1)),
+    unit(&["day"], Dimension::TIME, (86400, 1)),
+];
+
+pub fn find(name: &str) -> Option<&'static Unit> {
+    UNITS.iter().find(|unit| unit.names.contains(&name))
+}
+
+// Units multiplied together, each raised to a power, e.g. m/s is m¹·s⁻¹.
+// Quantities carry one, written as text such as `m/s` or `hPa*m^2/s`; an
+// empty product is no unit at all.
+#[derive(Clone, Default)]
+pub struct Compound {
+    factors: Vec<(&'static Unit, i32)>,
+}
+
+impl Compound {
+    pub fn single(unit: &'static Unit) -> Self {
+        Self { factors: vec![(unit, 1)] }
+    }
+
+    // A unit name, or names joined with `*` and `/` and raised to integer
+    // powers with `^`, as `name` writes them
+    pub fn parse(text: &str) -> Option<Self> {
+        if let Some(unit) = find(text) {
+            return Some(Self::single(unit));
+        }
+        let mut compound = Self::default();
+        let mut sign = 1;
+        let mut term = String::new();
+        for ch in text.chars().chain(std::iter::once('*')) {
+            if ch != '*' && ch != '/' {
+                term.push(ch);
+                continue;
+            }
+            let (name, exponent) = match term.split_once('^') {
+                Some((name, exponent)) => (name, exponent.parse::<i32>().ok()?),
+                None => (term.as_str(), 1),
+            };
+            // `1/s` has nothing above the line
+            if !(name == "1" && compound.factors.is_empty()) {
+                compound.factors.push((find(name)?, sign * exponent));
+            }
+            sign = if ch == '/' { -1 } else { 1 };
+            term.clear();
+        }
+        Some(compound)
+    }
+
+    pub fn name(&self) -> String {
+        let term = |unit: &Unit, exponent: i32| if exponent == 1 { unit.name().to_string() } else { format!("{}^{}", unit.name(), exponent) };
+        let above: Vec<String> = self.factors.iter().filter(|(_, exponent)| *exponent > 0).map(|(unit, exponent)| term(unit, *exponent)).collect();
+        let mut name = if above.is_empty() { "1".to_string() } else { above.join("*") };
+        for (unit, exponent) in self.factors.iter().filter(|(_, exponent)| *exponent < 0) {
+            name.push('/');
+            name.push_str(&term(unit, -exponent));
+        }
+        name
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.factors.is_empty()
+    }
+
+    pub fn dimension(&self) -> Dimension {
+        self.factors.iter().fold(Dimension::NONE, |dimension, (unit, exponent)| dimension.times(unit.dimension, *exponent))
+    }
+
+    // The unit of a compound that is just one unit, such as `degC`
+    pub fn unit(&self) -> Option<&'static Unit> {
+        match self.factors[..] {
+            [(unit, 1)] => Some(unit),
+            _ => None,
+        }
+    }
+
+    fn scale(&self) -> Value {
+        self.factors.iter().fold(Value::int(1), |scale, (unit, exponent)| scale * power(ratio(unit.scale), *exponent))
+    }
+
+    // A value in this unit in the base unit of its dimension, and back.
+    // Offsets only apply to a unit on its own: 25 °C is 298.15 K, but
+    // 25 °C/s is 25 K/s.
+    pub fn to_base(&self, value: Value) -> Value {
+        match self.unit() {
+            Some(unit) => (value + ratio(unit.offset)) * ratio(unit.scale),
+            None => value * self.scale(),
+        }
+    }
+
+    pub fn from_base(&self, value: Value) -> Value {
+        match self.unit() {
+            Some(unit) => value / ratio(unit.scale) - ratio(unit.offset),
+            None => value / self.scale(),
+        }
+    }
+
+    // A value in this unit in another unit of the same dimension. Floats are
+    // converted exactly and rounded once, so 77 °F is 25 °C rather than
+    // nearly 25.
+    pub fn convert(&self, value: Value, to: &Compound) -> Value {
+        match value {
+            Value::Float(float) => match BigRational::from_float(float) {
+                Some(exact) => Value::Float(to.from_base(self.to_base(exact.into())).to_f64()),
+                None => to.from_base(self.to_base(Value::Float(float))),
+            },
+            other => to.from_base(self.to_base(other)),
+        }
+    }
+
+    // The product of this unit and `other`, and the factor the product of
+    // the numbers must be multiplied by. A unit of `other` of a dimension
+    // already here is converted to the unit here, so km * m is 1000 m^2
+    // and km / mi is a plain number.
+    pub fn times(&self, other: &Compound) -> (Value, Compound) {
+        let mut factors = self.factors.clone();
+        let mut factor = Value::int(1);
+        for &(unit, exponent) in &other.factors {
+            match factors.iter_mut().find(|(existing, _)| existing.dimension == unit.dimension) {
+                Some((existing, existing_exponent)) => {
+                    factor = factor * power(ratio(unit.scale) / ratio(existing.scale), exponent);
+                    *existing_exponent += exponent;
+                }
+                None => factors.push((unit, exponent)),
+            }
+        }
+        factors.retain(|(_, exponent)| *exponent != 0);
+        (factor, Compound { factors })
+    }
+
+    pub fn powi(&self, exponent: i32) -> Compound {
+        let factors = self.factors.iter().map(|(unit, existing)| (*unit, existing * exponent)).filter(|(_, exponent)| *exponent != 0).collect();
+        Compound { factors }
+    }
+}
+
+// The registry as a Python dict from each spelling to (dimension, scale,
+// offset), for the transpiled `convert`
+pub fn python_table() -> String {
+    let entries: Vec<String> = UNITS
+        .iter()
+        .flat_map(|unit| {
+            unit.names.iter().map(move |name| {
+                format!(
+                    "    \"{}\": (\"{}\", _fractions.Fraction({}, {}), _fractions.Fraction({}, {})),",
+                    name,
+                    unit.dimension.name(),
+                    unit.scale.0,
+                    unit.scale.1,
+                    unit.offset.0,
+                    unit.offset.1
+                )
+            })
+        })
+        .collect();
+    format!("_UNITS = {{\n{}\n}}\n", entries.join("\n"))
+}
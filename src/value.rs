@@ -0,0 +1,452 @@
+use num_bigint::BigInt;
+use num_complex::Complex;
+use num_rational::BigRational;
+use num_traits::{ToPrimitive, Zero};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Value {
+    // Exact (possibly complex) number
+    Number(#[serde(with = "crate::serialize::complex")] Complex<BigRational>),
+    // Inexact number, used for NaN and +/-inf which BigRational can't represent
+    Float(#[serde(with = "crate::serialize::float")] f64),
+    Str(String),
+    List(Vec<Value>),
+    // Values by name, e.g. a station from `station()`; kept sorted by key
+    Map(BTreeMap<String, Value>),
+    // Number with a unit, from a literal such as `25C`; the unit is the
+    // usual spelling of one in `units::UNITS`
+    Quantity(Box<Value>, String),
+    // No value, e.g. an unset environment variable
+    Nil,
+    // Never written out, so secrets don't end up in saved sessions
+    #[serde(skip)]
+    Secret(Secret),
+}
+
+// How number literals and constants are represented. `F64` is fast;
+// `Exact` keeps BigRationals until a formula needs a float.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Numeric {
+    #[default]
+    F64,
+    Exact,
+}
+
+impl FromStr for Numeric {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "f64" => Ok(Numeric::F64),
+            "exact" => Ok(Numeric::Exact),
+            _ => Err(format!("expected 'f64' or 'exact', found '{}'", text)),
+        }
+    }
+}
+
+// A string, such as an API key, that is never shown: it displays as
+// `<secret>` when printed, traced or inspected in the debugger
+#[derive(Clone, PartialEq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: String) -> Self {
+        Self(value)
+    }
+
+    // The actual value, for builtins that send it somewhere
+    pub fn reveal(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Secret(<redacted>)")
+    }
+}
+
+impl Value {
+    pub fn int(value: i64) -> Self {
+        Value::Number(BigRational::from_integer(BigInt::from(value)).into())
+    }
+
+    pub fn ratio(numer: i64, denom: i64) -> Self {
+        Value::Number(BigRational::new(BigInt::from(numer), BigInt::from(denom)).into())
+    }
+
+    pub fn bool(value: bool) -> Self {
+        Value::int(if value { 1 } else { 0 })
+    }
+
+    // Finite floats are stored exactly; NaN and +/-inf stay as floats
+    pub fn from_f64(value: f64) -> Self {
+        match BigRational::from_float(value) {
+            Some(rational) => Value::Number(rational.into()),
+            None => Value::Float(value),
+        }
+    }
+
+    // Result of a float computation in the given numeric mode
+    pub fn float(value: f64, numeric: Numeric) -> Self {
+        match numeric {
+            Numeric::F64 => Value::Float(value),
+            Numeric::Exact => Value::from_f64(value),
+        }
+    }
+
+    // Literal or constant in the given numeric mode
+    pub fn number(value: &BigRational, numeric: Numeric) -> Self {
+        match numeric {
+            Numeric::F64 => Value::Float(value.to_f64().unwrap_or(f64::NAN)),
+            Numeric::Exact => Value::Number(value.clone().into()),
+        }
+    }
+
+    // Script argument: numbers are parsed (exactly for integers), anything
+    // else stays a string
+    pub fn parse(text: &str) -> Self {
+        if let Ok(integer) = text.parse::<BigInt>() {
+            return Value::Number(BigRational::from_integer(integer).into());
+        }
+        match text.parse::<f64>() {
+            Ok(value) if value.is_finite() => Value::from_f64(value),
+            _ => Value::Str(text.to_string()),
+        }
+    }
+
+    // JSON null is nil, true and false are 1 and 0, integers are exact and
+    // objects are maps
+    pub fn from_json(json: serde_json::Value) -> Self {
+        match json {
+            serde_json::Value::Null => Value::Nil,
+            serde_json::Value::Bool(value) => Value::bool(value),
+            serde_json::Value::Number(number) => match (number.as_i64(), number.as_u64()) {
+                (Some(integer), _) => Value::int(integer),
+                (None, Some(integer)) => Value::Number(BigRational::from_integer(integer.into()).into()),
+                _ => Value::from_f64(number.as_f64().unwrap_or(f64::NAN)),
+            },
+            serde_json::Value::String(text) => Value::Str(text),
+            serde_json::Value::Array(items) => Value::List(items.into_iter().map(Value::from_json).collect()),
+            serde_json::Value::Object(entries) => Value::Map(entries.into_iter().map(|(key, value)| (key, Value::from_json(value))).collect()),
+        }
+    }
+
+    // Numbers without a JSON equivalent (complex, NaN, infinite or with a
+    // unit) and secrets are errors
+    pub fn to_json(&self) -> Result<serde_json::Value, String> {
+        Ok(match self {
+            Value::Number(value) if !value.im.is_zero() => return Err(format!("Cannot write the complex number {} as JSON", self)),
+            Value::Number(value) if value.re.is_integer() => {
+                let integer = value.re.to_integer();
+                match (integer.to_i64(), integer.to_u64()) {
+                    (Some(integer), _) => integer.into(),
+                    (None, Some(integer)) => integer.into(),
+                    _ => json_float(value.re.to_f64().unwrap_or(f64::NAN))?,
+                }
+            }
+            Value::Number(value) => json_float(value.re.to_f64().unwrap_or(f64::NAN))?,
+            Value::Float(value) => json_float(*value)?,
+            Value::Str(text) => text.clone().into(),
+            Value::List(items) => serde_json::Value::Array(items.iter().map(Value::to_json).collect::<Result<_, _>>()?),
+            Value::Map(entries) => serde_json::Value::Object(entries.iter().map(|(key, value)| Ok((key.clone(), value.to_json()?))).collect::<Result<_, String>>()?),
+            Value::Quantity(..) => return Err(format!("Cannot write {} as JSON, convert it to a plain number first", self)),
+            Value::Nil => serde_json::Value::Null,
+            Value::Secret(_) => return Err("Cannot write a secret as JSON".to_string()),
+        })
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) | Value::Float(_) => "number",
+            Value::Str(_) => "string",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Quantity(..) => "quantity",
+            Value::Nil => "nil",
+            Value::Secret(_) => "secret",
+        }
+    }
+
+    pub fn is_number(&self) -> bool {
+        matches!(self, Value::Number(_) | Value::Float(_) | Value::Quantity(..))
+    }
+
+    // Real part as f64 (the imaginary part of exact numbers is dropped);
+    // quantities give the number without their unit
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Value::Number(value) => value.re.to_f64().unwrap_or(f64::NAN),
+            Value::Float(value) => *value,
+            Value::Quantity(value, _) => value.to_f64(),
+            other => panic!("Expected a number, found a {}.", other.type_name()),
+        }
+    }
+
+    pub fn to_usize(&self) -> Option<usize> {
+        match self {
+            Value::Number(value) => value.re.to_usize(),
+            Value::Float(value) if value.fract() == 0.0 => value.to_usize(),
+            _ => None,
+        }
+    }
+
+    pub fn is_nan(&self) -> bool {
+        match self {
+            Value::Float(value) => value.is_nan(),
+            Value::Quantity(value, _) => value.is_nan(),
+            _ => false,
+        }
+    }
+
+    // Anything but zero is truthy, including NaN (like Python)
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Number(value) => !value.is_zero(),
+            Value::Float(value) => *value != 0.0,
+            Value::Str(value) => !value.is_empty(),
+            Value::List(values) => !values.is_empty(),
+            Value::Map(entries) => !entries.is_empty(),
+            Value::Quantity(value, _) => value.is_truthy(),
+            Value::Nil => false,
+            Value::Secret(value) => !value.reveal().is_empty(),
+        }
+    }
+
+    // Text of the value with numbers rounded to `digits` significant digits,
+    // as `print` shows it
+    pub fn rounded(&self, digits: usize) -> String {
+        match self {
+            Value::Number(value) if value.im.is_zero() => round(value.re.to_f64().unwrap(), digits).to_string(),
+            Value::Number(value) => format!("{}{:+}i", round(value.re.to_f64().unwrap(), digits), round(value.im.to_f64().unwrap(), digits)),
+            Value::Float(value) => round(*value, digits).to_string(),
+            Value::List(values) => {
+                let items: Vec<String> = values
+                    .iter()
+                    .map(|value| match value {
+                        Value::Str(text) => format!("{:?}", text),
+                        other => other.rounded(digits),
+                    })
+                    .collect();
+                format!("[{}]", items.join(", "))
+            }
+            Value::Map(entries) => {
+                let items: Vec<String> = entries
+                    .iter()
+                    .map(|(key, value)| match value {
+                        Value::Str(text) => format!("{:?}: {:?}", key, text),
+                        other => format!("{:?}: {}", key, other.rounded(digits)),
+                    })
+                    .collect();
+                format!("{{{}}}", items.join(", "))
+            }
+            Value::Quantity(value, unit) => format!("{} {}", value.rounded(digits), unit),
+            other => other.to_string(),
+        }
+    }
+
+    pub fn pow(&self, exponent: &Value, numeric: Numeric) -> Value {
+        Value::float(self.to_f64().powf(exponent.to_f64()), numeric)
+    }
+
+    // Integer remainder; a zero divisor gives NaN
+    pub fn modulo(&self, other: &Value) -> Value {
+        match (self, other) {
+            (Value::Number(left), Value::Number(right)) if !right.re.to_integer().is_zero() => {
+                Value::Number(BigRational::from_integer(left.re.to_integer() % right.re.to_integer()).into())
+            }
+            (Value::Number(_), Value::Number(_)) => Value::Float(f64::NAN),
+            _ => Value::Float(self.to_f64().trunc() % other.to_f64().trunc()),
+        }
+    }
+}
+
+impl From<BigRational> for Value {
+    fn from(value: BigRational) -> Self {
+        Value::Number(value.into())
+    }
+}
+
+impl From<Complex<BigRational>> for Value {
+    fn from(value: Complex<BigRational>) -> Self {
+        Value::Number(value)
+    }
+}
+
+// Numeric equality: exact numbers compare exactly, anything involving a float
+// compares as f64 (so NaN is never equal to anything). Values of different
+// types are never equal.
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Number(left), Value::Number(right)) => left == right,
+            (Value::Str(left), Value::Str(right)) => left == right,
+            (Value::List(left), Value::List(right)) => left == right,
+            (Value::Map(left), Value::Map(right)) => left == right,
+            (Value::Quantity(left, left_unit), Value::Quantity(right, right_unit)) => left_unit == right_unit && left == right,
+            (Value::Nil, Value::Nil) => true,
+            (Value::Secret(left), Value::Secret(right)) => left == right,
+            _ if self.is_number() && other.is_number() => self.to_f64() == other.to_f64(),
+            _ => false,
+        }
+    }
+}
+
+// Numbers are ordered by their real part and strings alphabetically
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Number(left), Value::Number(right)) => left.re.partial_cmp(&right.re),
+            (Value::Str(left), Value::Str(right)) => left.partial_cmp(right),
+            (Value::Quantity(left, left_unit), Value::Quantity(right, right_unit)) if left_unit == right_unit => left.partial_cmp(right),
+            _ if self.is_number() && other.is_number() => self.to_f64().partial_cmp(&other.to_f64()),
+            _ => None,
+        }
+    }
+}
+
+fn json_float(value: f64) -> Result<serde_json::Value, String> {
+    serde_json::Number::from_f64(value).map(serde_json::Value::Number).ok_or_else(|| format!("Cannot write {} as JSON", value))
+}
+
+// Rounds to `digits` significant digits; the result prints without the
+// trailing noise of the full value, e.g. 37 rather than 36.99999999999999
+fn round(value: f64, digits: usize) -> f64 {
+    if !value.is_finite() {
+        return value;
+    }
+    format!("{:.*e}", digits.saturating_sub(1), value).parse().unwrap_or(value)
+}
+
+// Both operands as exact numbers when one is complex and the other a finite
+// float, so f64 arithmetic doesn't drop the imaginary part
+fn exact_pair(left: &Value, right: &Value) -> Option<(Complex<BigRational>, Complex<BigRational>)> {
+    let exact = |value: &Value| match value {
+        Value::Number(number) => Some(number.clone()),
+        Value::Float(float) => BigRational::from_float(*float).map(Complex::from),
+        _ => None,
+    };
+    let complex = |value: &Value| matches!(value, Value::Number(number) if !number.im.is_zero());
+    if complex(left) || complex(right) {
+        Some((exact(left)?, exact(right)?))
+    } else {
+        None
+    }
+}
+
+impl Neg for Value {
+    type Output = Value;
+
+    fn neg(self) -> Value {
+        match self {
+            Value::Number(value) => Value::Number(-value),
+            Value::Float(value) => Value::Float(-value),
+            Value::Quantity(value, unit) => Value::Quantity(Box::new(-*value), unit),
+            other => panic!("Cannot negate a {}.", other.type_name()),
+        }
+    }
+}
+
+impl Add for Value {
+    type Output = Value;
+
+    fn add(self, other: Value) -> Value {
+        match (self, other) {
+            (Value::Number(left), Value::Number(right)) => Value::Number(left + right),
+            (left, right) => match exact_pair(&left, &right) {
+                Some((left, right)) => Value::Number(left + right),
+                None => Value::Float(left.to_f64() + right.to_f64()),
+            },
+        }
+    }
+}
+
+impl Sub for Value {
+    type Output = Value;
+
+    fn sub(self, other: Value) -> Value {
+        match (self, other) {
+            (Value::Number(left), Value::Number(right)) => Value::Number(left - right),
+            (left, right) => match exact_pair(&left, &right) {
+                Some((left, right)) => Value::Number(left - right),
+                None => Value::Float(left.to_f64() - right.to_f64()),
+            },
+        }
+    }
+}
+
+impl Mul for Value {
+    type Output = Value;
+
+    fn mul(self, other: Value) -> Value {
+        match (self, other) {
+            (Value::Number(left), Value::Number(right)) => Value::Number(left * right),
+            (left, right) => match exact_pair(&left, &right) {
+                Some((left, right)) => Value::Number(left * right),
+                None => Value::Float(left.to_f64() * right.to_f64()),
+            },
+        }
+    }
+}
+
+impl Div for Value {
+    type Output = Value;
+
+    // Division by exact zero follows IEEE 754: x/0 is +/-inf and 0/0 is NaN
+    fn div(self, other: Value) -> Value {
+        match (self, other) {
+            (Value::Number(left), Value::Number(right)) if !right.is_zero() => Value::Number(left / right),
+            (left, right) => match exact_pair(&left, &right) {
+                Some((left, right)) if !right.is_zero() => Value::Number(left / right),
+                _ => Value::Float(left.to_f64() / right.to_f64()),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Number(value) if value.im.is_zero() => write!(f, "{}", value.re.to_f64().unwrap()),
+            Value::Number(value) => write!(f, "{}{:+}i", value.re.to_f64().unwrap(), value.im.to_f64().unwrap()),
+            Value::Float(value) => write!(f, "{}", value),
+            Value::Str(value) => write!(f, "{}", value),
+            Value::Quantity(value, unit) => write!(f, "{} {}", value, unit),
+            Value::Nil => write!(f, "nil"),
+            Value::Secret(_) => write!(f, "<secret>"),
+            Value::List(values) => {
+                write!(f, "[")?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    match value {
+                        Value::Str(text) => write!(f, "{:?}", text)?,
+                        other => write!(f, "{}", other)?,
+                    }
+                }
+                write!(f, "]")
+            }
+            Value::Map(entries) => {
+                write!(f, "{{")?;
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    match value {
+                        Value::Str(text) => write!(f, "{:?}: {:?}", key, text)?,
+                        other => write!(f, "{:?}: {}", key, other)?,
+                    }
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
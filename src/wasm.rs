@@ -0,0 +1,81 @@
+use crate::error::{self, panic_message, ErrorKind};
+use crate::{Interpreter, Lexer, Parser};
+use js_sys::Function;
+use std::cell::RefCell;
+use std::panic;
+use std::sync::{Arc, Mutex, Once};
+use wasm_bindgen::prelude::*;
+
+thread_local! {
+    // A panic aborts the module on wasm32 instead of unwinding, so the hook
+    // keeps the error message here for JavaScript to read
+    static LAST_ERROR: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+// JavaScript callback for printed lines. The wasm32 build runs on a single
+// thread, so the function never actually crosses threads.
+struct PrintCallback(Function);
+
+unsafe impl Send for PrintCallback {}
+unsafe impl Sync for PrintCallback {}
+
+static HOOK: Once = Once::new();
+
+// Installs the hook that keeps panic messages, once, in front of whatever
+// hook the embedding page set, which still runs
+fn install_hook() {
+    HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let message = panic_message(info.payload()).to_string();
+            LAST_ERROR.with(|error| *error.borrow_mut() = Some(message));
+            previous(info);
+        }));
+    });
+}
+
+fn run_with_output(source: &str, output: impl Fn(&str) + Send + Sync + 'static) -> Result<(), String> {
+    install_hook();
+    LAST_ERROR.with(|error| error.borrow_mut().take());
+    let nodes = error::catch(ErrorKind::Parse, || Parser::new(Lexer::new(source.to_string())).parse())
+        .map_err(|error| error.message)?;
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(output);
+    error::catch(ErrorKind::Runtime, || interpreter.interpret(nodes)).map_err(|error| error.message)
+}
+
+// Runs a script and returns everything it printed, followed by an
+// `error: ...` line if it failed
+#[wasm_bindgen]
+pub fn run(source: &str) -> String {
+    let printed = Arc::new(Mutex::new(String::new()));
+    let sink = printed.clone();
+    let result = run_with_output(source, move |line| {
+        let mut printed = sink.lock().unwrap();
+        printed.push_str(line);
+        printed.push('\n');
+    });
+    let mut printed = printed.lock().unwrap().clone();
+    if let Err(message) = result {
+        printed.push_str(&format!("error: {}\n", message));
+    }
+    printed
+}
+
+// Runs a script, calling `onPrint` with each line as it is printed. Throws
+// the error message if the script fails.
+#[wasm_bindgen(js_name = runStreaming)]
+pub fn run_streaming(source: &str, on_print: Function) -> Result<(), JsValue> {
+    let callback = PrintCallback(on_print);
+    run_with_output(source, move |line| {
+        let _ = callback.0.call1(&JsValue::NULL, &JsValue::from_str(line));
+    })
+    .map_err(|message| JsValue::from_str(&message))
+}
+
+// Message of the error that stopped the last script, for when the module
+// aborted before `run` or `runStreaming` could return it
+#[wasm_bindgen(js_name = lastError)]
+pub fn last_error() -> Option<String> {
+    LAST_ERROR.with(|error| error.borrow().clone())
+}